@@ -1,12 +1,42 @@
-//! Admin and config: init, min_topup, batch_charge.
+//! Admin and config: init, min_topup, batch_charge, schedule correction, migration
+//! export/import, upgrades.
 //!
 //! **PRs that only change admin or batch behavior should edit this file only.**
 
 use crate::charge_core::charge_one;
-use crate::types::{BatchChargeResult, Error};
-use soroban_sdk::{Address, Env, Symbol, Vec};
+use crate::dispute::get_dispute;
+use crate::history::record_status;
+use crate::index::add_to_indexes;
+use crate::multisig::{action_hash, require_approved};
+use crate::queries::get_subscription;
+use crate::subscription::{adjust_total_prepaid, next_event_seq, next_id};
+use crate::types::{
+    BatchChargeResult, Error, InitializedEvent, ScheduleCorrectedEvent, Subscription,
+    SubscriptionStatus,
+};
+use soroban_sdk::{symbol_short, token, Address, BytesN, Env, Symbol, Vec};
 
-pub fn do_init(env: &Env, token: Address, admin: Address, min_topup: i128) -> Result<(), Error> {
+/// Version of this contract code. Stored versions start here and grow by one per `upgrade`.
+pub const CONTRACT_VERSION: u32 = 1;
+
+pub fn do_init(
+    env: &Env,
+    token: Address,
+    admin: Address,
+    min_topup: i128,
+    require_registration: bool,
+) -> Result<(), Error> {
+    if env.storage().instance().has(&Symbol::new(env, "admin")) {
+        return Err(Error::AlreadyInitialized);
+    }
+    if min_topup < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    // Amounts are in token base units; record the scale so clients can check their pricing.
+    let decimals = token::Client::new(env, &token).decimals();
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "decimals"), &decimals);
     env.storage()
         .instance()
         .set(&Symbol::new(env, "token"), &token);
@@ -16,9 +46,46 @@ pub fn do_init(env: &Env, token: Address, admin: Address, min_topup: i128) -> Re
     env.storage()
         .instance()
         .set(&Symbol::new(env, "min_topup"), &min_topup);
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "req_reg"), &require_registration);
+    env.events().publish(
+        (symbol_short!("init"),),
+        InitializedEvent {
+            token,
+            admin,
+            min_topup,
+            require_registration,
+        },
+    );
+    extend_storage_ttl(env);
     Ok(())
 }
 
+/// `decimals()` of the default token, read from the token contract at `init`.
+pub fn get_token_decimals(env: &Env) -> Result<u32, Error> {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "decimals"))
+        .ok_or(Error::NotInitialized)
+}
+
+/// Whether merchants must `register_merchant` before subscriptions can name them (set at init).
+pub fn get_require_registration(env: &Env) -> bool {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "req_reg"))
+        .unwrap_or(false)
+}
+
+/// Admin address set at `init`.
+pub fn get_admin(env: &Env) -> Result<Address, Error> {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "admin"))
+        .ok_or(Error::NotInitialized)
+}
+
 pub fn require_admin(env: &Env) -> Result<Address, Error> {
     env.storage()
         .instance()
@@ -26,12 +93,23 @@ pub fn require_admin(env: &Env) -> Result<Address, Error> {
         .ok_or(Error::Unauthorized)
 }
 
+/// Default payment token set at `init`; used when a subscription does not name its own token.
+pub fn get_token(env: &Env) -> Result<Address, Error> {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "token"))
+        .ok_or(Error::NotInitialized)
+}
+
 pub fn do_set_min_topup(env: &Env, admin: Address, min_topup: i128) -> Result<(), Error> {
     admin.require_auth();
     let stored = require_admin(env)?;
     if admin != stored {
         return Err(Error::Unauthorized);
     }
+    if min_topup < 0 {
+        return Err(Error::InvalidAmount);
+    }
     env.storage()
         .instance()
         .set(&Symbol::new(env, "min_topup"), &min_topup);
@@ -45,6 +123,132 @@ pub fn get_min_topup(env: &Env) -> Result<i128, Error> {
         .ok_or(Error::NotFound)
 }
 
+/// Sets the grace period given to subscriptions created without their own `grace_seconds`.
+pub fn do_set_default_grace_seconds(
+    env: &Env,
+    admin: Address,
+    grace_seconds: u64,
+) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "grace_secs"), &grace_seconds);
+    Ok(())
+}
+
+/// Global default grace period in seconds (0 if never configured).
+pub fn get_default_grace_seconds(env: &Env) -> u64 {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "grace_secs"))
+        .unwrap_or(0)
+}
+
+/// Caps how many non-cancelled subscriptions one subscriber may hold. Zero removes the cap.
+pub fn do_set_max_subs_per_subscriber(env: &Env, admin: Address, max: u32) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "max_subs"), &max);
+    Ok(())
+}
+
+/// Per-subscriber subscription cap (0 = unlimited, the default).
+pub fn get_max_subs_per_subscriber(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "max_subs"))
+        .unwrap_or(0)
+}
+
+/// Sets the dunning limit: after this many consecutive unfunded attempts a subscription is
+/// cancelled. Zero disables dunning.
+pub fn do_set_max_retries(env: &Env, admin: Address, max_retries: u32) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "max_retries"), &max_retries);
+    Ok(())
+}
+
+/// Dunning limit (0 = disabled, the default).
+pub fn get_max_retries(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "max_retries"))
+        .unwrap_or(0)
+}
+
+/// When set, `resume_subscription` immediately takes a charge that fell due during the pause,
+/// if the balance covers it.
+pub fn do_set_auto_charge_on_resume(env: &Env, admin: Address, enabled: bool) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "resume_chg"), &enabled);
+    Ok(())
+}
+
+pub fn get_auto_charge_on_resume(env: &Env) -> bool {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "resume_chg"))
+        .unwrap_or(false)
+}
+
+/// Default TTL policy in ledgers (about 30 and 180 days at 5-second ledgers).
+pub const DEFAULT_TTL_THRESHOLD: u32 = 518_400;
+pub const DEFAULT_TTL_EXTEND_TO: u32 = 3_110_400;
+
+/// `(threshold, extend_to)` in ledgers: once the contract's storage has at most `threshold`
+/// ledgers to live, an access extends it to `extend_to`.
+pub fn get_ttl_config(env: &Env) -> (u32, u32) {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "ttl_cfg"))
+        .unwrap_or((DEFAULT_TTL_THRESHOLD, DEFAULT_TTL_EXTEND_TO))
+}
+
+/// Sets the TTL policy applied by [`extend_storage_ttl`]. Admin only. `extend_to` must be
+/// positive and at least `threshold` (`InvalidAmount`).
+pub fn do_set_ttl_config(
+    env: &Env,
+    admin: Address,
+    threshold: u32,
+    extend_to: u32,
+) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    if extend_to == 0 || threshold > extend_to {
+        return Err(Error::InvalidAmount);
+    }
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "ttl_cfg"), &(threshold, extend_to));
+    Ok(())
+}
+
+/// Keeps the contract's storage, which holds every subscription, from being archived while it is
+/// in use. Called on init, deposits, charges and `get_subscription`.
+pub fn extend_storage_ttl(env: &Env) {
+    let (threshold, extend_to) = get_ttl_config(env);
+    env.storage().instance().extend_ttl(threshold, extend_to);
+}
+
 pub fn do_batch_charge(
     env: &Env,
     subscription_ids: &Vec<u32>,
@@ -69,3 +273,119 @@ pub fn do_batch_charge(
     }
     Ok(results)
 }
+
+/// Returns the stored subscription for migration to another deployment. Admin only.
+pub fn do_export_subscription(
+    env: &Env,
+    admin: Address,
+    subscription_id: u32,
+) -> Result<Subscription, Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    get_subscription(env, subscription_id)
+}
+
+/// Stores an exported subscription under the next id and returns it. Admin only, and only while
+/// the instance is fresh: once `create_subscription` has been used, imports fail with
+/// [`Error::NotEmpty`] so migrated ids cannot interleave with native ones.
+///
+/// Only the `Subscription` record moves; operators must transfer the backing prepaid tokens to
+/// this contract separately.
+pub fn do_import_subscription(env: &Env, admin: Address, sub: Subscription) -> Result<u32, Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    if env.storage().instance().has(&Symbol::new(env, "created")) {
+        return Err(Error::NotEmpty);
+    }
+    // Charge paths trust `amount` and the balance; a native subscription was validated at creation.
+    if sub.amount <= 0 || sub.prepaid_balance < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    // Charge paths divide by the interval.
+    if sub.interval_seconds == 0 {
+        return Err(Error::InvalidInterval);
+    }
+    adjust_total_prepaid(env, &sub.token, sub.prepaid_balance)?;
+    let id = next_id(env);
+    env.storage().instance().set(&id, &sub);
+    add_to_indexes(env, id, &sub);
+    record_status(env, id, sub.status.clone());
+    Ok(id)
+}
+
+/// Deletes a `Cancelled` subscription's record to reclaim storage. Admin only. Owner indexes
+/// keep the id until `compact_index`; the id is never reused. A subscription with an open
+/// dispute is kept ([`Error::DisputeOpen`]) so the dispute can still be resolved.
+pub fn do_purge_subscription(env: &Env, admin: Address, subscription_id: u32) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    let sub = get_subscription(env, subscription_id)?;
+    if sub.status != SubscriptionStatus::Cancelled {
+        return Err(Error::InvalidStatusTransition);
+    }
+    if get_dispute(env, subscription_id).is_some() {
+        return Err(Error::DisputeOpen);
+    }
+    env.storage().instance().remove(&subscription_id);
+    Ok(())
+}
+
+/// Admin correction of the billing timer: sets `last_payment_timestamp` directly without
+/// charging, so the next charge becomes due one interval after `timestamp`.
+pub fn do_set_last_payment(
+    env: &Env,
+    admin: Address,
+    subscription_id: u32,
+    timestamp: u64,
+) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    let mut sub = get_subscription(env, subscription_id)?;
+    let previous_timestamp = sub.last_payment_timestamp;
+    sub.last_payment_timestamp = timestamp;
+    env.storage().instance().set(&subscription_id, &sub);
+    env.events().publish(
+        (symbol_short!("sched_fix"), subscription_id),
+        ScheduleCorrectedEvent {
+            subscription_id,
+            previous_timestamp,
+            new_timestamp: timestamp,
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+    Ok(())
+}
+
+/// Current contract version: [`CONTRACT_VERSION`] plus the number of upgrades applied.
+pub fn get_version(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "version"))
+        .unwrap_or(CONTRACT_VERSION)
+}
+
+/// Multisig action hash for upgrading to `new_wasm_hash`.
+pub fn upgrade_action_hash(env: &Env, new_wasm_hash: &BytesN<32>) -> BytesN<32> {
+    action_hash(env, symbol_short!("upgrade"), new_wasm_hash.clone())
+}
+
+/// Replaces the contract WASM with `new_wasm_hash` (already uploaded) and bumps the stored
+/// version. Admin only (or approved signers once multisig is on, see [`crate::multisig`]);
+/// storage is kept as-is, so the new code must read the existing layout.
+pub fn do_upgrade(env: &Env, admin: Address, new_wasm_hash: BytesN<32>) -> Result<(), Error> {
+    require_approved(env, &admin, &upgrade_action_hash(env, &new_wasm_hash))?;
+    let version = get_version(env).checked_add(1).ok_or(Error::Overflow)?;
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "version"), &version);
+    env.deployer().update_current_contract_wasm(new_wasm_hash);
+    Ok(())
+}
@@ -10,11 +10,72 @@
 //! - **Optional idempotency key**: If the caller supplies an idempotency key (e.g. for retries),
 //!   we store one key per subscription. A second call with the same key returns `Ok(())` without
 //!   debiting again (idempotent success). Storage stays bounded (one key and one period per sub).
+//!
+//! # Grace period
+//!
+//! When a due charge cannot be covered, the subscription is *past due* until
+//! `grace_period_end`: charges fail with [`Error::InsufficientBalance`] but the status stays
+//! `Active`. A charge attempt after the window moves it to `InsufficientBalance`.
+//!
+//! # Dunning
+//!
+//! Every unfunded attempt increments `failed_charge_count` and emits `ChargeFailedEvent`. When
+//! the admin sets `max_retries > 0`, the subscription stays `Active` between attempts and is
+//! cancelled with a refund once the count reaches the limit. A successful charge resets it.
+//!
+//! # Cancel at period end
+//!
+//! A pending `cancel_at_period_end` is finalized by the first charge attempt that is due: no
+//! payment is taken, the balance is refunded and `SubscriptionCancelledEvent` is emitted.
+//!
+//! # Autopay
+//!
+//! With autopay on, a due charge that the prepaid balance cannot cover first pulls the
+//! shortfall from the subscriber via `transfer_from` on the token allowance granted to this
+//! contract. If the allowance or the subscriber's balance is too small nothing is pulled and the
+//! charge fails as usual. Autopay subscriptions are not auto-paused after a charge.
+//!
+//! # Fixed number of cycles
+//!
+//! A subscription created with `max_cycles > 0` counts its interval charges in `cycles_charged`.
+//! The charge that reaches the limit is taken normally and the subscription is then cancelled in
+//! the same call: the remaining balance is refunded and `SubscriptionCancelledEvent` is emitted
+//! with the contract as authorizer.
+//!
+//! # Stale subscriptions
+//!
+//! `expire_stale` lets a keeper cancel, in one call, listed subscriptions that are still unfunded
+//! after their grace window (see [`is_stale`]); the remaining balance is refunded.
+//!
+//! # Authorization expiry
+//!
+//! A subscriber can bound their consent with `renew_authorization`: once the expiry passes,
+//! interval charges fail with [`Error::AuthorizationExpired`] until the subscriber renews it.
+//! A pending cancel or a lapse is still applied, since neither takes a payment.
+//!
+//! # Lapse
+//!
+//! A due charge on a subscription with auto-renew disabled takes no payment: the subscription
+//! moves to `Cancelled`, its prepaid balance is refunded and `SubscriptionLapsedEvent` is emitted.
 
+use crate::admin::{extend_storage_ttl, get_max_retries};
+use crate::credits::{consume_credit, in_credit_mode, next_credit_cost, release_credits};
+use crate::fees::{accrue_fee, split_fee, BPS_DENOMINATOR};
+use crate::history::{record_charge, record_merchant_revenue, record_status};
+use crate::installments::installment_remainder;
+use crate::merchant::{credit_merchant_with_release, publish_merchant_credited};
 use crate::queries::get_subscription;
 use crate::state_machine::validate_status_transition;
-use crate::types::{Error, SubscriptionChargedEvent, SubscriptionStatus};
-use soroban_sdk::{symbol_short, Env, Symbol};
+use crate::subscription::{
+    adjust_total_prepaid, authorization_expired, clear_cancel_pending, clear_paused_by,
+    get_auto_renew, get_autopay, get_cancel_pending, get_release_months, next_event_seq,
+    set_auto_paused, warn_if_low_balance,
+};
+use crate::types::{
+    CancelSettlement, ChargeFailedEvent, Error, Subscription, SubscriptionCancelledEvent,
+    SubscriptionChargedEvent, SubscriptionLapsedEvent, SubscriptionPausedEvent, SubscriptionStatus,
+};
+use soroban_sdk::{symbol_short, token, Address, Env, Symbol, Vec};
 
 const KEY_CHARGED_PERIOD: Symbol = symbol_short!("cp");
 const KEY_IDEM: Symbol = symbol_short!("idem");
@@ -27,6 +88,124 @@ fn idem_key(subscription_id: u32) -> (Symbol, u32) {
     (KEY_IDEM, subscription_id)
 }
 
+/// Nonce of the most recent successful interval, final settlement or usage charge (0 before
+/// any). Nonces start at 1 and have no gaps, so this is also the number of those charges
+/// processed contract-wide.
+pub fn get_charge_nonce(env: &Env) -> u64 {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "charge_nonce"))
+        .unwrap_or(0)
+}
+
+pub fn next_charge_nonce(env: &Env) -> u64 {
+    let nonce = get_charge_nonce(env) + 1;
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "charge_nonce"), &nonce);
+    nonce
+}
+
+/// Checks that `sub` is due for a charge at `now`: its billing period has not been charged yet
+/// (replay protection) and `interval_seconds` has elapsed since the last payment. Does not
+/// check status or balance and never writes to storage.
+pub fn check_charge_due(
+    env: &Env,
+    subscription_id: u32,
+    sub: &Subscription,
+    now: u64,
+) -> Result<(), Error> {
+    let period_index = now / sub.interval_seconds;
+
+    // Replay: already charged for this billing period (derived key)
+    if let Some(stored_period) = env
+        .storage()
+        .instance()
+        .get::<_, u64>(&charged_period_key(subscription_id))
+    {
+        if period_index <= stored_period {
+            return Err(Error::Replay);
+        }
+    }
+
+    let next_allowed = sub
+        .last_payment_timestamp
+        .checked_add(sub.interval_seconds)
+        .ok_or(Error::Overflow)?;
+    if now < next_allowed {
+        return Err(Error::IntervalNotElapsed);
+    }
+    Ok(())
+}
+
+/// Adds `amount` to the subscription's lifetime `total_charged`, rejecting with
+/// [`Error::SpendingCapReached`] if that would exceed a non-zero `max_total_charge`.
+pub fn record_total_charged(sub: &mut Subscription, amount: i128) -> Result<(), Error> {
+    let total = sub
+        .total_charged
+        .checked_add(amount)
+        .ok_or(Error::Overflow)?;
+    if sub.max_total_charge > 0 && total > sub.max_total_charge {
+        return Err(Error::SpendingCapReached);
+    }
+    sub.total_charged = total;
+    Ok(())
+}
+
+/// Remaining amount that may still be charged under the spending cap (`i128::MAX` if uncapped).
+pub fn remaining_spending_cap(sub: &Subscription) -> i128 {
+    if sub.max_total_charge > 0 {
+        (sub.max_total_charge - sub.total_charged).max(0)
+    } else {
+        i128::MAX
+    }
+}
+
+/// End of the grace window for the currently due charge: `last_payment_timestamp +
+/// interval_seconds + grace_seconds`.
+pub fn grace_period_end(sub: &Subscription) -> Result<u64, Error> {
+    sub.last_payment_timestamp
+        .checked_add(sub.interval_seconds)
+        .and_then(|due| due.checked_add(sub.grace_seconds))
+        .ok_or(Error::Overflow)
+}
+
+/// Interval charge amount at `now`: `amount` less the merchant coupon discount while it is
+/// active (the discount rounds down, in the subscriber's disfavour by at most one unit).
+pub fn effective_charge_amount(sub: &Subscription, now: u64) -> Result<i128, Error> {
+    if sub.discount_bps == 0 || now >= sub.discount_expiry {
+        return Ok(sub.amount);
+    }
+    let discount = sub
+        .amount
+        .checked_mul(i128::from(sub.discount_bps))
+        .ok_or(Error::Overflow)?
+        / BPS_DENOMINATOR;
+    sub.amount.checked_sub(discount).ok_or(Error::Overflow)
+}
+
+/// How an interval charge attempt that did not error ended.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub enum ChargeOutcome {
+    /// Charged, or settled without a charge (idempotent replay, lapse, period-end cancel).
+    Done,
+    /// The prepaid balance (and autopay) could not cover the charge; the failed attempt was
+    /// recorded by [`record_failed_charge`].
+    Unfunded,
+}
+
+/// [`charge_interval`] with an unfunded attempt reported as [`Error::InsufficientBalance`].
+pub fn charge_one(
+    env: &Env,
+    subscription_id: u32,
+    idempotency_key: Option<soroban_sdk::BytesN<32>>,
+) -> Result<(), Error> {
+    match charge_interval(env, subscription_id, idempotency_key)? {
+        ChargeOutcome::Done => Ok(()),
+        ChargeOutcome::Unfunded => Err(Error::InsufficientBalance),
+    }
+}
+
 /// Performs a single interval-based charge with optional replay protection.
 ///
 /// # Idempotency
@@ -39,12 +218,13 @@ fn idem_key(subscription_id: u32) -> (Symbol, u32) {
 /// # Storage
 ///
 /// Bounded: one `u64` (last charged period) and optionally one idempotency key per subscription.
-pub fn charge_one(
+pub fn charge_interval(
     env: &Env,
     subscription_id: u32,
     idempotency_key: Option<soroban_sdk::BytesN<32>>,
-) -> Result<(), Error> {
+) -> Result<ChargeOutcome, Error> {
     let mut sub = get_subscription(env, subscription_id)?;
+    extend_storage_ttl(env);
 
     if sub.status != SubscriptionStatus::Active {
         return Err(Error::NotActive);
@@ -61,43 +241,74 @@ pub fn charge_one(
             .get::<_, soroban_sdk::BytesN<32>>(&idem_key(subscription_id))
         {
             if stored == *k {
-                return Ok(());
+                return Ok(ChargeOutcome::Done);
             }
         }
     }
 
-    // Replay: already charged for this billing period (derived key)
-    if let Some(stored_period) = env
-        .storage()
-        .instance()
-        .get::<_, u64>(&charged_period_key(subscription_id))
-    {
-        if period_index <= stored_period {
-            return Err(Error::Replay);
-        }
+    if now < sub.start_timestamp {
+        return Err(Error::NotStarted);
     }
-
-    let next_allowed = sub
-        .last_payment_timestamp
-        .checked_add(sub.interval_seconds)
-        .ok_or(Error::Overflow)?;
-    if now < next_allowed {
-        return Err(Error::IntervalNotElapsed);
+    check_charge_due(env, subscription_id, &sub, now)?;
+    if let Some(authorizer) = get_cancel_pending(env, subscription_id) {
+        return cancel_with_refund(env, subscription_id, sub, authorizer)
+            .map(|()| ChargeOutcome::Done);
+    }
+    if !get_auto_renew(env, subscription_id) {
+        return lapse(env, subscription_id, sub).map(|()| ChargeOutcome::Done);
+    }
+    if authorization_expired(env, subscription_id, now) {
+        return Err(Error::AuthorizationExpired);
     }
+    // In credit mode the charge is one credit at the price it was bought at.
+    let credit_mode = in_credit_mode(env, subscription_id);
+    let amount = if credit_mode {
+        next_credit_cost(env, subscription_id, &sub)
+    } else {
+        // The final installment of an installment plan also carries the division remainder.
+        effective_charge_amount(&sub, now)?
+            .checked_add(installment_remainder(env, subscription_id, &sub))
+            .ok_or(Error::Overflow)?
+    };
 
-    if sub.prepaid_balance < sub.amount {
-        validate_status_transition(&sub.status, &SubscriptionStatus::InsufficientBalance)?;
-        sub.status = SubscriptionStatus::InsufficientBalance;
-        env.storage().instance().set(&subscription_id, &sub);
-        return Err(Error::InsufficientBalance);
+    if remaining_spending_cap(&sub) < amount {
+        return Err(Error::SpendingCapReached);
     }
 
-    sub.prepaid_balance = sub
-        .prepaid_balance
-        .checked_sub(sub.amount)
-        .ok_or(Error::Overflow)?;
+    let autopay = !credit_mode && get_autopay(env, subscription_id);
+    if credit_mode {
+        if sub.credits == 0 {
+            let amount = sub.amount;
+            return record_failed_charge(env, subscription_id, sub, amount, now);
+        }
+        consume_credit(env, subscription_id, &mut sub, amount)?;
+    } else {
+        if sub.prepaid_balance < amount && autopay {
+            pull_autopay_shortfall(env, &mut sub, amount)?;
+        }
+        if sub.prepaid_balance < amount {
+            return record_failed_charge(env, subscription_id, sub, amount, now);
+        }
+        sub.prepaid_balance = sub
+            .prepaid_balance
+            .checked_sub(amount)
+            .ok_or(Error::Overflow)?;
+    }
+    adjust_total_prepaid(env, &sub.token, -amount)?;
+    record_total_charged(&mut sub, amount)?;
     sub.last_payment_timestamp = now;
+    sub.failed_charge_count = 0;
+    sub.cycles_charged = sub.cycles_charged.checked_add(1).ok_or(Error::Overflow)?;
     env.storage().instance().set(&subscription_id, &sub);
+    let (merchant_share, fee) = split_fee(env, amount)?;
+    credit_merchant_with_release(
+        env,
+        &sub.merchant,
+        &sub.token,
+        merchant_share,
+        get_release_months(env, subscription_id),
+    )?;
+    accrue_fee(env, &sub.token, fee)?;
 
     // Record charged period and optional idempotency key (bounded storage)
     env.storage()
@@ -107,14 +318,359 @@ pub fn charge_one(
         env.storage().instance().set(&idem_key(subscription_id), &k);
     }
 
+    record_charge(env, subscription_id, amount);
+    record_merchant_revenue(env, &sub.merchant, amount);
+
+    publish_merchant_credited(
+        env,
+        subscription_id,
+        &sub.merchant,
+        &sub.token,
+        merchant_share,
+    );
     env.events().publish(
-        (symbol_short!("charged"),),
+        (symbol_short!("charged"), subscription_id),
         SubscriptionChargedEvent {
             subscription_id,
             merchant: sub.merchant.clone(),
-            amount: sub.amount,
+            amount,
+            charge_nonce: next_charge_nonce(env),
+            receipt: amount >= sub.receipt_threshold,
+            external_ref: sub.external_ref.clone(),
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+    if sub.max_cycles > 0 && sub.cycles_charged >= sub.max_cycles {
+        // Expired: cancel and refund the remainder.
+        let authorizer = env.current_contract_address();
+        return cancel_with_refund(env, subscription_id, sub, authorizer)
+            .map(|()| ChargeOutcome::Done);
+    }
+    if credit_mode {
+        return Ok(ChargeOutcome::Done);
+    }
+    warn_if_low_balance(env, subscription_id, &sub);
+
+    // Auto-pause when the remaining balance cannot cover the next charge; a later deposit that
+    // restores the balance resumes it (see `do_deposit_funds`).
+    if sub.prepaid_balance < sub.amount && !autopay {
+        validate_status_transition(&sub.status, &SubscriptionStatus::Paused)?;
+        sub.status = SubscriptionStatus::Paused;
+        env.storage().instance().set(&subscription_id, &sub);
+        set_auto_paused(env, subscription_id, true);
+        clear_paused_by(env, subscription_id);
+        record_status(env, subscription_id, SubscriptionStatus::Paused);
+        env.events().publish(
+            (symbol_short!("paused"), subscription_id),
+            SubscriptionPausedEvent {
+                subscription_id,
+                authorizer: env.current_contract_address(),
+                seq: next_event_seq(env, subscription_id),
+            },
+        );
+    }
+
+    Ok(ChargeOutcome::Done)
+}
+
+/// Whether the subscriber's allowance to this contract and token balance both cover
+/// `shortfall`.
+pub fn autopay_covers(env: &Env, sub: &Subscription, shortfall: i128) -> bool {
+    let token = token::Client::new(env, &sub.token);
+    token.allowance(&sub.subscriber, &env.current_contract_address()) >= shortfall
+        && token.balance(&sub.subscriber) >= shortfall
+}
+
+/// Pulls `amount - prepaid_balance` from the subscriber's allowance into the prepaid balance.
+/// Leaves `sub` unchanged when the allowance or the subscriber's balance cannot cover it.
+fn pull_autopay_shortfall(env: &Env, sub: &mut Subscription, amount: i128) -> Result<(), Error> {
+    let shortfall = amount
+        .checked_sub(sub.prepaid_balance)
+        .ok_or(Error::Overflow)?;
+    if !autopay_covers(env, sub, shortfall) {
+        return Ok(());
+    }
+    let vault = env.current_contract_address();
+    token::Client::new(env, &sub.token).transfer_from(&vault, &sub.subscriber, &vault, &shortfall);
+    sub.prepaid_balance = amount;
+    adjust_total_prepaid(env, &sub.token, shortfall)
+}
+
+/// Moves `sub` to `Cancelled`, saves it and refunds its prepaid balance to the subscriber.
+/// Returns the refunded amount. The caller validates the transition and emits the event.
+fn close_and_refund(env: &Env, subscription_id: u32, mut sub: Subscription) -> Result<i128, Error> {
+    release_credits(env, subscription_id, &mut sub)?;
+    let refund = sub.prepaid_balance;
+    sub.prepaid_balance = 0;
+    adjust_total_prepaid(env, &sub.token, -refund)?;
+    sub.status = SubscriptionStatus::Cancelled;
+    env.storage().instance().set(&subscription_id, &sub);
+    clear_cancel_pending(env, subscription_id);
+    record_status(env, subscription_id, SubscriptionStatus::Cancelled);
+    if refund > 0 {
+        token::Client::new(env, &sub.token).transfer(
+            &env.current_contract_address(),
+            &sub.subscriber,
+            &refund,
+        );
+    }
+    Ok(refund)
+}
+
+/// Records an unfunded charge attempt: bumps `failed_charge_count` and emits
+/// `ChargeFailedEvent`. With dunning enabled (`max_retries > 0`) the subscription stays `Active`
+/// for retries and is cancelled (balance refunded) once the count reaches `max_retries`; without
+/// it the grace period decides. Returns [`ChargeOutcome::Unfunded`].
+fn record_failed_charge(
+    env: &Env,
+    subscription_id: u32,
+    mut sub: Subscription,
+    amount: i128,
+    now: u64,
+) -> Result<ChargeOutcome, Error> {
+    sub.failed_charge_count = sub.failed_charge_count.saturating_add(1);
+    env.events().publish(
+        (symbol_short!("chg_fail"), subscription_id),
+        ChargeFailedEvent {
+            subscription_id,
+            amount,
+            prepaid_balance: sub.prepaid_balance,
+            failed_charge_count: sub.failed_charge_count,
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+
+    let max_retries = get_max_retries(env);
+    if max_retries > 0 {
+        if sub.failed_charge_count >= max_retries {
+            validate_status_transition(&sub.status, &SubscriptionStatus::Cancelled)?;
+            let refund_to = sub.subscriber.clone();
+            let refund = close_and_refund(env, subscription_id, sub)?;
+            env.events().publish(
+                (symbol_short!("cancelled"), subscription_id),
+                SubscriptionCancelledEvent {
+                    subscription_id,
+                    authorizer: env.current_contract_address(),
+                    refund_amount: refund,
+                    refund_to,
+                    swept_to_merchant: 0,
+                    seq: next_event_seq(env, subscription_id),
+                },
+            );
+        } else {
+            env.storage().instance().set(&subscription_id, &sub);
+        }
+        return Ok(ChargeOutcome::Unfunded);
+    }
+
+    // Past due: within the grace window the subscription stays Active so a deposit followed
+    // by a retry can still collect this period.
+    if now >= grace_period_end(&sub)? {
+        validate_status_transition(&sub.status, &SubscriptionStatus::InsufficientBalance)?;
+        sub.status = SubscriptionStatus::InsufficientBalance;
+        record_status(
+            env,
+            subscription_id,
+            SubscriptionStatus::InsufficientBalance,
+        );
+    }
+    env.storage().instance().set(&subscription_id, &sub);
+    Ok(ChargeOutcome::Unfunded)
+}
+
+/// Cancels `sub` without charging, refunds its prepaid balance and emits
+/// `SubscriptionCancelledEvent` crediting `authorizer`. Used for a due `cancel_at_period_end`
+/// (authorizer: whoever scheduled it), `max_cycles` expiry and [`expire_stale`].
+fn cancel_with_refund(
+    env: &Env,
+    subscription_id: u32,
+    sub: Subscription,
+    authorizer: Address,
+) -> Result<(), Error> {
+    validate_status_transition(&sub.status, &SubscriptionStatus::Cancelled)?;
+    let refund_to = sub.subscriber.clone();
+    let refund = close_and_refund(env, subscription_id, sub)?;
+    env.events().publish(
+        (symbol_short!("cancelled"), subscription_id),
+        SubscriptionCancelledEvent {
+            subscription_id,
+            authorizer,
+            refund_amount: refund,
+            refund_to,
+            swept_to_merchant: 0,
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+    Ok(())
+}
+
+/// Whether `sub` is unfunded past its grace window: `InsufficientBalance`, or `Active` with the
+/// grace window over and a due charge that neither the prepaid balance nor autopay covers.
+pub fn is_stale(
+    env: &Env,
+    subscription_id: u32,
+    sub: &Subscription,
+    now: u64,
+) -> Result<bool, Error> {
+    match sub.status {
+        SubscriptionStatus::InsufficientBalance => Ok(true),
+        SubscriptionStatus::Active => {
+            if now < grace_period_end(sub)? {
+                return Ok(false);
+            }
+            let amount = effective_charge_amount(sub, now)?;
+            if sub.prepaid_balance >= amount {
+                return Ok(false);
+            }
+            let shortfall = amount - sub.prepaid_balance;
+            Ok(!(get_autopay(env, subscription_id) && autopay_covers(env, sub, shortfall)))
+        }
+        _ => Ok(false),
+    }
+}
+
+/// Cancels each listed subscription that [`is_stale`], refunding what is left of its balance
+/// (`SubscriptionCancelledEvent` with the contract as authorizer). Unknown ids and subscriptions
+/// still within grace are skipped. Returns the number cancelled.
+pub fn expire_stale(env: &Env, subscription_ids: &Vec<u32>) -> Result<u32, Error> {
+    let now = env.ledger().timestamp();
+    let mut expired = 0u32;
+    for id in subscription_ids.iter() {
+        let Ok(sub) = get_subscription(env, id) else {
+            continue;
+        };
+        if !is_stale(env, id, &sub, now)? {
+            continue;
+        }
+        cancel_with_refund(env, id, sub, env.current_contract_address())?;
+        expired += 1;
+    }
+    Ok(expired)
+}
+
+/// Ends a due subscription whose auto-renew is off: no charge, refund the prepaid balance.
+fn lapse(env: &Env, subscription_id: u32, sub: Subscription) -> Result<(), Error> {
+    validate_status_transition(&sub.status, &SubscriptionStatus::Cancelled)?;
+    let last_cycle_end = sub
+        .last_payment_timestamp
+        .checked_add(sub.interval_seconds)
+        .ok_or(Error::Overflow)?;
+    let refund = close_and_refund(env, subscription_id, sub)?;
+
+    env.events().publish(
+        (symbol_short!("lapsed"), subscription_id),
+        SubscriptionLapsedEvent {
+            subscription_id,
+            last_cycle_end,
+            refund_amount: refund,
+            seq: next_event_seq(env, subscription_id),
         },
     );
+    Ok(())
+}
 
+/// Share of `amount` for the time used since `last_payment_timestamp`, capped at one interval and
+/// rounded down.
+fn prorate(sub: &Subscription, amount: i128, now: u64) -> Result<i128, Error> {
+    let used = now
+        .saturating_sub(sub.last_payment_timestamp)
+        .min(sub.interval_seconds);
+    let scaled = amount
+        .checked_mul(i128::from(used))
+        .ok_or(Error::Overflow)?;
+    Ok(scaled / i128::from(sub.interval_seconds))
+}
+
+/// Amount [`charge_final_settlement`] would take right now under `settlement` (0 when none).
+pub fn final_settlement_amount(
+    env: &Env,
+    subscription_id: u32,
+    sub: &Subscription,
+    settlement: &CancelSettlement,
+) -> Result<i128, Error> {
+    let now = env.ledger().timestamp();
+    let settle = match settlement {
+        CancelSettlement::Off => false,
+        CancelSettlement::IfDue => check_charge_due(env, subscription_id, sub, now).is_ok(),
+        CancelSettlement::Always | CancelSettlement::Prorated => true,
+    };
+    if !settle {
+        return Ok(0);
+    }
+    // Settling what would be an installment plan's final charge collects the remainder too, so
+    // the plan still sums to its total. Cancelling earlier collects only the installments taken.
+    let mut amount = effective_charge_amount(sub, now)?
+        .checked_add(installment_remainder(env, subscription_id, sub))
+        .ok_or(Error::Overflow)?;
+    if *settlement == CancelSettlement::Prorated {
+        amount = prorate(sub, amount, now)?;
+    }
+    Ok(amount
+        .min(sub.prepaid_balance)
+        .min(remaining_spending_cap(sub))
+        .max(0))
+}
+
+/// Takes the final settlement charge at cancel time according to `settlement`.
+///
+/// Charges up to one interval's effective amount (never more than the prepaid balance or the
+/// remaining spending cap), credits the merchant and platform fee as a normal charge does, and
+/// emits `SubscriptionChargedEvent`. With [`CancelSettlement::IfDue`] the charge is only taken
+/// when [`check_charge_due`] passes; with [`CancelSettlement::Prorated`] it is scaled to the time
+/// used (see [`prorate`]). The caller persists `sub`.
+pub fn charge_final_settlement(
+    env: &Env,
+    subscription_id: u32,
+    sub: &mut Subscription,
+    settlement: &CancelSettlement,
+) -> Result<(), Error> {
+    let now = env.ledger().timestamp();
+    let amount = final_settlement_amount(env, subscription_id, sub, settlement)?;
+    if amount <= 0 {
+        return Ok(());
+    }
+
+    sub.prepaid_balance = sub
+        .prepaid_balance
+        .checked_sub(amount)
+        .ok_or(Error::Overflow)?;
+    adjust_total_prepaid(env, &sub.token, -amount)?;
+    record_total_charged(sub, amount)?;
+    sub.last_payment_timestamp = now;
+    let (merchant_share, fee) = split_fee(env, amount)?;
+    credit_merchant_with_release(
+        env,
+        &sub.merchant,
+        &sub.token,
+        merchant_share,
+        get_release_months(env, subscription_id),
+    )?;
+    accrue_fee(env, &sub.token, fee)?;
+    env.storage().instance().set(
+        &charged_period_key(subscription_id),
+        &(now / sub.interval_seconds),
+    );
+    record_charge(env, subscription_id, amount);
+    record_merchant_revenue(env, &sub.merchant, amount);
+
+    publish_merchant_credited(
+        env,
+        subscription_id,
+        &sub.merchant,
+        &sub.token,
+        merchant_share,
+    );
+    env.events().publish(
+        (symbol_short!("charged"), subscription_id),
+        SubscriptionChargedEvent {
+            subscription_id,
+            merchant: sub.merchant.clone(),
+            amount,
+            charge_nonce: next_charge_nonce(env),
+            receipt: amount >= sub.receipt_threshold,
+            external_ref: sub.external_ref.clone(),
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
     Ok(())
 }
@@ -0,0 +1,152 @@
+//! Prepaid credits billing model: buy_credits.
+//!
+//! **PRs that only change how credits are bought or consumed should edit this file only.**
+//!
+//! A subscriber can buy a fixed number of credits at the plan `amount` each. The tokens are
+//! held in the prepaid pool but tracked apart from `prepaid_balance`: once a subscription holds
+//! credits it is in credit mode and every interval charge consumes one credit (see
+//! `charge_one`). Each credit is worth the price it was bought at; the value of unused credits is
+//! moved back into `prepaid_balance` when the subscription is cancelled, so every refund and
+//! settlement path treats it like a prepaid balance.
+
+use crate::queries::get_subscription;
+use crate::subscription::{adjust_total_prepaid, check_max_prepaid, next_event_seq};
+use crate::types::{CreditsPurchasedEvent, Error, Subscription};
+use crate::yield_adapter::deposit_to_adapter;
+use soroban_sdk::{symbol_short, token, Address, Env, Symbol};
+
+const KEY_CREDIT_VALUE: Symbol = symbol_short!("credval");
+
+fn credit_value_key(subscription_id: u32) -> (Symbol, u32) {
+    (KEY_CREDIT_VALUE, subscription_id)
+}
+
+/// Tokens backing the subscription's unused credits.
+pub fn get_credit_value(env: &Env, subscription_id: u32) -> i128 {
+    env.storage()
+        .instance()
+        .get(&credit_value_key(subscription_id))
+        .unwrap_or(0)
+}
+
+/// Whether charges consume credits instead of the prepaid balance. Set by the first
+/// `buy_credits`; stays on after the credits run out, so an exhausted subscription fails its
+/// charge rather than silently falling back to the balance.
+pub fn in_credit_mode(env: &Env, subscription_id: u32) -> bool {
+    env.storage()
+        .instance()
+        .has(&credit_value_key(subscription_id))
+}
+
+/// Buys `count` credits at the plan `amount` each, paid from the subscriber's wallet.
+/// Subscriber only. Returns the credits now held.
+pub fn do_buy_credits(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    count: u32,
+) -> Result<u32, Error> {
+    subscriber.require_auth();
+    let mut sub = get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Unauthorized);
+    }
+    if count == 0 {
+        return Err(Error::InvalidAmount);
+    }
+    let cost = sub
+        .amount
+        .checked_mul(count as i128)
+        .ok_or(Error::Overflow)?;
+    check_max_prepaid(env, subscription_id, &sub, cost)?;
+    sub.credits = sub.credits.checked_add(count).ok_or(Error::Overflow)?;
+    let value = get_credit_value(env, subscription_id)
+        .checked_add(cost)
+        .ok_or(Error::Overflow)?;
+    adjust_total_prepaid(env, &sub.token, cost)?;
+
+    token::Client::new(env, &sub.token).transfer(
+        &subscriber,
+        &env.current_contract_address(),
+        &cost,
+    );
+    deposit_to_adapter(env, &sub.token, cost)?;
+
+    env.storage()
+        .instance()
+        .set(&credit_value_key(subscription_id), &value);
+    env.storage().instance().set(&subscription_id, &sub);
+
+    env.events().publish(
+        (symbol_short!("credits"), subscription_id),
+        CreditsPurchasedEvent {
+            subscription_id,
+            subscriber,
+            count,
+            cost,
+            credits: sub.credits,
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+    Ok(sub.credits)
+}
+
+/// Price of the next credit: the backing value spread evenly over the credits left, with the
+/// last credit taking any remainder. Zero when no credits remain.
+pub fn next_credit_cost(env: &Env, subscription_id: u32, sub: &Subscription) -> i128 {
+    if sub.credits == 0 {
+        return 0;
+    }
+    get_credit_value(env, subscription_id) / sub.credits as i128
+}
+
+/// Uses one credit worth `cost` (see [`next_credit_cost`]). The caller saves `sub` and moves
+/// `cost` out of the prepaid pool.
+pub fn consume_credit(
+    env: &Env,
+    subscription_id: u32,
+    sub: &mut Subscription,
+    cost: i128,
+) -> Result<(), Error> {
+    sub.credits = sub
+        .credits
+        .checked_sub(1)
+        .ok_or(Error::InsufficientBalance)?;
+    let value = get_credit_value(env, subscription_id)
+        .checked_sub(cost)
+        .ok_or(Error::Overflow)?;
+    env.storage()
+        .instance()
+        .set(&credit_value_key(subscription_id), &value);
+    Ok(())
+}
+
+/// Ends credit mode so charges use the prepaid balance again. Only valid once the credits were
+/// released (see [`release_credits`]); used when a cancelled subscription is reactivated.
+pub fn leave_credit_mode(env: &Env, subscription_id: u32) {
+    env.storage()
+        .instance()
+        .remove(&credit_value_key(subscription_id));
+}
+
+/// Moves the value of unused credits into `prepaid_balance` and clears them, so a cancellation
+/// refunds or settles them with the rest of the balance. The caller saves `sub`.
+pub fn release_credits(
+    env: &Env,
+    subscription_id: u32,
+    sub: &mut Subscription,
+) -> Result<(), Error> {
+    let value = get_credit_value(env, subscription_id);
+    if value == 0 && sub.credits == 0 {
+        return Ok(());
+    }
+    sub.prepaid_balance = sub
+        .prepaid_balance
+        .checked_add(value)
+        .ok_or(Error::Overflow)?;
+    sub.credits = 0;
+    env.storage()
+        .instance()
+        .set(&credit_value_key(subscription_id), &0i128);
+    Ok(())
+}
@@ -0,0 +1,213 @@
+//! Charge disputes: dispute_charge, resolve_dispute.
+//!
+//! **PRs that only change dispute handling should edit this file only.**
+//!
+//! Opening a dispute moves the disputed amount out of the merchant's withdrawable balance into
+//! a frozen per-token pool. The admin resolves it either by refunding the subscriber or by
+//! returning the funds to the merchant. With `auto_pause_on_dispute` set, an `Active`
+//! subscription is also paused while the dispute is open and resumed on resolution.
+//!
+//! Refunded disputes are totalled per subscription; a new dispute can only claim what has been
+//! charged and not yet refunded.
+
+use crate::admin::require_admin;
+use crate::history::record_status;
+use crate::merchant::{credit_merchant, debit_merchant};
+use crate::queries::get_subscription;
+use crate::state_machine::validate_status_transition;
+use crate::subscription::{clear_paused_by, next_event_seq, set_auto_paused};
+use crate::types::{
+    Dispute, DisputeOpenedEvent, DisputeResolvedEvent, Error, SubscriptionPausedEvent,
+    SubscriptionResumedEvent, SubscriptionStatus,
+};
+use soroban_sdk::{symbol_short, token, Address, Env, Symbol};
+
+const KEY_DISPUTE: Symbol = symbol_short!("dispute");
+const KEY_FROZEN: Symbol = symbol_short!("dfrozen");
+const KEY_REFUNDED: Symbol = symbol_short!("drefund");
+
+fn dispute_key(subscription_id: u32) -> (Symbol, u32) {
+    (KEY_DISPUTE, subscription_id)
+}
+
+pub fn get_dispute(env: &Env, subscription_id: u32) -> Option<Dispute> {
+    env.storage().instance().get(&dispute_key(subscription_id))
+}
+
+/// Total refunded to the subscriber through resolved disputes.
+fn refunded_total(env: &Env, subscription_id: u32) -> i128 {
+    env.storage()
+        .instance()
+        .get(&(KEY_REFUNDED, subscription_id))
+        .unwrap_or(0)
+}
+
+/// Disputed funds in `token` currently frozen pending resolution.
+pub fn get_frozen_dispute_funds(env: &Env, token: &Address) -> i128 {
+    env.storage()
+        .instance()
+        .get(&(KEY_FROZEN, token.clone()))
+        .unwrap_or(0)
+}
+
+fn adjust_frozen(env: &Env, token: &Address, delta: i128) -> Result<(), Error> {
+    let total = get_frozen_dispute_funds(env, token)
+        .checked_add(delta)
+        .ok_or(Error::Overflow)?;
+    env.storage()
+        .instance()
+        .set(&(KEY_FROZEN, token.clone()), &total);
+    Ok(())
+}
+
+/// Whether opening a dispute also pauses the subscription (default `false`).
+pub fn get_auto_pause_on_dispute(env: &Env) -> bool {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "dispute_pause"))
+        .unwrap_or(false)
+}
+
+pub fn do_set_auto_pause_on_dispute(env: &Env, admin: Address, enabled: bool) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "dispute_pause"), &enabled);
+    Ok(())
+}
+
+/// Subscriber disputes `amount` of what they have been charged and not already had refunded by an
+/// earlier dispute. The amount is frozen out of the merchant's balance (which must still hold
+/// it). One open dispute per subscription.
+pub fn do_dispute_charge(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    amount: i128,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    let mut sub = get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Unauthorized);
+    }
+    if get_dispute(env, subscription_id).is_some() {
+        return Err(Error::DisputeOpen);
+    }
+    let disputable = sub
+        .total_charged
+        .checked_sub(refunded_total(env, subscription_id))
+        .ok_or(Error::Overflow)?;
+    if amount <= 0 || amount > disputable {
+        return Err(Error::InvalidAmount);
+    }
+    debit_merchant(env, &sub.merchant, &sub.token, amount)?;
+    adjust_frozen(env, &sub.token, amount)?;
+
+    let pause = get_auto_pause_on_dispute(env) && sub.status == SubscriptionStatus::Active;
+    if pause {
+        validate_status_transition(&sub.status, &SubscriptionStatus::Paused)?;
+        sub.status = SubscriptionStatus::Paused;
+        env.storage().instance().set(&subscription_id, &sub);
+        // Not a low-balance pause: a deposit must not lift it.
+        set_auto_paused(env, subscription_id, false);
+        clear_paused_by(env, subscription_id);
+        record_status(env, subscription_id, SubscriptionStatus::Paused);
+    }
+    env.storage().instance().set(
+        &dispute_key(subscription_id),
+        &Dispute {
+            amount,
+            opened_at: env.ledger().timestamp(),
+            paused_subscription: pause,
+        },
+    );
+
+    env.events().publish(
+        (symbol_short!("disputed"), subscription_id),
+        DisputeOpenedEvent {
+            subscription_id,
+            subscriber,
+            amount,
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+    if pause {
+        env.events().publish(
+            (symbol_short!("paused"), subscription_id),
+            SubscriptionPausedEvent {
+                subscription_id,
+                authorizer: env.current_contract_address(),
+                seq: next_event_seq(env, subscription_id),
+            },
+        );
+    }
+    Ok(())
+}
+
+/// Admin closes the open dispute: `refund` sends the frozen amount to the subscriber, otherwise
+/// it goes back to the merchant's balance. A subscription paused by the dispute (and still
+/// paused) is resumed.
+pub fn do_resolve_dispute(
+    env: &Env,
+    subscription_id: u32,
+    admin: Address,
+    refund: bool,
+) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    let mut sub = get_subscription(env, subscription_id)?;
+    let dispute = get_dispute(env, subscription_id).ok_or(Error::NotFound)?;
+    env.storage()
+        .instance()
+        .remove(&dispute_key(subscription_id));
+    adjust_frozen(env, &sub.token, -dispute.amount)?;
+    if refund {
+        let refunded = refunded_total(env, subscription_id)
+            .checked_add(dispute.amount)
+            .ok_or(Error::Overflow)?;
+        env.storage()
+            .instance()
+            .set(&(KEY_REFUNDED, subscription_id), &refunded);
+        token::Client::new(env, &sub.token).transfer(
+            &env.current_contract_address(),
+            &sub.subscriber,
+            &dispute.amount,
+        );
+    } else {
+        credit_merchant(env, &sub.merchant, &sub.token, dispute.amount)?;
+    }
+
+    let resume = dispute.paused_subscription && sub.status == SubscriptionStatus::Paused;
+    if resume {
+        validate_status_transition(&sub.status, &SubscriptionStatus::Active)?;
+        sub.status = SubscriptionStatus::Active;
+        env.storage().instance().set(&subscription_id, &sub);
+        record_status(env, subscription_id, SubscriptionStatus::Active);
+    }
+
+    env.events().publish(
+        (symbol_short!("dresolved"), subscription_id),
+        DisputeResolvedEvent {
+            subscription_id,
+            amount: dispute.amount,
+            refunded: refund,
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+    if resume {
+        env.events().publish(
+            (symbol_short!("resumed"), subscription_id),
+            SubscriptionResumedEvent {
+                subscription_id,
+                authorizer: env.current_contract_address(),
+                seq: next_event_seq(env, subscription_id),
+            },
+        );
+    }
+    Ok(())
+}
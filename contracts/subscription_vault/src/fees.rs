@@ -0,0 +1,216 @@
+//! Platform fees: configuration, accrual on charges, and the fee-collector sweep.
+//!
+//! **PRs that only change platform fee behavior should edit this file only.**
+//!
+//! A fee of `fee_bps` basis points is taken from every charge before the remainder is credited
+//! to the merchant. Fees accrue per token and are swept to the fee collector with
+//! `withdraw_fees`.
+//!
+//! All amounts are whole token base units. When `amount * fee_bps` is not divisible by
+//! 10_000, the remainder goes to the merchant or the platform according to the admin-set
+//! [`RoundingRemainderPolicy`]; the merchant share and fee always sum to the charged amount.
+//!
+//! An optional cancel fee of `cancel_fee_bps` is deducted from the refund of a
+//! `cancel_subscription` (or `cancel_subscription_to`) and accrues with the platform fees.
+
+use crate::admin::require_admin;
+use crate::types::{Error, FeesWithdrawnEvent, RoundingRemainderPolicy};
+use soroban_sdk::{symbol_short, token, Address, Env, Symbol};
+
+/// Basis-point denominator (10_000 bps = 100%).
+pub const BPS_DENOMINATOR: i128 = 10_000;
+
+const KEY_FEE_BALANCE: Symbol = symbol_short!("pfee");
+
+fn fee_balance_key(token: &Address) -> (Symbol, Address) {
+    (KEY_FEE_BALANCE, token.clone())
+}
+
+/// Sets the platform fee (in basis points) and the address allowed to sweep accrued fees.
+pub fn do_set_platform_fee(
+    env: &Env,
+    admin: Address,
+    fee_bps: u32,
+    fee_collector: Address,
+) -> Result<(), Error> {
+    admin.require_auth();
+    let stored = require_admin(env)?;
+    if admin != stored {
+        return Err(Error::Unauthorized);
+    }
+    if i128::from(fee_bps) > BPS_DENOMINATOR {
+        return Err(Error::InvalidAmount);
+    }
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "fee_bps"), &fee_bps);
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "fee_collector"), &fee_collector);
+    Ok(())
+}
+
+/// Replaces the fee collector (e.g. a dedicated treasury instead of the admin key) without
+/// changing the fee rate. Admin only.
+pub fn do_set_fee_collector(
+    env: &Env,
+    admin: Address,
+    fee_collector: Address,
+) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "fee_collector"), &fee_collector);
+    Ok(())
+}
+
+/// Sets how fee rounding remainders are assigned.
+pub fn do_set_rounding_remainder_policy(
+    env: &Env,
+    admin: Address,
+    policy: RoundingRemainderPolicy,
+) -> Result<(), Error> {
+    admin.require_auth();
+    let stored = require_admin(env)?;
+    if admin != stored {
+        return Err(Error::Unauthorized);
+    }
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "rounding"), &policy);
+    Ok(())
+}
+
+/// Current rounding remainder policy (`Merchant` if never configured).
+pub fn get_rounding_remainder_policy(env: &Env) -> RoundingRemainderPolicy {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "rounding"))
+        .unwrap_or(RoundingRemainderPolicy::Merchant)
+}
+
+/// Platform fee in basis points (0 if never configured).
+pub fn get_platform_fee_bps(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "fee_bps"))
+        .unwrap_or(0)
+}
+
+/// Sets the early-cancellation fee (basis points of the refund). Admin only; 0 disables it.
+pub fn do_set_cancel_fee(env: &Env, admin: Address, cancel_fee_bps: u32) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    if i128::from(cancel_fee_bps) > BPS_DENOMINATOR {
+        return Err(Error::InvalidAmount);
+    }
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "cfee_bps"), &cancel_fee_bps);
+    Ok(())
+}
+
+/// Early-cancellation fee in basis points (0 if never configured).
+pub fn get_cancel_fee_bps(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "cfee_bps"))
+        .unwrap_or(0)
+}
+
+/// Cancel fee on a `refund`, rounded down so the subscriber never pays more than the rate.
+pub fn cancel_fee(env: &Env, refund: i128) -> Result<i128, Error> {
+    let scaled = refund
+        .checked_mul(i128::from(get_cancel_fee_bps(env)))
+        .ok_or(Error::Overflow)?;
+    Ok(scaled / BPS_DENOMINATOR)
+}
+
+/// Address that receives swept platform fees. Defaults to the admin until configured.
+pub fn get_fee_collector(env: &Env) -> Result<Address, Error> {
+    match env
+        .storage()
+        .instance()
+        .get(&Symbol::new(env, "fee_collector"))
+    {
+        Some(collector) => Ok(collector),
+        None => require_admin(env),
+    }
+}
+
+/// Platform fees accrued in `token` and not yet swept.
+pub fn get_fee_balance(env: &Env, token: &Address) -> i128 {
+    env.storage()
+        .instance()
+        .get(&fee_balance_key(token))
+        .unwrap_or(0)
+}
+
+/// Splits a charged `amount` into `(merchant_share, platform_fee)`. Both are whole base units
+/// and sum to `amount`; the rounding remainder follows [`get_rounding_remainder_policy`].
+pub fn split_fee(env: &Env, amount: i128) -> Result<(i128, i128), Error> {
+    let scaled = amount
+        .checked_mul(i128::from(get_platform_fee_bps(env)))
+        .ok_or(Error::Overflow)?;
+    let mut fee = scaled / BPS_DENOMINATOR;
+    if scaled % BPS_DENOMINATOR != 0
+        && get_rounding_remainder_policy(env) == RoundingRemainderPolicy::Platform
+    {
+        fee += 1;
+    }
+    let merchant_share = amount.checked_sub(fee).ok_or(Error::Overflow)?;
+    Ok((merchant_share, fee))
+}
+
+/// Adds `amount` of `token` to the accrued platform fees.
+pub fn accrue_fee(env: &Env, token: &Address, amount: i128) -> Result<(), Error> {
+    if amount == 0 {
+        return Ok(());
+    }
+    let balance = get_fee_balance(env, token)
+        .checked_add(amount)
+        .ok_or(Error::Overflow)?;
+    env.storage()
+        .instance()
+        .set(&fee_balance_key(token), &balance);
+    Ok(())
+}
+
+/// Transfers all accrued platform fees in `token` to the fee collector. `caller` must be the fee
+/// collector or the admin. Returns the amount swept (0 when nothing had accrued).
+pub fn do_withdraw_fees(env: &Env, caller: Address, token: Address) -> Result<i128, Error> {
+    caller.require_auth();
+    let fee_collector = get_fee_collector(env)?;
+    if caller != fee_collector && caller != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+
+    let amount = get_fee_balance(env, &token);
+    if amount == 0 {
+        return Ok(0);
+    }
+    env.storage()
+        .instance()
+        .set(&fee_balance_key(&token), &0i128);
+
+    token::Client::new(env, &token).transfer(
+        &env.current_contract_address(),
+        &fee_collector,
+        &amount,
+    );
+
+    env.events().publish(
+        (symbol_short!("fee_wdraw"),),
+        FeesWithdrawnEvent {
+            fee_collector,
+            token,
+            amount,
+        },
+    );
+    Ok(amount)
+}
@@ -0,0 +1,229 @@
+//! Bounded per-subscription history: recent charges, status changes and deposits.
+//!
+//! Each buffer keeps the newest `history_cap` entries (default [`DEFAULT_HISTORY_CAP`]); older
+//! entries are dropped as new ones arrive. Lowering the cap trims a buffer lazily: reads return
+//! only its newest `history_cap` entries and the next write to it drops the rest from storage.
+//!
+//! Each merchant also has a revenue log of daily buckets (the newest [`MAX_REVENUE_BUCKETS`]),
+//! summing the gross amount of every charge to that merchant in the day.
+//!
+//! Contract-wide counters for [`get_stats`] are kept alongside: per-status subscription counts,
+//! updated by every status change, and the number and volume of charges.
+//!
+//! **PRs that only change history retention should edit this file only.**
+
+use crate::admin::require_admin;
+use crate::subscription::subscription_count;
+use crate::types::{
+    ChargeRecord, DepositRecord, Error, StatusRecord, SubscriptionStatus, VaultStats,
+};
+use soroban_sdk::{symbol_short, Address, Env, IntoVal, Symbol, TryFromVal, Val, Vec};
+
+/// Entries kept per buffer until the admin configures a different cap.
+pub const DEFAULT_HISTORY_CAP: u32 = 10;
+
+const KEY_CHARGES: Symbol = symbol_short!("chg_hist");
+const KEY_STATUSES: Symbol = symbol_short!("st_hist");
+const KEY_DEPOSITS: Symbol = symbol_short!("dep_hist");
+const KEY_MERCHANT_REVENUE: Symbol = symbol_short!("m_rev");
+const KEY_STATUS_COUNT: Symbol = symbol_short!("st_count");
+const KEY_CHARGE_TOTALS: Symbol = symbol_short!("chg_tot");
+
+/// Width of one merchant revenue bucket (one day).
+pub const REVENUE_BUCKET_SECONDS: u64 = 86_400;
+/// Daily buckets kept per merchant; older days are dropped.
+pub const MAX_REVENUE_BUCKETS: u32 = 90;
+
+pub fn get_history_cap(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "hist_cap"))
+        .unwrap_or(DEFAULT_HISTORY_CAP)
+}
+
+/// Sets the per-buffer cap (at least 1). Existing buffers are trimmed lazily (see [`load`]), so
+/// the cost does not grow with the number of subscriptions.
+pub fn do_set_history_cap(env: &Env, admin: Address, cap: u32) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    if cap == 0 {
+        return Err(Error::InvalidAmount);
+    }
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "hist_cap"), &cap);
+    Ok(())
+}
+
+pub fn record_charge(env: &Env, subscription_id: u32, amount: i128) {
+    let entry = ChargeRecord {
+        timestamp: env.ledger().timestamp(),
+        amount,
+    };
+    push(env, KEY_CHARGES, subscription_id, entry);
+}
+
+/// Appends to the status history and moves the subscription between the per-status counts.
+/// The previous status is the last history entry, which trimming always keeps.
+pub fn record_status(env: &Env, subscription_id: u32, status: SubscriptionStatus) {
+    let previous = get_status_history(env, subscription_id).last();
+    if let Some(previous) = previous {
+        adjust_status_count(env, previous.status, -1);
+    }
+    adjust_status_count(env, status.clone(), 1);
+    let entry = StatusRecord {
+        timestamp: env.ledger().timestamp(),
+        status,
+    };
+    push(env, KEY_STATUSES, subscription_id, entry);
+}
+
+pub fn record_deposit(env: &Env, subscription_id: u32, amount: i128) {
+    let entry = DepositRecord {
+        timestamp: env.ledger().timestamp(),
+        amount,
+    };
+    push(env, KEY_DEPOSITS, subscription_id, entry);
+}
+
+fn status_count(env: &Env, status: SubscriptionStatus) -> u32 {
+    env.storage()
+        .instance()
+        .get(&(KEY_STATUS_COUNT, status))
+        .unwrap_or(0)
+}
+
+fn adjust_status_count(env: &Env, status: SubscriptionStatus, delta: i32) {
+    let count = status_count(env, status.clone()).saturating_add_signed(delta);
+    env.storage()
+        .instance()
+        .set(&(KEY_STATUS_COUNT, status), &count);
+}
+
+/// Adds a charge of `amount` to `merchant`'s bucket for the current day and to the
+/// contract-wide charge totals.
+pub fn record_merchant_revenue(env: &Env, merchant: &Address, amount: i128) {
+    let (charges, volume): (u64, i128) = env
+        .storage()
+        .instance()
+        .get(&KEY_CHARGE_TOTALS)
+        .unwrap_or((0, 0));
+    env.storage().instance().set(
+        &KEY_CHARGE_TOTALS,
+        &(charges.saturating_add(1), volume.saturating_add(amount)),
+    );
+
+    let now = env.ledger().timestamp();
+    let bucket = now - now % REVENUE_BUCKET_SECONDS;
+    let key = (KEY_MERCHANT_REVENUE, merchant.clone());
+    let mut buckets: Vec<ChargeRecord> =
+        env.storage().instance().get(&key).unwrap_or(Vec::new(env));
+    match buckets.last() {
+        Some(mut last) if last.timestamp == bucket => {
+            last.amount = last.amount.saturating_add(amount);
+            buckets.set(buckets.len() - 1, last);
+        }
+        _ => {
+            buckets.push_back(ChargeRecord {
+                timestamp: bucket,
+                amount,
+            });
+            while buckets.len() > MAX_REVENUE_BUCKETS {
+                buckets.pop_front();
+            }
+        }
+    }
+    env.storage().instance().set(&key, &buckets);
+}
+
+/// Gross amount charged to `merchant` from `since_timestamp` on, at day resolution: the whole
+/// day containing `since_timestamp` counts. Covers at most the last [`MAX_REVENUE_BUCKETS`]
+/// days with charges.
+pub fn merchant_revenue_since(env: &Env, merchant: &Address, since_timestamp: u64) -> i128 {
+    let buckets: Vec<ChargeRecord> = env
+        .storage()
+        .instance()
+        .get(&(KEY_MERCHANT_REVENUE, merchant.clone()))
+        .unwrap_or(Vec::new(env));
+    let first = since_timestamp - since_timestamp % REVENUE_BUCKET_SECONDS;
+    buckets
+        .iter()
+        .filter(|b| b.timestamp >= first)
+        .fold(0i128, |total, b| total.saturating_add(b.amount))
+}
+
+/// Contract-wide counters; see [`VaultStats`].
+pub fn get_stats(env: &Env) -> VaultStats {
+    let (charges, volume) = env
+        .storage()
+        .instance()
+        .get(&KEY_CHARGE_TOTALS)
+        .unwrap_or((0, 0));
+    VaultStats {
+        total_created: subscription_count(env),
+        active: status_count(env, SubscriptionStatus::Active),
+        cancelled: status_count(env, SubscriptionStatus::Cancelled),
+        charges,
+        volume,
+    }
+}
+
+/// Recent charges, oldest first.
+pub fn get_charge_history(env: &Env, subscription_id: u32) -> Vec<ChargeRecord> {
+    load(env, KEY_CHARGES, subscription_id)
+}
+
+/// Recent status changes (including creation), oldest first.
+pub fn get_status_history(env: &Env, subscription_id: u32) -> Vec<StatusRecord> {
+    load(env, KEY_STATUSES, subscription_id)
+}
+
+/// [`get_status_history`] as `(timestamp, status)` pairs, for dispute resolution without
+/// replaying events.
+pub fn status_history(env: &Env, subscription_id: u32) -> Vec<(u64, SubscriptionStatus)> {
+    let mut pairs = Vec::new(env);
+    for record in get_status_history(env, subscription_id).iter() {
+        pairs.push_back((record.timestamp, record.status));
+    }
+    pairs
+}
+
+/// Recent deposits, oldest first.
+pub fn get_deposit_history(env: &Env, subscription_id: u32) -> Vec<DepositRecord> {
+    load(env, KEY_DEPOSITS, subscription_id)
+}
+
+/// The newest `history_cap` entries of a buffer. A buffer written before the cap was lowered
+/// may hold more in storage until its next [`push`].
+fn load<T>(env: &Env, kind: Symbol, subscription_id: u32) -> Vec<T>
+where
+    T: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    let mut entries: Vec<T> = env
+        .storage()
+        .instance()
+        .get(&(kind, subscription_id))
+        .unwrap_or(Vec::new(env));
+    let cap = get_history_cap(env);
+    while entries.len() > cap {
+        entries.pop_front();
+    }
+    entries
+}
+
+fn push<T>(env: &Env, kind: Symbol, subscription_id: u32, entry: T)
+where
+    T: IntoVal<Env, Val> + TryFromVal<Env, Val>,
+{
+    let mut entries: Vec<T> = load(env, kind.clone(), subscription_id);
+    entries.push_back(entry);
+    let cap = get_history_cap(env);
+    while entries.len() > cap {
+        entries.pop_front();
+    }
+    env.storage()
+        .instance()
+        .set(&(kind, subscription_id), &entries);
+}
@@ -0,0 +1,85 @@
+//! Per-subscriber and per-merchant subscription id indexes.
+//!
+//! **PRs that only change how subscriptions are listed by owner should edit this file only.**
+//!
+//! Ids are appended when a subscription is created or imported and are never removed
+//! automatically: cancelled subscriptions stay listed. Ids of purged subscriptions are dropped
+//! by an admin `compact_index` call.
+
+use crate::admin::require_admin;
+use crate::types::{Error, Subscription, SubscriptionStatus};
+use soroban_sdk::{symbol_short, Address, Env, Symbol, Vec};
+
+const KEY_SUBSCRIBER_INDEX: Symbol = symbol_short!("sub_idx");
+const KEY_MERCHANT_INDEX: Symbol = symbol_short!("mer_idx");
+
+fn index_key(owner: &Address, is_merchant: bool) -> (Symbol, Address) {
+    let kind = if is_merchant {
+        KEY_MERCHANT_INDEX
+    } else {
+        KEY_SUBSCRIBER_INDEX
+    };
+    (kind, owner.clone())
+}
+
+/// Subscription ids indexed for `owner` as merchant (`is_merchant`) or subscriber, oldest first.
+pub fn get_index(env: &Env, owner: &Address, is_merchant: bool) -> Vec<u32> {
+    env.storage()
+        .instance()
+        .get(&index_key(owner, is_merchant))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Number of `owner`'s indexed subscriptions that still exist and are not `Cancelled`.
+pub fn count_open(env: &Env, owner: &Address, is_merchant: bool) -> u32 {
+    let mut count = 0;
+    for id in get_index(env, owner, is_merchant).iter() {
+        let sub: Option<Subscription> = env.storage().instance().get(&id);
+        if matches!(sub, Some(s) if s.status != SubscriptionStatus::Cancelled) {
+            count += 1;
+        }
+    }
+    count
+}
+
+fn push_index(env: &Env, owner: &Address, is_merchant: bool, subscription_id: u32) {
+    let mut ids = get_index(env, owner, is_merchant);
+    ids.push_back(subscription_id);
+    env.storage()
+        .instance()
+        .set(&index_key(owner, is_merchant), &ids);
+}
+
+/// Adds a newly stored subscription to its subscriber's and merchant's indexes.
+pub fn add_to_indexes(env: &Env, subscription_id: u32, sub: &Subscription) {
+    push_index(env, &sub.subscriber, false, subscription_id);
+    push_index(env, &sub.merchant, true, subscription_id);
+}
+
+/// Removes ids of purged subscriptions from `owner`'s index. Admin only. Returns how many ids
+/// were removed.
+pub fn do_compact_index(
+    env: &Env,
+    admin: Address,
+    owner: Address,
+    is_merchant: bool,
+) -> Result<u32, Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    let ids = get_index(env, &owner, is_merchant);
+    let mut kept = Vec::new(env);
+    for id in ids.iter() {
+        if env.storage().instance().has(&id) {
+            kept.push_back(id);
+        }
+    }
+    let removed = ids.len() - kept.len();
+    if removed > 0 {
+        env.storage()
+            .instance()
+            .set(&index_key(&owner, is_merchant), &kept);
+    }
+    Ok(removed)
+}
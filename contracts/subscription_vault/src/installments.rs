@@ -0,0 +1,60 @@
+//! Interest-free installment plans: create_installment_plan.
+//!
+//! **PRs that only change how installment plans are priced or created should edit this file only.**
+//!
+//! An installment plan is an ordinary subscription that sells a fixed `total` as `installments`
+//! interval charges of `total / installments`. It is created with `max_cycles = installments`, so
+//! the final installment is followed by the usual expiry: the subscription is cancelled and any
+//! leftover balance refunded. The remainder of the division is stored per subscription and added
+//! to the final charge (see `charge_one`), so the charges add up to `total` exactly.
+
+use crate::subscription::do_create_subscription_from_params;
+use crate::types::{Error, Subscription, SubscriptionParams};
+use soroban_sdk::{symbol_short, Address, Env, Symbol};
+
+const KEY_INSTALLMENT_REMAINDER: Symbol = symbol_short!("instrem");
+
+/// Creates a plan charging `total` over `installments` intervals. Fails with `InvalidAmount` when
+/// `installments` is zero or `total` is too small to give every installment a positive amount.
+/// Auth: subscriber, as for `create_subscription`. Returns the subscription id.
+pub fn do_create_installment_plan(
+    env: &Env,
+    subscriber: Address,
+    merchant: Address,
+    total: i128,
+    installments: u32,
+    interval_seconds: u64,
+) -> Result<u32, Error> {
+    if installments == 0 || total < i128::from(installments) {
+        return Err(Error::InvalidAmount);
+    }
+    let count = i128::from(installments);
+    let params = SubscriptionParams::new(subscriber, merchant, total / count, interval_seconds)?
+        .with_max_cycles(installments);
+    let id = do_create_subscription_from_params(env, params)?;
+    // Stored even when zero: its presence marks the subscription as a plan.
+    env.storage()
+        .instance()
+        .set(&(KEY_INSTALLMENT_REMAINDER, id), &(total % count));
+    Ok(id)
+}
+
+/// Whether the subscription was created by `create_installment_plan`. Plans cannot be
+/// reactivated once cancelled.
+pub fn is_installment_plan(env: &Env, subscription_id: u32) -> bool {
+    env.storage()
+        .instance()
+        .has(&(KEY_INSTALLMENT_REMAINDER, subscription_id))
+}
+
+/// Extra amount the next interval charge carries: the plan's division remainder when that charge
+/// is the final installment, otherwise 0.
+pub fn installment_remainder(env: &Env, subscription_id: u32, sub: &Subscription) -> i128 {
+    if sub.max_cycles == 0 || sub.cycles_charged.saturating_add(1) != sub.max_cycles {
+        return 0;
+    }
+    env.storage()
+        .instance()
+        .get(&(KEY_INSTALLMENT_REMAINDER, subscription_id))
+        .unwrap_or(0)
+}
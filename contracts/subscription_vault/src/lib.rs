@@ -1,29 +1,132 @@
 #![no_std]
+// Contract entrypoints mirror the on-chain ABI, which allows up to 10 parameters.
+#![allow(clippy::too_many_arguments)]
 
 mod admin;
 mod charge_core;
+mod credits;
+mod dispute;
+mod fees;
+mod history;
+mod index;
+mod installments;
 mod merchant;
+mod multisig;
 mod queries;
 mod state_machine;
 mod subscription;
+mod swap_adapter;
 mod types;
+mod yield_adapter;
 
-use soroban_sdk::{contract, contractimpl, symbol_short, Address, Env, Vec};
+use soroban_sdk::{
+    contract, contractimpl, symbol_short, Address, Bytes, BytesN, Env, Map, String, Symbol, Vec,
+};
 
 pub use state_machine::{can_transition, get_allowed_transitions, validate_status_transition};
+pub use swap_adapter::{SwapAdapter, SwapAdapterClient};
 pub use types::{
-    BatchChargeResult, Error, FundsDepositedEvent, MerchantWithdrawalEvent, OneOffChargedEvent,
+    BalanceRestoredEvent, BatchChargeResult, CancelScheduledEvent, CancelSettlement,
+    ChargeFailedEvent, ChargeRecord, CreditsPurchasedEvent, DepositRecord, Dispute,
+    DisputeOpenedEvent, DisputeResolvedEvent, DustSweptEvent, Error, FeesWithdrawnEvent,
+    FundsDepositedEvent, InitializedEvent, LowBalanceEvent, MerchantCreditedEvent,
+    MerchantWithdrawalEvent, OneOffChargedEvent, PayoutSwappedEvent, PrepaidWithdrawnEvent, Quote,
+    RoundingRemainderPolicy, ScheduleCorrectedEvent, SetupFeeChargedEvent, StatusRecord,
     Subscription, SubscriptionCancelledEvent, SubscriptionChargedEvent, SubscriptionCreatedEvent,
-    SubscriptionPausedEvent, SubscriptionResumedEvent, SubscriptionStatus,
+    SubscriptionLapsedEvent, SubscriptionParams, SubscriptionPausedEvent,
+    SubscriptionReactivatedEvent, SubscriptionResumedEvent, SubscriptionStatus,
+    UnauthorizedAttemptEvent, UsageChargedEvent, UsageRounding, VaultStats,
 };
+pub use yield_adapter::{YieldAdapter, YieldAdapterClient};
 
 #[contract]
 pub struct SubscriptionVault;
 
 #[contractimpl]
 impl SubscriptionVault {
-    pub fn init(env: Env, token: Address, admin: Address, min_topup: i128) -> Result<(), Error> {
-        admin::do_init(&env, token, admin, min_topup)
+    /// With `require_registration`, subscriptions can only name merchants that called
+    /// `register_merchant`. Emits `InitializedEvent`; a second call fails with
+    /// `AlreadyInitialized`.
+    pub fn init(
+        env: Env,
+        token: Address,
+        admin: Address,
+        min_topup: i128,
+        require_registration: bool,
+    ) -> Result<(), Error> {
+        admin::do_init(&env, token, admin, min_topup, require_registration)
+    }
+
+    /// Merchant consents to receive subscriptions (needed when `init` set `require_registration`).
+    pub fn register_merchant(env: Env, merchant: Address) -> Result<(), Error> {
+        merchant::do_register_merchant(&env, merchant)
+    }
+
+    pub fn is_merchant_registered(env: Env, merchant: Address) -> bool {
+        merchant::is_merchant_registered(&env, &merchant)
+    }
+
+    /// Merchant limits new subscriptions to these intervals (e.g. monthly and annual only).
+    /// An empty list accepts any interval.
+    pub fn set_allowed_intervals(
+        env: Env,
+        merchant: Address,
+        intervals: Vec<u64>,
+    ) -> Result<(), Error> {
+        merchant::do_set_allowed_intervals(&env, merchant, intervals)
+    }
+
+    pub fn get_allowed_intervals(env: Env, merchant: Address) -> Vec<u64> {
+        merchant::get_allowed_intervals(&env, &merchant)
+    }
+
+    /// Merchant-only: stop `subscriber` from opening new subscriptions to the merchant
+    /// (`SubscriberBlocked`). Existing subscriptions keep running.
+    pub fn block_subscriber(env: Env, merchant: Address, subscriber: Address) -> Result<(), Error> {
+        merchant::do_set_subscriber_blocked(&env, merchant, subscriber, true)
+    }
+
+    /// Merchant-only: lift a `block_subscriber`.
+    pub fn unblock_subscriber(
+        env: Env,
+        merchant: Address,
+        subscriber: Address,
+    ) -> Result<(), Error> {
+        merchant::do_set_subscriber_blocked(&env, merchant, subscriber, false)
+    }
+
+    pub fn is_subscriber_blocked(env: Env, merchant: Address, subscriber: Address) -> bool {
+        merchant::is_subscriber_blocked(&env, &merchant, &subscriber)
+    }
+
+    /// Merchant-only: when enabled, resuming one of the merchant's subscriptions fails with
+    /// `InsufficientBalance` unless the prepaid balance (or autopay) covers one `amount`.
+    pub fn set_require_funded_resume(
+        env: Env,
+        merchant: Address,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        merchant::do_set_require_funded_resume(&env, merchant, enabled)
+    }
+
+    pub fn get_require_funded_resume(env: Env, merchant: Address) -> bool {
+        merchant::get_require_funded_resume(&env, &merchant)
+    }
+
+    /// Default payment token set at `init` (`NotInitialized` before that).
+    pub fn get_token(env: Env) -> Result<Address, Error> {
+        admin::get_token(&env)
+    }
+
+    /// Decimals of the default token as reported by the token at `init`. All amounts are in
+    /// base units of this scale.
+    pub fn token_decimals(env: Env) -> Result<u32, Error> {
+        admin::get_token_decimals(&env)
+    }
+
+    /// Admin address set at `init` (`NotInitialized` before that).
+    pub fn get_admin(env: Env) -> Result<Address, Error> {
+        admin::get_admin(&env)
     }
 
     pub fn set_min_topup(env: Env, admin: Address, min_topup: i128) -> Result<(), Error> {
@@ -34,6 +137,164 @@ impl SubscriptionVault {
         admin::get_min_topup(&env)
     }
 
+    /// Admin-only: grace period for subscriptions created without their own `grace_seconds`.
+    pub fn set_default_grace_seconds(
+        env: Env,
+        admin: Address,
+        grace_seconds: u64,
+    ) -> Result<(), Error> {
+        admin::do_set_default_grace_seconds(&env, admin, grace_seconds)
+    }
+
+    pub fn get_default_grace_seconds(env: Env) -> u64 {
+        admin::get_default_grace_seconds(&env)
+    }
+
+    /// Admin-only: limit each subscriber to `max` non-cancelled subscriptions (0 = unlimited).
+    pub fn set_max_subs_per_subscriber(env: Env, admin: Address, max: u32) -> Result<(), Error> {
+        admin::do_set_max_subs_per_subscriber(&env, admin, max)
+    }
+
+    pub fn get_max_subs_per_subscriber(env: Env) -> u32 {
+        admin::get_max_subs_per_subscriber(&env)
+    }
+
+    /// Admin-only: cancel subscriptions after `max_retries` consecutive unfunded charge attempts
+    /// (0 disables). While enabled, an unfunded `charge_subscription` records the attempt and
+    /// returns successfully.
+    pub fn set_max_retries(env: Env, admin: Address, max_retries: u32) -> Result<(), Error> {
+        admin::do_set_max_retries(&env, admin, max_retries)
+    }
+
+    pub fn get_max_retries(env: Env) -> u32 {
+        admin::get_max_retries(&env)
+    }
+
+    /// Admin-only: make `resume_subscription` take an overdue charge right away when funded.
+    pub fn set_auto_charge_on_resume(env: Env, admin: Address, enabled: bool) -> Result<(), Error> {
+        admin::do_set_auto_charge_on_resume(&env, admin, enabled)
+    }
+
+    pub fn get_auto_charge_on_resume(env: Env) -> bool {
+        admin::get_auto_charge_on_resume(&env)
+    }
+
+    /// Admin-only: when the contract storage has at most `threshold` ledgers to live, deposits,
+    /// charges and `get_subscription` extend it to `extend_to` ledgers.
+    pub fn set_ttl_config(
+        env: Env,
+        admin: Address,
+        threshold: u32,
+        extend_to: u32,
+    ) -> Result<(), Error> {
+        admin::do_set_ttl_config(&env, admin, threshold, extend_to)
+    }
+
+    /// `(threshold, extend_to)` in ledgers.
+    pub fn get_ttl_config(env: Env) -> (u32, u32) {
+        admin::get_ttl_config(&env)
+    }
+
+    /// Admin-only: set the platform fee (basis points, taken from every charge) and the
+    /// fee collector allowed to sweep it.
+    pub fn set_platform_fee(
+        env: Env,
+        admin: Address,
+        fee_bps: u32,
+        fee_collector: Address,
+    ) -> Result<(), Error> {
+        fees::do_set_platform_fee(&env, admin, fee_bps, fee_collector)
+    }
+
+    pub fn get_platform_fee(env: Env) -> u32 {
+        fees::get_platform_fee_bps(&env)
+    }
+
+    /// Admin-only: early-cancellation fee in basis points, deducted from the refund of
+    /// `cancel_subscription` / `cancel_subscription_to` and accrued with the platform fees.
+    pub fn set_cancel_fee(env: Env, admin: Address, cancel_fee_bps: u32) -> Result<(), Error> {
+        fees::do_set_cancel_fee(&env, admin, cancel_fee_bps)
+    }
+
+    pub fn get_cancel_fee(env: Env) -> u32 {
+        fees::get_cancel_fee_bps(&env)
+    }
+
+    /// Admin-only: park deposits in an external yield contract implementing
+    /// `deposit`/`withdraw` (see `yield_adapter`), or stop with `None`. Funds are pulled back as
+    /// balances are charged, refunded or withdrawn.
+    pub fn set_yield_adapter(
+        env: Env,
+        admin: Address,
+        adapter: Option<Address>,
+    ) -> Result<(), Error> {
+        yield_adapter::do_set_yield_adapter(&env, admin, adapter)
+    }
+
+    pub fn get_yield_adapter(env: Env) -> Option<Address> {
+        yield_adapter::get_yield_adapter(&env)
+    }
+
+    /// `(principal, shares)` held with the yield adapter in `token`.
+    pub fn get_yield_position(env: Env, token: Address) -> (i128, i128) {
+        yield_adapter::get_yield_position(&env, &token)
+    }
+
+    /// Admin-only: exchange contract implementing `swap` (see `swap_adapter`) used by
+    /// `withdraw_merchant_funds_as`, or none.
+    pub fn set_swap_adapter(
+        env: Env,
+        admin: Address,
+        adapter: Option<Address>,
+    ) -> Result<(), Error> {
+        swap_adapter::do_set_swap_adapter(&env, admin, adapter)
+    }
+
+    pub fn get_swap_adapter(env: Env) -> Option<Address> {
+        swap_adapter::get_swap_adapter(&env)
+    }
+
+    /// Admin-only: choose whether fee rounding remainders go to the merchant or the platform.
+    pub fn set_rounding_remainder_policy(
+        env: Env,
+        admin: Address,
+        policy: RoundingRemainderPolicy,
+    ) -> Result<(), Error> {
+        fees::do_set_rounding_remainder_policy(&env, admin, policy)
+    }
+
+    pub fn get_rounding_remainder_policy(env: Env) -> RoundingRemainderPolicy {
+        fees::get_rounding_remainder_policy(&env)
+    }
+
+    /// Admin-only: send future fee sweeps to `fee_collector` (the admin until configured),
+    /// keeping the current fee rate.
+    pub fn set_fee_collector(
+        env: Env,
+        admin: Address,
+        fee_collector: Address,
+    ) -> Result<(), Error> {
+        fees::do_set_fee_collector(&env, admin, fee_collector)
+    }
+
+    pub fn get_fee_collector(env: Env) -> Result<Address, Error> {
+        fees::get_fee_collector(&env)
+    }
+
+    /// Platform fees accrued in `token` and not yet withdrawn.
+    pub fn get_fee_balance(env: Env, token: Address) -> i128 {
+        fees::get_fee_balance(&env, &token)
+    }
+
+    /// Sweep all accrued platform fees in `token` to the fee collector. Callable by the fee
+    /// collector or the admin. Returns the amount transferred.
+    pub fn withdraw_fees(env: Env, caller: Address, token: Address) -> Result<i128, Error> {
+        fees::do_withdraw_fees(&env, caller, token)
+    }
+
+    /// Create a subscription. The subscriber must authorize the call: this is their explicit
+    /// consent to future charges by `merchant`, so a merchant cannot open a subscription against
+    /// someone else's account.
     pub fn create_subscription(
         env: Env,
         subscriber: Address,
@@ -41,6 +302,9 @@ impl SubscriptionVault {
         amount: i128,
         interval_seconds: u64,
         usage_enabled: bool,
+        token: Option<Address>,
+        min_deposit: i128,
+        max_total_charge: i128,
     ) -> Result<u32, Error> {
         subscription::do_create_subscription(
             &env,
@@ -49,16 +313,148 @@ impl SubscriptionVault {
             amount,
             interval_seconds,
             usage_enabled,
+            token,
+            min_deposit,
+            max_total_charge,
+        )
+    }
+
+    /// Same as `create_subscription`, also returning the stored record so callers can render it
+    /// without a follow-up `get_subscription`.
+    pub fn create_subscription_full(
+        env: Env,
+        subscriber: Address,
+        merchant: Address,
+        amount: i128,
+        interval_seconds: u64,
+        usage_enabled: bool,
+        token: Option<Address>,
+        min_deposit: i128,
+        max_total_charge: i128,
+    ) -> Result<(u32, Subscription), Error> {
+        let id = subscription::do_create_subscription(
+            &env,
+            subscriber,
+            merchant,
+            amount,
+            interval_seconds,
+            usage_enabled,
+            token,
+            min_deposit,
+            max_total_charge,
+        )?;
+        Ok((id, queries::get_subscription(&env, id)?))
+    }
+
+    /// Same as `create_subscription` with the interval given in whole days.
+    pub fn create_subscription_days(
+        env: Env,
+        subscriber: Address,
+        merchant: Address,
+        amount: i128,
+        interval_days: u32,
+        usage_enabled: bool,
+        token: Option<Address>,
+        min_deposit: i128,
+        max_total_charge: i128,
+    ) -> Result<u32, Error> {
+        subscription::do_create_subscription(
+            &env,
+            subscriber,
+            merchant,
+            amount,
+            u64::from(interval_days) * types::DAY_SECONDS,
+            usage_enabled,
+            token,
+            min_deposit,
+            max_total_charge,
+        )
+    }
+
+    /// Interest-free installment plan: `total` split into `installments` equal interval charges,
+    /// the last one also taking the remainder so they sum to `total`. The subscription is
+    /// cancelled after the final installment. Auth: subscriber.
+    pub fn create_installment_plan(
+        env: Env,
+        subscriber: Address,
+        merchant: Address,
+        total: i128,
+        installments: u32,
+        interval_seconds: u64,
+    ) -> Result<u32, Error> {
+        installments::do_create_installment_plan(
+            &env,
+            subscriber,
+            merchant,
+            total,
+            installments,
+            interval_seconds,
         )
     }
 
+    /// Same as `create_subscription`, taking a validated [`SubscriptionParams`].
+    pub fn create_subscription_from_params(
+        env: Env,
+        params: SubscriptionParams,
+    ) -> Result<u32, Error> {
+        subscription::do_create_subscription_from_params(&env, params)
+    }
+
+    /// Top up the prepaid balance. Optional `idempotency_key` makes retries safe: repeating the
+    /// subscription's latest deposit key returns success without crediting again.
     pub fn deposit_funds(
         env: Env,
         subscription_id: u32,
         subscriber: Address,
         amount: i128,
+        idempotency_key: Option<soroban_sdk::BytesN<32>>,
+    ) -> Result<(), Error> {
+        subscription::do_deposit_funds(&env, subscription_id, subscriber, amount, idempotency_key)
+    }
+
+    /// Subscriber tops up and pays the due charge in one call. If the charge cannot be taken
+    /// (e.g. the deposit still does not cover it) the whole call reverts, deposit included.
+    pub fn deposit_and_charge(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        amount: i128,
+    ) -> Result<(), Error> {
+        subscription::do_deposit_and_charge(&env, subscription_id, subscriber, amount)
+    }
+
+    /// Top up a subscription from a third party (e.g. an employer): tokens come from `payer`,
+    /// who must authorize; the subscriber's auth is not needed.
+    pub fn deposit_for(
+        env: Env,
+        subscription_id: u32,
+        payer: Address,
+        amount: i128,
+    ) -> Result<(), Error> {
+        subscription::do_deposit_for(&env, subscription_id, payer, amount)
+    }
+
+    /// Subscriber tops up several of their subscriptions at once (`amounts[i]` into
+    /// `subscription_ids[i]`) with a single transfer per token. Any invalid entry reverts the
+    /// whole batch.
+    pub fn deposit_batch(
+        env: Env,
+        subscription_ids: Vec<u32>,
+        subscriber: Address,
+        amounts: Vec<i128>,
+    ) -> Result<(), Error> {
+        subscription::do_deposit_batch(&env, subscription_ids, subscriber, amounts)
+    }
+
+    /// Subscriber takes `amount` of unused prepaid balance back without cancelling; the status is
+    /// unchanged. Fails with `InsufficientBalance` if `amount` exceeds the balance.
+    pub fn withdraw_prepaid(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        amount: i128,
     ) -> Result<(), Error> {
-        subscription::do_deposit_funds(&env, subscription_id, subscriber, amount)
+        subscription::do_withdraw_prepaid(&env, subscription_id, subscriber, amount)
     }
 
     /// Charge one subscription for the current billing interval. Optional `idempotency_key` enables
@@ -71,6 +467,214 @@ impl SubscriptionVault {
         subscription::do_charge_subscription(&env, subscription_id, idempotency_key)
     }
 
+    /// Admin sets how many entries each history buffer keeps (default 10); existing buffers are
+    /// trimmed lazily on their next read or write.
+    pub fn set_history_cap(env: Env, admin: Address, cap: u32) -> Result<(), Error> {
+        history::do_set_history_cap(&env, admin, cap)
+    }
+
+    pub fn get_history_cap(env: Env) -> u32 {
+        history::get_history_cap(&env)
+    }
+
+    /// Most recent charges (newest last), up to the history cap.
+    pub fn get_charge_history(env: Env, subscription_id: u32) -> Vec<ChargeRecord> {
+        history::get_charge_history(&env, subscription_id)
+    }
+
+    /// Most recent status changes (newest last), up to the history cap.
+    pub fn get_status_history(env: Env, subscription_id: u32) -> Vec<StatusRecord> {
+        history::get_status_history(&env, subscription_id)
+    }
+
+    /// Last status transitions as `(timestamp, status)`, oldest first, up to the history cap.
+    pub fn status_history(env: Env, subscription_id: u32) -> Vec<(u64, SubscriptionStatus)> {
+        history::status_history(&env, subscription_id)
+    }
+
+    /// Most recent deposits (newest last), up to the history cap.
+    pub fn get_deposit_history(env: Env, subscription_id: u32) -> Vec<DepositRecord> {
+        history::get_deposit_history(&env, subscription_id)
+    }
+
+    /// Nonce carried by the most recent `SubscriptionChargedEvent` or `UsageChargedEvent` (0
+    /// before any charge).
+    pub fn get_charge_nonce(env: Env) -> u64 {
+        charge_core::get_charge_nonce(&env)
+    }
+
+    /// Successful charges that carry a `charge_nonce`: interval, final settlement and usage
+    /// charges. Unlike `stats().charges` it leaves out one-off and setup fee charges.
+    pub fn get_total_charges(env: Env) -> u64 {
+        charge_core::get_charge_nonce(&env)
+    }
+
+    /// Breakdown of the next interval charge: base, discount, platform fee and net debit.
+    pub fn quote_next_charge(env: Env, subscription_id: u32) -> Result<Quote, Error> {
+        queries::quote_next_charge(&env, subscription_id)
+    }
+
+    /// Amount a charge taken now would debit (coupon applied); see `quote_next_charge` for the
+    /// fee split and `can_charge` for whether it would succeed.
+    pub fn preview_charge(env: Env, subscription_id: u32) -> Result<i128, Error> {
+        queries::preview_charge(&env, subscription_id)
+    }
+
+    /// Refund `cancel_subscription` would pay now, after any final settlement charge and the
+    /// cancel fee, without cancelling.
+    pub fn preview_cancel(env: Env, subscription_id: u32) -> Result<i128, Error> {
+        queries::preview_cancel(&env, subscription_id)
+    }
+
+    /// Ids of subscriptions where `subscriber` is the subscriber, oldest first.
+    pub fn get_subscriptions_by_subscriber(env: Env, subscriber: Address) -> Vec<u32> {
+        index::get_index(&env, &subscriber, false)
+    }
+
+    /// Ids of subscriptions billed to `merchant`, oldest first.
+    pub fn get_subscriptions_by_merchant(env: Env, merchant: Address) -> Vec<u32> {
+        index::get_index(&env, &merchant, true)
+    }
+
+    /// Admin overrides `last_payment_timestamp` (e.g. after a mistaken early charge) without
+    /// charging. Emits `ScheduleCorrectedEvent`.
+    pub fn set_last_payment(
+        env: Env,
+        admin: Address,
+        subscription_id: u32,
+        timestamp: u64,
+    ) -> Result<(), Error> {
+        admin::do_set_last_payment(&env, admin, subscription_id, timestamp)
+    }
+
+    /// Require `threshold` approvals from `signers` for `upgrade`, `force_cancel` and further
+    /// multisig changes. A threshold of 0 returns those actions to single-admin control. Admin
+    /// only until a threshold is set; after that `caller` must be a signer and the change needs
+    /// approvals of `set_multisig_action_hash`.
+    pub fn set_admin_multisig(
+        env: Env,
+        caller: Address,
+        signers: Vec<Address>,
+        threshold: u32,
+    ) -> Result<(), Error> {
+        multisig::do_set_admin_multisig(&env, caller, signers, threshold)
+    }
+
+    pub fn set_multisig_action_hash(env: Env, signers: Vec<Address>, threshold: u32) -> BytesN<32> {
+        multisig::set_multisig_action_hash(&env, &signers, threshold)
+    }
+
+    /// `(signers, threshold)` of the admin multisig.
+    pub fn get_admin_multisig(env: Env) -> (Vec<Address>, u32) {
+        multisig::get_admin_multisig(&env)
+    }
+
+    /// Signer approves a protected action by its hash (from `upgrade_action_hash` or
+    /// `force_cancel_action_hash`). Returns the approvals recorded so far.
+    pub fn approve_action(
+        env: Env,
+        signer: Address,
+        action_hash: BytesN<32>,
+    ) -> Result<u32, Error> {
+        multisig::do_approve_action(&env, signer, action_hash)
+    }
+
+    pub fn get_action_approvals(env: Env, action_hash: BytesN<32>) -> Vec<Address> {
+        multisig::get_approvals(&env, &action_hash)
+    }
+
+    pub fn upgrade_action_hash(env: Env, new_wasm_hash: BytesN<32>) -> BytesN<32> {
+        admin::upgrade_action_hash(&env, &new_wasm_hash)
+    }
+
+    /// Admin deletes a cancelled subscription's record.
+    pub fn purge_subscription(env: Env, admin: Address, subscription_id: u32) -> Result<(), Error> {
+        admin::do_purge_subscription(&env, admin, subscription_id)
+    }
+
+    /// Admin drops purged ids from `owner`'s merchant or subscriber index; returns the count.
+    pub fn compact_index(
+        env: Env,
+        admin: Address,
+        owner: Address,
+        is_merchant: bool,
+    ) -> Result<u32, Error> {
+        index::do_compact_index(&env, admin, owner, is_merchant)
+    }
+
+    /// Ids of up to `limit` (max 100) subscriptions created in `[start, end]` (ledger seconds,
+    /// inclusive), ascending. Pass the last id of a page as the next `start_after`.
+    pub fn created_between(
+        env: Env,
+        start: u64,
+        end: u64,
+        start_after: Option<u32>,
+        limit: u32,
+    ) -> Vec<u32> {
+        queries::created_between(&env, start, end, start_after, limit)
+    }
+
+    /// Page of subscription ids after `start_after` (from the first id when `None`), ascending,
+    /// at most `limit` (capped to 100). Use the last id returned as the next cursor.
+    pub fn list_subscriptions(env: Env, start_after: Option<u32>, limit: u32) -> Vec<u32> {
+        queries::list_subscriptions(&env, start_after, limit)
+    }
+
+    /// Same paging as `list_subscriptions`, returning only subscriptions whose stored status is
+    /// `status`.
+    pub fn list_by_status(
+        env: Env,
+        status: SubscriptionStatus,
+        start_after: Option<u32>,
+        limit: u32,
+    ) -> Vec<u32> {
+        queries::list_by_status(&env, status, start_after, limit)
+    }
+
+    /// Sequence number the subscription's next event will carry. Every subscription event has a
+    /// `seq` field counting up from 0, so off-chain consumers can detect missed events.
+    pub fn get_event_seq(env: Env, subscription_id: u32) -> u64 {
+        subscription::get_event_seq(&env, subscription_id)
+    }
+
+    /// Status as of now, counting a lapse or scheduled cancel that takes effect at the end of the
+    /// paid period but has not been applied by a charge attempt yet. Read-only.
+    pub fn effective_status(env: Env, subscription_id: u32) -> Result<SubscriptionStatus, Error> {
+        queries::effective_status(&env, subscription_id)
+    }
+
+    /// Gross interval charges `merchant` can expect over the next `window_seconds`, counting only
+    /// what each active subscription's prepaid balance can cover.
+    pub fn merchant_projected_revenue(
+        env: Env,
+        merchant: Address,
+        window_seconds: u64,
+    ) -> Result<i128, Error> {
+        queries::merchant_projected_revenue(&env, &merchant, window_seconds)
+    }
+
+    /// Gross amount charged to `merchant` (interval, settlement and one-off charges, all tokens)
+    /// from `since_timestamp` on. Day resolution: the whole day containing `since_timestamp`
+    /// counts. Only the last 90 days with charges are kept.
+    pub fn merchant_revenue_since(env: Env, merchant: Address, since_timestamp: u64) -> i128 {
+        history::merchant_revenue_since(&env, &merchant, since_timestamp)
+    }
+
+    /// Earliest ledger time at which the next interval charge can succeed.
+    pub fn next_charge_timestamp(env: Env, subscription_id: u32) -> Result<u64, Error> {
+        queries::next_charge_timestamp(&env, subscription_id)
+    }
+
+    /// True while a due charge is unfunded but still within the subscription's grace window.
+    pub fn is_past_due(env: Env, subscription_id: u32) -> Result<bool, Error> {
+        queries::is_past_due(&env, subscription_id)
+    }
+
+    /// Number of full intervals the prepaid balance covers; each charge draws down one.
+    pub fn covered_intervals(env: Env, subscription_id: u32) -> Result<u64, Error> {
+        queries::covered_intervals(&env, subscription_id)
+    }
+
     pub fn estimate_topup_for_intervals(
         env: Env,
         subscription_id: u32,
@@ -79,6 +683,11 @@ impl SubscriptionVault {
         queries::estimate_topup_for_intervals(&env, subscription_id, num_intervals)
     }
 
+    /// Read-only: whether a charge would currently succeed, and if not the blocking error code.
+    pub fn can_charge(env: Env, subscription_id: u32) -> Result<(bool, u32), Error> {
+        queries::can_charge(&env, subscription_id)
+    }
+
     pub fn batch_charge(
         env: Env,
         subscription_ids: Vec<u32>,
@@ -86,34 +695,281 @@ impl SubscriptionVault {
         admin::do_batch_charge(&env, &subscription_ids)
     }
 
+    /// Keeper entrypoint (no auth): cancels the listed subscriptions that are still unfunded
+    /// after their grace window, refunding any balance left. Returns how many were cancelled.
+    pub fn expire_stale(env: Env, subscription_ids: Vec<u32>) -> Result<u32, Error> {
+        charge_core::expire_stale(&env, &subscription_ids)
+    }
+
+    /// Cancel a subscription. Runs the merchant-configured final settlement charge (see
+    /// `set_cancel_settlement`), then refunds the remaining prepaid balance to the subscriber.
     pub fn cancel_subscription(
         env: Env,
         subscription_id: u32,
         authorizer: Address,
     ) -> Result<(), Error> {
-        authorizer.require_auth();
-        let mut sub: Subscription = env
-            .storage()
-            .instance()
-            .get(&subscription_id)
-            .ok_or(Error::NotFound)?;
+        subscription::do_cancel_subscription(&env, subscription_id, authorizer)
+    }
+
+    /// Subscriber cancels with the refund sent to `refund_to` (e.g. a closed account's
+    /// successor) instead of back to the subscriber.
+    pub fn cancel_subscription_to(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        refund_to: Address,
+    ) -> Result<(), Error> {
+        subscription::do_cancel_subscription_to(&env, subscription_id, subscriber, refund_to)
+    }
+
+    /// Merchant cancels and keeps the remaining prepaid balance (e.g. after a dispute decided in
+    /// the merchant's favour): it is credited to the merchant instead of refunded.
+    pub fn cancel_no_refund(
+        env: Env,
+        subscription_id: u32,
+        merchant: Address,
+    ) -> Result<(), Error> {
+        subscription::do_cancel_no_refund(&env, subscription_id, merchant)
+    }
+
+    /// Admin cancels any subscription, refunding the subscriber. High-value: needs M-of-N
+    /// approvals of `force_cancel_action_hash` once `set_admin_multisig` is configured.
+    pub fn force_cancel(env: Env, admin: Address, subscription_id: u32) -> Result<(), Error> {
+        subscription::do_force_cancel(&env, admin, subscription_id)
+    }
+
+    pub fn force_cancel_action_hash(env: Env, subscription_id: u32) -> BytesN<32> {
+        subscription::force_cancel_action_hash(&env, subscription_id)
+    }
+
+    /// Subscriber-only: with `auto_renew` off, the next due charge lapses the subscription
+    /// (cancelled, balance refunded, `SubscriptionLapsedEvent`) instead of billing it.
+    pub fn set_auto_renew(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        auto_renew: bool,
+    ) -> Result<(), Error> {
+        subscription::do_set_auto_renew(&env, subscription_id, subscriber, auto_renew)
+    }
+
+    pub fn get_auto_renew(env: Env, subscription_id: u32) -> bool {
+        subscription::get_auto_renew(&env, subscription_id)
+    }
+
+    /// Subscriber-only: with autopay on, a charge the prepaid balance cannot cover pulls the
+    /// shortfall through the token allowance the subscriber granted this contract.
+    pub fn set_autopay(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        enabled: bool,
+    ) -> Result<(), Error> {
+        subscription::do_set_autopay(&env, subscription_id, subscriber, enabled)
+    }
+
+    pub fn get_autopay(env: Env, subscription_id: u32) -> bool {
+        subscription::get_autopay(&env, subscription_id)
+    }
+
+    /// Subscriber-only: consent to interval charges until `new_expiry` (0 = no expiry). After
+    /// the expiry, charges fail with `AuthorizationExpired` until this is called again.
+    pub fn renew_authorization(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        new_expiry: u64,
+    ) -> Result<(), Error> {
+        subscription::do_renew_authorization(&env, subscription_id, subscriber, new_expiry)
+    }
+
+    pub fn get_authorization_expiry(env: Env, subscription_id: u32) -> u64 {
+        subscription::get_authorization_expiry(&env, subscription_id)
+    }
+
+    /// Subscriber-only: buy `count` prepaid credits at the plan `amount` each. From then on each
+    /// interval charge consumes one credit instead of the prepaid balance; returns the credits
+    /// now held.
+    pub fn buy_credits(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        count: u32,
+    ) -> Result<u32, Error> {
+        credits::do_buy_credits(&env, subscription_id, subscriber, count)
+    }
+
+    /// Merchant-only: cap the prepaid balance plus unused credits at `max_prepaid` (0 removes
+    /// the cap). Deposits that would exceed it fail with `ExceedsMaxPrepaid`.
+    pub fn set_max_prepaid(
+        env: Env,
+        subscription_id: u32,
+        merchant: Address,
+        max_prepaid: i128,
+    ) -> Result<(), Error> {
+        subscription::do_set_max_prepaid(&env, subscription_id, merchant, max_prepaid)
+    }
+
+    /// Subscriber-only: emit a `LowBalanceEvent` when a charge leaves the prepaid balance below
+    /// `threshold` (0 disables).
+    pub fn set_low_balance_threshold(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        threshold: i128,
+    ) -> Result<(), Error> {
+        subscription::do_set_low_balance_threshold(&env, subscription_id, subscriber, threshold)
+    }
+
+    /// Merchant-only: set the subscription's metadata label (plan name, order id), returned by
+    /// `get_subscription`. At most `MAX_METADATA_LEN` bytes.
+    pub fn set_metadata(
+        env: Env,
+        subscription_id: u32,
+        merchant: Address,
+        metadata: String,
+    ) -> Result<(), Error> {
+        subscription::do_set_metadata(&env, subscription_id, merchant, metadata)
+    }
+
+    /// Merchant-only: set the merchant's reconciliation reference echoed in charged events.
+    pub fn set_external_ref(
+        env: Env,
+        subscription_id: u32,
+        merchant: Address,
+        external_ref: Bytes,
+    ) -> Result<(), Error> {
+        subscription::do_set_external_ref(&env, subscription_id, merchant, external_ref)
+    }
+
+    /// Subscriber-only: only charges of at least `threshold` are flagged `receipt` in
+    /// `SubscriptionChargedEvent` (0 flags every charge).
+    pub fn set_receipt_threshold(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        threshold: i128,
+    ) -> Result<(), Error> {
+        subscription::do_set_receipt_threshold(&env, subscription_id, subscriber, threshold)
+    }
+
+    /// Merchant-only: discount interval charges by `discount_bps` until `expiry_timestamp`.
+    pub fn apply_coupon(
+        env: Env,
+        subscription_id: u32,
+        authorizer: Address,
+        discount_bps: u32,
+        expiry_timestamp: u64,
+    ) -> Result<(), Error> {
+        subscription::do_apply_coupon(
+            &env,
+            subscription_id,
+            authorizer,
+            discount_bps,
+            expiry_timestamp,
+        )
+    }
 
-        validate_status_transition(&sub.status, &SubscriptionStatus::Cancelled)?;
+    /// Merchant-only: choose whether cancelling takes one final charge before the refund.
+    pub fn set_cancel_settlement(
+        env: Env,
+        subscription_id: u32,
+        merchant: Address,
+        settlement: CancelSettlement,
+    ) -> Result<(), Error> {
+        subscription::do_set_cancel_settlement(&env, subscription_id, merchant, settlement)
+    }
 
-        let refund = sub.prepaid_balance;
-        sub.status = SubscriptionStatus::Cancelled;
-        env.storage().instance().set(&subscription_id, &sub);
+    pub fn get_cancel_settlement(env: Env, subscription_id: u32) -> CancelSettlement {
+        subscription::get_cancel_settlement(&env, subscription_id)
+    }
 
-        env.events().publish(
-            (symbol_short!("cancelled"),),
-            SubscriptionCancelledEvent {
-                subscription_id,
-                authorizer,
-                refund_amount: refund,
-            },
-        );
+    /// Subscriber-only: delegate pause, resume and cancel to `manager` (`None` clears it).
+    pub fn set_manager(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        manager: Option<Address>,
+    ) -> Result<(), Error> {
+        subscription::do_set_manager(&env, subscription_id, subscriber, manager)
+    }
 
-        Ok(())
+    pub fn get_manager(env: Env, subscription_id: u32) -> Option<Address> {
+        subscription::get_manager(&env, subscription_id)
+    }
+
+    /// Read-only: whether `who` may perform `action` (`deposit`, `pause`, `resume`, `cancel`)
+    /// on the subscription. Does not require auth; unknown actions return `false`.
+    pub fn auth_check(
+        env: Env,
+        subscription_id: u32,
+        action: Symbol,
+        who: Address,
+    ) -> Result<bool, Error> {
+        queries::auth_check(&env, subscription_id, action, who)
+    }
+
+    /// Subscriber-only: cancel `old_id` and atomically open a subscription to `new_merchant`,
+    /// pre-funded with the old subscription's refund. Returns the new id.
+    pub fn cancel_and_resubscribe(
+        env: Env,
+        old_id: u32,
+        subscriber: Address,
+        new_merchant: Address,
+        amount: i128,
+        interval_seconds: u64,
+    ) -> Result<u32, Error> {
+        subscription::do_cancel_and_resubscribe(
+            &env,
+            old_id,
+            subscriber,
+            new_merchant,
+            amount,
+            interval_seconds,
+        )
+    }
+
+    /// Cancels at the end of the paid period instead of now; see `CancelScheduledEvent`.
+    pub fn cancel_at_period_end(
+        env: Env,
+        subscription_id: u32,
+        authorizer: Address,
+    ) -> Result<(), Error> {
+        subscription::do_cancel_at_period_end(&env, subscription_id, authorizer)
+    }
+
+    /// Subscriber disputes `amount` already charged; the funds are frozen out of the merchant's
+    /// balance until `resolve_dispute`.
+    pub fn dispute_charge(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+        amount: i128,
+    ) -> Result<(), Error> {
+        dispute::do_dispute_charge(&env, subscription_id, subscriber, amount)
+    }
+
+    /// Admin resolves the open dispute: refund the subscriber or return the funds to the merchant.
+    pub fn resolve_dispute(
+        env: Env,
+        subscription_id: u32,
+        admin: Address,
+        refund: bool,
+    ) -> Result<(), Error> {
+        dispute::do_resolve_dispute(&env, subscription_id, admin, refund)
+    }
+
+    pub fn get_dispute(env: Env, subscription_id: u32) -> Option<Dispute> {
+        dispute::get_dispute(&env, subscription_id)
+    }
+
+    /// Admin toggles pausing subscriptions while a dispute is open (default off).
+    pub fn set_auto_pause_on_dispute(env: Env, admin: Address, enabled: bool) -> Result<(), Error> {
+        dispute::do_set_auto_pause_on_dispute(&env, admin, enabled)
+    }
+
+    pub fn get_auto_pause_on_dispute(env: Env) -> bool {
+        dispute::get_auto_pause_on_dispute(&env)
     }
 
     pub fn pause_subscription(
@@ -122,26 +978,26 @@ impl SubscriptionVault {
         authorizer: Address,
     ) -> Result<(), Error> {
         authorizer.require_auth();
-        let mut sub: Subscription = env
+        let sub: Subscription = env
             .storage()
             .instance()
             .get(&subscription_id)
             .ok_or(Error::NotFound)?;
+        subscription::require_lifecycle_authorizer(
+            &env,
+            subscription_id,
+            &sub,
+            &authorizer,
+            symbol_short!("pause"),
+        )?;
 
-        validate_status_transition(&sub.status, &SubscriptionStatus::Paused)?;
-
-        sub.status = SubscriptionStatus::Paused;
-        env.storage().instance().set(&subscription_id, &sub);
-
-        env.events().publish(
-            (symbol_short!("paused"),),
-            SubscriptionPausedEvent {
-                subscription_id,
-                authorizer,
-            },
-        );
+        subscription::apply_manual_pause(&env, subscription_id, sub, authorizer)
+    }
 
-        Ok(())
+    /// Merchant pauses every `Active` subscription billed to them (e.g. during an outage);
+    /// others are skipped. Returns how many were paused.
+    pub fn pause_all_for_merchant(env: Env, merchant: Address) -> Result<u32, Error> {
+        subscription::do_pause_all_for_merchant(&env, merchant)
     }
 
     pub fn resume_subscription(
@@ -150,26 +1006,43 @@ impl SubscriptionVault {
         authorizer: Address,
     ) -> Result<(), Error> {
         authorizer.require_auth();
-        let mut sub: Subscription = env
+        let sub: Subscription = env
             .storage()
             .instance()
             .get(&subscription_id)
             .ok_or(Error::NotFound)?;
+        subscription::require_lifecycle_authorizer(
+            &env,
+            subscription_id,
+            &sub,
+            &authorizer,
+            symbol_short!("resume"),
+        )?;
 
-        validate_status_transition(&sub.status, &SubscriptionStatus::Active)?;
+        subscription::apply_manual_resume(&env, subscription_id, sub, authorizer)
+    }
 
-        sub.status = SubscriptionStatus::Active;
-        env.storage().instance().set(&subscription_id, &sub);
+    /// Subscriber-only: resubscribe on a `Cancelled` subscription instead of creating a new one.
+    /// It becomes `Active` with `last_payment_timestamp` reset to now and auto-renew back on; any
+    /// other status, and installment plans, fail with `InvalidStatusTransition`.
+    pub fn reactivate_subscription(
+        env: Env,
+        subscription_id: u32,
+        subscriber: Address,
+    ) -> Result<(), Error> {
+        subscription::do_reactivate(&env, subscription_id, subscriber)
+    }
 
-        env.events().publish(
-            (symbol_short!("resumed"),),
-            SubscriptionResumedEvent {
-                subscription_id,
-                authorizer,
-            },
-        );
+    /// Merchant resumes every subscription they paused themselves (`pause_subscription` or
+    /// `pause_all_for_merchant`); subscriber- or contract-paused ones are left alone. Returns how
+    /// many were resumed.
+    pub fn resume_all_for_merchant(env: Env, merchant: Address) -> Result<u32, Error> {
+        subscription::do_resume_all_for_merchant(&env, merchant)
+    }
 
-        Ok(())
+    /// Who manually paused the subscription, while it is paused by a manual pause.
+    pub fn get_paused_by(env: Env, subscription_id: u32) -> Option<Address> {
+        subscription::get_paused_by(&env, subscription_id)
     }
 
     /// Merchant-initiated one-off charge: debits `amount` from the subscription's prepaid balance.
@@ -184,12 +1057,179 @@ impl SubscriptionVault {
         subscription::do_charge_one_off(&env, subscription_id, merchant, amount)
     }
 
+    /// Merchant reports `amount` of usage on a `usage_enabled` subscription. Usage accrues until
+    /// it reaches the subscription's `min_charge` and is then deducted in one charge; returns the
+    /// amount deducted (0 while accruing). Subscription must be Active.
+    pub fn charge_usage(
+        env: Env,
+        subscription_id: u32,
+        merchant: Address,
+        amount: i128,
+    ) -> Result<i128, Error> {
+        subscription::do_charge_usage(&env, subscription_id, merchant, amount)
+    }
+
+    /// `charge_usage` for `units`, priced at the `usage_rate` agreed at creation and rounded with
+    /// its `UsageRounding` mode. Returns the amount deducted (0 while accruing).
+    pub fn charge_usage_units(
+        env: Env,
+        subscription_id: u32,
+        merchant: Address,
+        units: i128,
+    ) -> Result<i128, Error> {
+        subscription::do_charge_usage_units(&env, subscription_id, merchant, units)
+    }
+
+    /// Read-only: amount `charge_usage_units` would report for `units`, after rounding.
+    pub fn preview_usage_charge(
+        env: Env,
+        subscription_id: u32,
+        units: i128,
+    ) -> Result<i128, Error> {
+        queries::preview_usage_charge(&env, subscription_id, units)
+    }
+
+    /// Withdraw accrued merchant funds in the default (init) token.
     pub fn withdraw_merchant_funds(env: Env, merchant: Address, amount: i128) -> Result<(), Error> {
         merchant::withdraw_merchant_funds(&env, merchant, amount)
     }
 
+    /// Withdraw accrued merchant funds in a specific token.
+    pub fn withdraw_merchant_token_funds(
+        env: Env,
+        merchant: Address,
+        token: Address,
+        amount: i128,
+    ) -> Result<(), Error> {
+        merchant::withdraw_merchant_token_funds(&env, merchant, token, amount)
+    }
+
+    /// Withdraw `amount` of accrued funds in the default token, paid out in `out_token` through
+    /// the swap adapter. Reverts with `SlippageExceeded` if the swap yields less than `min_out`;
+    /// returns the amount of `out_token` paid.
+    pub fn withdraw_merchant_funds_as(
+        env: Env,
+        merchant: Address,
+        amount: i128,
+        out_token: Address,
+        min_out: i128,
+    ) -> Result<i128, Error> {
+        swap_adapter::withdraw_merchant_funds_as(&env, merchant, amount, out_token, min_out)
+    }
+
+    /// Admin-only: merchant balances below `limit` become sweepable dust (0 disables).
+    pub fn set_dust_limit(env: Env, admin: Address, limit: i128) -> Result<(), Error> {
+        merchant::do_set_dust_limit(&env, admin, limit)
+    }
+
+    pub fn get_dust_limit(env: Env) -> i128 {
+        merchant::get_dust_limit(&env)
+    }
+
+    /// Admin-only: move the listed merchants' `token` balances below the dust limit to the fee
+    /// collector (at most 100 merchants per call). Emits a `DustSweptEvent` per balance and
+    /// returns the total swept.
+    pub fn sweep_dust(
+        env: Env,
+        admin: Address,
+        token: Address,
+        merchants: Vec<Address>,
+    ) -> Result<i128, Error> {
+        merchant::do_sweep_dust(&env, admin, token, merchants)
+    }
+
+    /// Part of the merchant's balance in `token` that can be withdrawn now; charges on
+    /// subscriptions created `with_release_months` stay locked until they vest.
+    pub fn get_merchant_withdrawable(env: Env, merchant: Address, token: Address) -> i128 {
+        merchant::get_withdrawable_balance(&env, &merchant, &token)
+    }
+
+    /// Accrued balance for `merchant` in `token`, including any part still vesting.
+    pub fn get_merchant_balance(env: Env, merchant: Address, token: Address) -> i128 {
+        merchant::get_merchant_balance(&env, &merchant, &token)
+    }
+
+    /// Audit one merchant in the default token: `(credited - withdrawn, stored_balance)`.
+    pub fn reconcile_merchant(env: Env, merchant: Address) -> Result<(i128, i128), Error> {
+        merchant::reconcile_merchant(&env, &merchant)
+    }
+
+    /// Audit one merchant in `token`: `(credited - withdrawn, stored_balance)`.
+    pub fn reconcile_merchant_token(
+        env: Env,
+        merchant: Address,
+        token: Address,
+    ) -> Result<(i128, i128), Error> {
+        merchant::reconcile_merchant_token(&env, &merchant, &token)
+    }
+
+    /// Accrued balances in `token` for several merchants in one call, including locked funds
+    /// (see `get_merchant_withdrawable`).
+    pub fn merchant_balances(
+        env: Env,
+        merchants: Vec<Address>,
+        token: Address,
+    ) -> Map<Address, i128> {
+        merchant::get_merchant_balances(&env, &merchants, &token)
+    }
+
+    /// Sum of all subscriptions' prepaid balances in `token`.
+    pub fn total_prepaid(env: Env, token: Address) -> i128 {
+        subscription::get_total_prepaid(&env, &token)
+    }
+
+    /// Sum of all merchants' unwithdrawn balances in `token`. Together with `total_prepaid` and
+    /// `get_fee_balance` this equals the contract's holdings of `token`.
+    pub fn total_merchant_owed(env: Env, token: Address) -> i128 {
+        merchant::get_total_merchant_owed(&env, &token)
+    }
+
+    /// Contract-wide counters: subscriptions created, currently active and cancelled, and the
+    /// number and gross volume of charges taken.
+    pub fn stats(env: Env) -> VaultStats {
+        history::get_stats(&env)
+    }
+
+    /// Per-token total the contract owes (prepaid + merchant balances + frozen dispute funds +
+    /// accrued fees), for comparison against its actual token balances.
+    pub fn obligations(env: Env) -> Result<Map<Address, i128>, Error> {
+        queries::obligations(&env)
+    }
+
+    /// Contract version; starts at 1 and increases by one with each `upgrade`.
+    pub fn version(env: Env) -> u32 {
+        admin::get_version(&env)
+    }
+
+    /// Admin-only (M-of-N approvals once `set_admin_multisig` is configured): replace the
+    /// contract code with an uploaded WASM and bump `version`.
+    pub fn upgrade(
+        env: Env,
+        admin: Address,
+        new_wasm_hash: soroban_sdk::BytesN<32>,
+    ) -> Result<(), Error> {
+        admin::do_upgrade(&env, admin, new_wasm_hash)
+    }
+
+    /// Admin-only: read a subscription for migration to another deployment.
+    pub fn export_subscription(
+        env: Env,
+        admin: Address,
+        subscription_id: u32,
+    ) -> Result<Subscription, Error> {
+        admin::do_export_subscription(&env, admin, subscription_id)
+    }
+
+    /// Admin-only: store an exported subscription on a fresh deployment. Rejected with
+    /// `NotEmpty` once `create_subscription` has been used on this instance.
+    pub fn import_subscription(env: Env, admin: Address, sub: Subscription) -> Result<u32, Error> {
+        admin::do_import_subscription(&env, admin, sub)
+    }
+
     pub fn get_subscription(env: Env, subscription_id: u32) -> Result<Subscription, Error> {
-        queries::get_subscription(&env, subscription_id)
+        let sub = queries::get_subscription(&env, subscription_id)?;
+        admin::extend_storage_ttl(&env);
+        Ok(sub)
     }
 }
 
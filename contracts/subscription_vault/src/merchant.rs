@@ -1,11 +1,544 @@
-//! Merchant entrypoints: withdraw_merchant_funds.
+//! Merchant entrypoints: register_merchant, set_allowed_intervals, block_subscriber,
+//! withdraw_merchant_funds, and the admin dust sweep.
 //!
 //! **PRs that only change merchant payouts should edit this file only.**
+//!
+//! Merchant balances are accrued per `(merchant, token)` pair so subscriptions billed in
+//! different tokens never share a balance.
+//!
+//! Alongside the balance, lifetime totals credited and withdrawn are kept per pair so
+//! [`reconcile_merchant_token`] can check `credited - withdrawn == balance` for one merchant.
+//!
+//! Charges on subscriptions created with a release schedule are credited in full but locked in
+//! release tranches; withdrawals are limited to the vested part ([`get_withdrawable_balance`]).
+//! Charges credited at the same time on the same schedule share a tranche, fully vested tranches
+//! are dropped on the next credit, and at most [`MAX_RELEASE_TRANCHES`] are kept per pair: past
+//! that the oldest is folded into the next one, which only delays its vesting.
+
+use crate::admin::{get_token, require_admin};
+use crate::fees::get_fee_collector;
+use crate::queries::MAX_LIST_LIMIT;
+use crate::subscription::next_event_seq;
+use crate::types::{
+    DustSweptEvent, Error, MerchantCreditedEvent, MerchantWithdrawalEvent, ReleaseTranche,
+    RELEASE_MONTH_SECONDS,
+};
+use soroban_sdk::{symbol_short, token, Address, Env, Map, Symbol, Vec};
+
+const KEY_MERCHANT_BALANCE: Symbol = symbol_short!("mbal");
+const KEY_MERCHANT_CREDITED: Symbol = symbol_short!("mcredit");
+const KEY_MERCHANT_WITHDRAWN: Symbol = symbol_short!("mwithdrw");
+const KEY_TOTAL_OWED: Symbol = symbol_short!("towed");
+const KEY_REGISTERED: Symbol = symbol_short!("mreg");
+const KEY_ALLOWED_INTERVALS: Symbol = symbol_short!("mintvls");
+const KEY_RELEASES: Symbol = symbol_short!("mrelease");
+const KEY_FUNDED_RESUME: Symbol = symbol_short!("mresfund");
+const KEY_BLOCKED: Symbol = symbol_short!("mblocked");
+const KEY_CHARGE_COUNT: Symbol = symbol_short!("mchgcnt");
+
+/// Release tranches kept per `(merchant, token)` pair.
+pub const MAX_RELEASE_TRANCHES: u32 = 32;
+
+fn balance_key(merchant: &Address, token: &Address) -> (Symbol, Address, Address) {
+    (KEY_MERCHANT_BALANCE, merchant.clone(), token.clone())
+}
+
+fn total_key(kind: Symbol, merchant: &Address, token: &Address) -> (Symbol, Address, Address) {
+    (kind, merchant.clone(), token.clone())
+}
+
+fn get_total(env: &Env, kind: Symbol, merchant: &Address, token: &Address) -> i128 {
+    env.storage()
+        .instance()
+        .get(&total_key(kind, merchant, token))
+        .unwrap_or(0)
+}
+
+fn add_total(
+    env: &Env,
+    kind: Symbol,
+    merchant: &Address,
+    token: &Address,
+    amount: i128,
+) -> Result<(), Error> {
+    let total = get_total(env, kind.clone(), merchant, token)
+        .checked_add(amount)
+        .ok_or(Error::Overflow)?;
+    env.storage()
+        .instance()
+        .set(&total_key(kind, merchant, token), &total);
+    Ok(())
+}
+
+/// Merchant opts in to receiving subscriptions. Requires the merchant's auth; idempotent.
+pub fn do_register_merchant(env: &Env, merchant: Address) -> Result<(), Error> {
+    merchant.require_auth();
+    env.storage()
+        .instance()
+        .set(&(KEY_REGISTERED, merchant), &true);
+    Ok(())
+}
+
+pub fn is_merchant_registered(env: &Env, merchant: &Address) -> bool {
+    env.storage()
+        .instance()
+        .has(&(KEY_REGISTERED, merchant.clone()))
+}
+
+/// Restricts new subscriptions to `merchant` to the given intervals (seconds). An empty set
+/// lifts the restriction. Requires the merchant's auth; existing subscriptions are unaffected.
+pub fn do_set_allowed_intervals(
+    env: &Env,
+    merchant: Address,
+    intervals: Vec<u64>,
+) -> Result<(), Error> {
+    merchant.require_auth();
+    let key = (KEY_ALLOWED_INTERVALS, merchant);
+    if intervals.is_empty() {
+        env.storage().instance().remove(&key);
+    } else {
+        env.storage().instance().set(&key, &intervals);
+    }
+    Ok(())
+}
+
+/// The merchant's allowed intervals; empty means any interval is accepted.
+pub fn get_allowed_intervals(env: &Env, merchant: &Address) -> Vec<u64> {
+    env.storage()
+        .instance()
+        .get(&(KEY_ALLOWED_INTERVALS, merchant.clone()))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Rejects `interval_seconds` with `IntervalNotAllowed` when the merchant has an allowed set
+/// that does not contain it.
+pub fn check_interval_allowed(
+    env: &Env,
+    merchant: &Address,
+    interval_seconds: u64,
+) -> Result<(), Error> {
+    let allowed = get_allowed_intervals(env, merchant);
+    if !allowed.is_empty() && !allowed.contains(interval_seconds) {
+        return Err(Error::IntervalNotAllowed);
+    }
+    Ok(())
+}
+
+/// Adds (`blocked`) or removes `subscriber` from the merchant's blocklist. Blocked subscribers
+/// cannot open new subscriptions to the merchant; existing ones are unaffected. Requires the
+/// merchant's auth; idempotent.
+pub fn do_set_subscriber_blocked(
+    env: &Env,
+    merchant: Address,
+    subscriber: Address,
+    blocked: bool,
+) -> Result<(), Error> {
+    merchant.require_auth();
+    let key = (KEY_BLOCKED, merchant, subscriber);
+    if blocked {
+        env.storage().instance().set(&key, &true);
+    } else {
+        env.storage().instance().remove(&key);
+    }
+    Ok(())
+}
 
-use crate::types::Error;
-use soroban_sdk::{Address, Env};
+pub fn is_subscriber_blocked(env: &Env, merchant: &Address, subscriber: &Address) -> bool {
+    env.storage()
+        .instance()
+        .has(&(KEY_BLOCKED, merchant.clone(), subscriber.clone()))
+}
 
-pub fn withdraw_merchant_funds(_env: &Env, merchant: Address, _amount: i128) -> Result<(), Error> {
+/// When enabled, resuming one of `merchant`'s subscriptions fails with `InsufficientBalance`
+/// unless the next charge is covered. Requires the merchant's auth.
+pub fn do_set_require_funded_resume(
+    env: &Env,
+    merchant: Address,
+    enabled: bool,
+) -> Result<(), Error> {
     merchant.require_auth();
+    let key = (KEY_FUNDED_RESUME, merchant);
+    if enabled {
+        env.storage().instance().set(&key, &true);
+    } else {
+        env.storage().instance().remove(&key);
+    }
     Ok(())
 }
+
+pub fn get_require_funded_resume(env: &Env, merchant: &Address) -> bool {
+    env.storage()
+        .instance()
+        .has(&(KEY_FUNDED_RESUME, merchant.clone()))
+}
+
+/// Balance accrued by `merchant` in `token`, including funds still locked by a release schedule;
+/// see [`get_withdrawable_balance`] for the part that can be withdrawn now.
+pub fn get_merchant_balance(env: &Env, merchant: &Address, token: &Address) -> i128 {
+    env.storage()
+        .instance()
+        .get(&balance_key(merchant, token))
+        .unwrap_or(0)
+}
+
+/// Sum of all merchants' accrued balances in `token` (see [`get_merchant_balance`]).
+pub fn get_total_merchant_owed(env: &Env, token: &Address) -> i128 {
+    env.storage()
+        .instance()
+        .get(&(KEY_TOTAL_OWED, token.clone()))
+        .unwrap_or(0)
+}
+
+fn adjust_total_owed(env: &Env, token: &Address, delta: i128) -> Result<(), Error> {
+    let total = get_total_merchant_owed(env, token)
+        .checked_add(delta)
+        .ok_or(Error::Overflow)?;
+    env.storage()
+        .instance()
+        .set(&(KEY_TOTAL_OWED, token.clone()), &total);
+    Ok(())
+}
+
+/// Accrued balances in `token` for each of `merchants` (zero for merchants with none), including
+/// locked funds; see [`get_withdrawable_balance`].
+pub fn get_merchant_balances(
+    env: &Env,
+    merchants: &Vec<Address>,
+    token: &Address,
+) -> Map<Address, i128> {
+    let mut balances = Map::new(env);
+    for merchant in merchants.iter() {
+        let balance = get_merchant_balance(env, &merchant, token);
+        balances.set(merchant, balance);
+    }
+    balances
+}
+
+/// Credits `amount` of `token` to the merchant's accrued balance (no auth; called by charge paths).
+pub fn credit_merchant(
+    env: &Env,
+    merchant: &Address,
+    token: &Address,
+    amount: i128,
+) -> Result<(), Error> {
+    let balance = get_merchant_balance(env, merchant, token)
+        .checked_add(amount)
+        .ok_or(Error::Overflow)?;
+    env.storage()
+        .instance()
+        .set(&balance_key(merchant, token), &balance);
+    adjust_total_owed(env, token, amount)?;
+    add_total(env, KEY_MERCHANT_CREDITED, merchant, token, amount)
+}
+
+/// [`credit_merchant`], then locks the credited amount to vest over `release_months` months
+/// when that is non-zero.
+pub fn credit_merchant_with_release(
+    env: &Env,
+    merchant: &Address,
+    token: &Address,
+    amount: i128,
+    release_months: u32,
+) -> Result<(), Error> {
+    credit_merchant(env, merchant, token, amount)?;
+    if release_months == 0 || amount == 0 {
+        return Ok(());
+    }
+    let now = env.ledger().timestamp();
+    let mut tranches = Vec::new(env);
+    for t in get_release_tranches(env, merchant, token).iter() {
+        if unvested(&t, now) > 0 {
+            tranches.push_back(t);
+        }
+    }
+    match tranches.last() {
+        Some(mut last) if last.start == now && last.months == release_months => {
+            last.amount = last.amount.checked_add(amount).ok_or(Error::Overflow)?;
+            tranches.set(tranches.len() - 1, last);
+        }
+        _ => tranches.push_back(ReleaseTranche {
+            amount,
+            start: now,
+            months: release_months,
+        }),
+    }
+    while tranches.len() > MAX_RELEASE_TRANCHES {
+        // Folding keeps the later start and the longer schedule, so nothing vests early.
+        let oldest = tranches.pop_front_unchecked();
+        let next = tranches.get_unchecked(0);
+        tranches.set(
+            0,
+            ReleaseTranche {
+                amount: next
+                    .amount
+                    .checked_add(oldest.amount)
+                    .ok_or(Error::Overflow)?,
+                start: next.start,
+                months: next.months.max(oldest.months),
+            },
+        );
+    }
+    env.storage()
+        .instance()
+        .set(&(KEY_RELEASES, merchant.clone(), token.clone()), &tranches);
+    Ok(())
+}
+
+/// Emits `MerchantCreditedEvent` for `amount` credited to `merchant` by a charge on
+/// `subscription_id`, and counts the charge toward the next withdrawal's `charge_count`.
+pub fn publish_merchant_credited(
+    env: &Env,
+    subscription_id: u32,
+    merchant: &Address,
+    token: &Address,
+    amount: i128,
+) {
+    let count_key = charge_count_key(merchant, token);
+    let count = get_charges_since_withdrawal(env, merchant, token).saturating_add(1);
+    env.storage().instance().set(&count_key, &count);
+    env.events().publish(
+        (symbol_short!("credited"), subscription_id),
+        MerchantCreditedEvent {
+            subscription_id,
+            merchant: merchant.clone(),
+            token: token.clone(),
+            amount,
+            new_balance: get_merchant_balance(env, merchant, token),
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+}
+
+fn charge_count_key(merchant: &Address, token: &Address) -> (Symbol, Address, Address) {
+    (KEY_CHARGE_COUNT, merchant.clone(), token.clone())
+}
+
+/// Charges that credited `merchant` in `token` since its last withdrawal in that token.
+pub fn get_charges_since_withdrawal(env: &Env, merchant: &Address, token: &Address) -> u32 {
+    env.storage()
+        .instance()
+        .get(&charge_count_key(merchant, token))
+        .unwrap_or(0)
+}
+
+fn get_release_tranches(env: &Env, merchant: &Address, token: &Address) -> Vec<ReleaseTranche> {
+    env.storage()
+        .instance()
+        .get(&(KEY_RELEASES, merchant.clone(), token.clone()))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Part of `tranche` still locked at `now`: one `months`-th vests per elapsed release month.
+fn unvested(tranche: &ReleaseTranche, now: u64) -> i128 {
+    let elapsed = now.saturating_sub(tranche.start) / RELEASE_MONTH_SECONDS;
+    let months = u64::from(tranche.months);
+    if elapsed >= months {
+        return 0;
+    }
+    // amount * elapsed / months, split so that it cannot overflow for any amount.
+    let (elapsed, months) = (i128::from(elapsed as u32), i128::from(tranche.months));
+    let vested = tranche.amount / months * elapsed + tranche.amount % months * elapsed / months;
+    tranche.amount - vested
+}
+
+/// Amount of the merchant's balance in `token` that has not vested yet.
+pub fn get_locked_balance(env: &Env, merchant: &Address, token: &Address) -> i128 {
+    let now = env.ledger().timestamp();
+    get_release_tranches(env, merchant, token)
+        .iter()
+        .map(|t| unvested(&t, now))
+        .sum()
+}
+
+/// Part of the merchant's balance in `token` that can be withdrawn now (balance minus the
+/// unvested amount).
+pub fn get_withdrawable_balance(env: &Env, merchant: &Address, token: &Address) -> i128 {
+    (get_merchant_balance(env, merchant, token) - get_locked_balance(env, merchant, token)).max(0)
+}
+
+/// Drops tranches that have fully vested.
+fn prune_release_tranches(env: &Env, merchant: &Address, token: &Address) {
+    let now = env.ledger().timestamp();
+    let mut remaining = Vec::new(env);
+    for t in get_release_tranches(env, merchant, token).iter() {
+        if unvested(&t, now) > 0 {
+            remaining.push_back(t);
+        }
+    }
+    let key = (KEY_RELEASES, merchant.clone(), token.clone());
+    if remaining.is_empty() {
+        env.storage().instance().remove(&key);
+    } else {
+        env.storage().instance().set(&key, &remaining);
+    }
+}
+
+/// Takes `amount` of `token` out of the merchant's accrued balance without paying it out (no auth;
+/// used to freeze disputed funds). Counted as withdrawn so reconciliation still balances.
+pub fn debit_merchant(
+    env: &Env,
+    merchant: &Address,
+    token: &Address,
+    amount: i128,
+) -> Result<(), Error> {
+    let balance = get_merchant_balance(env, merchant, token);
+    if balance < amount {
+        return Err(Error::InsufficientBalance);
+    }
+    env.storage()
+        .instance()
+        .set(&balance_key(merchant, token), &(balance - amount));
+    adjust_total_owed(env, token, -amount)?;
+    add_total(env, KEY_MERCHANT_WITHDRAWN, merchant, token, amount)
+}
+
+/// Withdraws from the merchant's balance in the default (init) token.
+pub fn withdraw_merchant_funds(env: &Env, merchant: Address, amount: i128) -> Result<(), Error> {
+    let token = get_token(env)?;
+    withdraw_merchant_token_funds(env, merchant, token, amount)
+}
+
+/// Withdraws `amount` of `token` from the merchant's accrued balance to the merchant.
+pub fn withdraw_merchant_token_funds(
+    env: &Env,
+    merchant: Address,
+    token: Address,
+    amount: i128,
+) -> Result<(), Error> {
+    merchant.require_auth();
+    let charge_count = debit_withdrawal(env, &merchant, &token, amount)?;
+
+    token::Client::new(env, &token).transfer(&env.current_contract_address(), &merchant, &amount);
+
+    env.events().publish(
+        (symbol_short!("withdraw"),),
+        MerchantWithdrawalEvent {
+            merchant,
+            token,
+            amount,
+            charge_count,
+        },
+    );
+    Ok(())
+}
+
+/// Takes `amount` out of the merchant's withdrawable balance in `token` for a payout and resets
+/// the charge counter. Returns the charges counted since the previous withdrawal. The caller
+/// checks auth, pays out and emits `MerchantWithdrawalEvent`.
+pub fn debit_withdrawal(
+    env: &Env,
+    merchant: &Address,
+    token: &Address,
+    amount: i128,
+) -> Result<u32, Error> {
+    if amount <= 0 {
+        return Err(Error::InvalidAmount);
+    }
+    if get_withdrawable_balance(env, merchant, token) < amount {
+        return Err(Error::InsufficientBalance);
+    }
+    prune_release_tranches(env, merchant, token);
+    let balance = get_merchant_balance(env, merchant, token);
+    let remaining = balance.checked_sub(amount).ok_or(Error::Overflow)?;
+    env.storage()
+        .instance()
+        .set(&balance_key(merchant, token), &remaining);
+    add_total(env, KEY_MERCHANT_WITHDRAWN, merchant, token, amount)?;
+    adjust_total_owed(env, token, -amount)?;
+
+    let charge_count = get_charges_since_withdrawal(env, merchant, token);
+    env.storage()
+        .instance()
+        .remove(&charge_count_key(merchant, token));
+    Ok(charge_count)
+}
+
+/// Sets the dust limit: `sweep_dust` moves merchant balances strictly below it to the fee
+/// collector. Zero (the default) disables sweeping. Admin only.
+pub fn do_set_dust_limit(env: &Env, admin: Address, limit: i128) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    if limit < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "dust_limit"), &limit);
+    Ok(())
+}
+
+pub fn get_dust_limit(env: &Env) -> i128 {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "dust_limit"))
+        .unwrap_or(0)
+}
+
+/// Moves each listed merchant's non-zero `token` balance below the dust limit to the fee
+/// collector. At most [`MAX_LIST_LIMIT`] merchants per call (`InvalidAmount`), so the cost stays
+/// bounded; sweep more in further calls. Admin only; returns the total swept. Swept amounts count
+/// as withdrawn, so reconciliation still balances.
+pub fn do_sweep_dust(
+    env: &Env,
+    admin: Address,
+    token: Address,
+    merchants: Vec<Address>,
+) -> Result<i128, Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    if merchants.len() > MAX_LIST_LIMIT {
+        return Err(Error::InvalidAmount);
+    }
+    let limit = get_dust_limit(env);
+    if limit == 0 {
+        return Ok(0);
+    }
+    let fee_collector = get_fee_collector(env)?;
+    let mut swept = 0i128;
+    for merchant in merchants.iter() {
+        let balance = get_merchant_balance(env, &merchant, &token);
+        if balance == 0 || balance >= limit || get_locked_balance(env, &merchant, &token) > 0 {
+            continue;
+        }
+        debit_merchant(env, &merchant, &token, balance)?;
+        token::Client::new(env, &token).transfer(
+            &env.current_contract_address(),
+            &fee_collector,
+            &balance,
+        );
+        swept = swept.checked_add(balance).ok_or(Error::Overflow)?;
+        env.events().publish(
+            (symbol_short!("dust"),),
+            DustSweptEvent {
+                merchant,
+                token: token.clone(),
+                amount: balance,
+                fee_collector: fee_collector.clone(),
+            },
+        );
+    }
+    Ok(swept)
+}
+
+/// Returns `(credited - withdrawn, stored_balance)` for `merchant` in `token`. The two match
+/// unless the balance diverged from the charge and withdrawal history.
+pub fn reconcile_merchant_token(
+    env: &Env,
+    merchant: &Address,
+    token: &Address,
+) -> Result<(i128, i128), Error> {
+    let expected = get_total(env, KEY_MERCHANT_CREDITED, merchant, token)
+        .checked_sub(get_total(env, KEY_MERCHANT_WITHDRAWN, merchant, token))
+        .ok_or(Error::Overflow)?;
+    Ok((expected, get_merchant_balance(env, merchant, token)))
+}
+
+/// [`reconcile_merchant_token`] for the default (init) token.
+pub fn reconcile_merchant(env: &Env, merchant: &Address) -> Result<(i128, i128), Error> {
+    let token = get_token(env)?;
+    reconcile_merchant_token(env, merchant, &token)
+}
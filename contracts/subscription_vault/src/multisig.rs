@@ -0,0 +1,121 @@
+//! M-of-N approvals for high-value admin actions: set_admin_multisig, approve_action.
+//!
+//! **PRs that only change admin approval rules should edit this file only.**
+//!
+//! Without a multisig configured, protected actions (`upgrade`, `force_cancel`) need only the
+//! admin's auth. Once the admin sets signers and a non-zero threshold, each protected action is
+//! identified by an action hash (see [`action_hash`]); signers approve that hash with
+//! `approve_action` and the action runs when a signer calls it with at least `threshold`
+//! approvals recorded. Approvals are consumed by the execution, and only signers in the current
+//! set count towards the threshold.
+//!
+//! Once multisig is on, changing it is itself a protected action ([`set_multisig_action_hash`]),
+//! so the admin alone cannot lower the threshold or swap in another signer set.
+
+use crate::admin::require_admin;
+use crate::types::Error;
+use soroban_sdk::xdr::ToXdr;
+use soroban_sdk::{symbol_short, Address, BytesN, Env, IntoVal, Symbol, Val, Vec};
+
+const KEY_SIGNERS: Symbol = symbol_short!("msig_set");
+const KEY_THRESHOLD: Symbol = symbol_short!("msig_thr");
+const KEY_APPROVALS: Symbol = symbol_short!("msig_ap");
+
+/// Hash signers approve to replace the multisig with `(signers, threshold)`.
+pub fn set_multisig_action_hash(env: &Env, signers: &Vec<Address>, threshold: u32) -> BytesN<32> {
+    action_hash(env, symbol_short!("set_msig"), (signers.clone(), threshold))
+}
+
+/// Replaces the signer set and threshold. `threshold` 0 turns multisig off; otherwise it must not
+/// exceed the number of signers (`InvalidAmount`). Without multisig `caller` must be the admin;
+/// with it, the change needs approvals of [`set_multisig_action_hash`] like any protected action.
+pub fn do_set_admin_multisig(
+    env: &Env,
+    caller: Address,
+    signers: Vec<Address>,
+    threshold: u32,
+) -> Result<(), Error> {
+    if threshold > signers.len() {
+        return Err(Error::InvalidAmount);
+    }
+    let hash = set_multisig_action_hash(env, &signers, threshold);
+    require_approved(env, &caller, &hash)?;
+    env.storage().instance().set(&KEY_SIGNERS, &signers);
+    env.storage().instance().set(&KEY_THRESHOLD, &threshold);
+    Ok(())
+}
+
+/// `(signers, threshold)`; threshold 0 means protected actions need only the admin.
+pub fn get_admin_multisig(env: &Env) -> (Vec<Address>, u32) {
+    let signers = env
+        .storage()
+        .instance()
+        .get(&KEY_SIGNERS)
+        .unwrap_or(Vec::new(env));
+    let threshold = env.storage().instance().get(&KEY_THRESHOLD).unwrap_or(0);
+    (signers, threshold)
+}
+
+/// Hash identifying a protected action and its arguments, e.g.
+/// `action_hash(env, symbol_short!("upgrade"), wasm_hash)`.
+pub fn action_hash(env: &Env, action: Symbol, args: impl IntoVal<Env, Val>) -> BytesN<32> {
+    let args: Val = args.into_val(env);
+    env.crypto().sha256(&(action, args).to_xdr(env)).to_bytes()
+}
+
+fn approvals_key(hash: &BytesN<32>) -> (Symbol, BytesN<32>) {
+    (KEY_APPROVALS, hash.clone())
+}
+
+/// Signers that have approved `hash` and not yet been consumed by its execution.
+pub fn get_approvals(env: &Env, hash: &BytesN<32>) -> Vec<Address> {
+    env.storage()
+        .instance()
+        .get(&approvals_key(hash))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Approvals of `hash` from signers in `signers`; approvals left by removed signers are ignored.
+fn approval_count(env: &Env, hash: &BytesN<32>, signers: &Vec<Address>) -> u32 {
+    get_approvals(env, hash)
+        .iter()
+        .filter(|a| signers.contains(a))
+        .count() as u32
+}
+
+/// Records `signer`'s approval of `hash`; approving twice counts once. Returns the number of
+/// approvals from current signers now recorded.
+pub fn do_approve_action(env: &Env, signer: Address, hash: BytesN<32>) -> Result<u32, Error> {
+    signer.require_auth();
+    let (signers, _) = get_admin_multisig(env);
+    if !signers.contains(&signer) {
+        return Err(Error::Unauthorized);
+    }
+    let mut approvals = get_approvals(env, &hash);
+    if !approvals.contains(&signer) {
+        approvals.push_back(signer);
+        env.storage()
+            .instance()
+            .set(&approvals_key(&hash), &approvals);
+    }
+    Ok(approval_count(env, &hash, &signers))
+}
+
+/// Authorizes `caller` to run the protected action `hash`. Without multisig `caller` must be
+/// the admin. With multisig `caller` must be a signer and `hash` must have at least `threshold`
+/// approvals from current signers, which are then cleared.
+pub fn require_approved(env: &Env, caller: &Address, hash: &BytesN<32>) -> Result<(), Error> {
+    caller.require_auth();
+    let (signers, threshold) = get_admin_multisig(env);
+    if threshold == 0 {
+        if *caller != require_admin(env)? {
+            return Err(Error::Unauthorized);
+        }
+        return Ok(());
+    }
+    if !signers.contains(caller) || approval_count(env, hash, &signers) < threshold {
+        return Err(Error::Unauthorized);
+    }
+    env.storage().instance().remove(&approvals_key(hash));
+    Ok(())
+}
@@ -1,9 +1,25 @@
-//! Read-only entrypoints and helpers: get_subscription, estimate_topup.
+//! Read-only entrypoints and helpers: get_subscription, estimate_topup, can_charge.
 //!
 //! **PRs that only add or change read-only/query behavior should edit this file only.**
 
-use crate::types::{Error, Subscription};
-use soroban_sdk::Env;
+use crate::charge_core::{
+    autopay_covers, check_charge_due, effective_charge_amount, final_settlement_amount,
+    grace_period_end, remaining_spending_cap,
+};
+use crate::credits::{get_credit_value, in_credit_mode, next_credit_cost};
+use crate::dispute::get_frozen_dispute_funds;
+use crate::fees::{cancel_fee, get_fee_balance, split_fee};
+use crate::index::get_index;
+use crate::installments::installment_remainder;
+use crate::merchant::get_total_merchant_owed;
+use crate::state_machine::validate_status_transition;
+use crate::subscription::{
+    authorization_expired, get_auto_renew, get_autopay, get_cancel_pending, get_cancel_settlement,
+    get_total_prepaid, get_tracked_tokens, is_lifecycle_authorizer, subscription_count,
+    usage_charge_for_units,
+};
+use crate::types::{Error, Quote, Subscription, SubscriptionStatus};
+use soroban_sdk::{symbol_short, Address, Env, Map, Symbol, Vec};
 
 pub fn get_subscription(env: &Env, subscription_id: u32) -> Result<Subscription, Error> {
     env.storage()
@@ -35,3 +51,329 @@ pub fn estimate_topup_for_intervals(
         .max(0);
     Ok(topup)
 }
+
+/// Number of full billing intervals the current prepaid balance covers
+/// (`prepaid_balance / amount`).
+pub fn covered_intervals(env: &Env, subscription_id: u32) -> Result<u64, Error> {
+    let sub = get_subscription(env, subscription_id)?;
+    if sub.amount <= 0 || sub.prepaid_balance <= 0 {
+        return Ok(0);
+    }
+    let intervals = sub.prepaid_balance / sub.amount;
+    Ok(u64::try_from(intervals).unwrap_or(u64::MAX))
+}
+
+/// Ids greater than `start_after` of subscriptions with `start <= created_at <= end`, ascending,
+/// at most `limit` capped to [`MAX_LIST_LIMIT`]. Purged subscriptions are skipped. Ids are
+/// assigned in creation order (imports keep the source contract's order), so the scan stops at
+/// the first subscription created after `end`.
+pub fn created_between(
+    env: &Env,
+    start: u64,
+    end: u64,
+    start_after: Option<u32>,
+    limit: u32,
+) -> Vec<u32> {
+    let limit = limit.min(MAX_LIST_LIMIT);
+    let first = match start_after {
+        Some(id) => id.saturating_add(1),
+        None => 0,
+    };
+    let mut ids = Vec::new(env);
+    for id in first..subscription_count(env) {
+        if ids.len() >= limit {
+            break;
+        }
+        let Some(sub) = env.storage().instance().get::<_, Subscription>(&id) else {
+            continue;
+        };
+        if sub.created_at > end {
+            break;
+        }
+        if sub.created_at >= start {
+            ids.push_back(id);
+        }
+    }
+    ids
+}
+
+/// Largest page returned by [`list_subscriptions`].
+pub const MAX_LIST_LIMIT: u32 = 100;
+
+/// Ids greater than `start_after` (all ids when `None`), ascending, at most `limit` capped to
+/// [`MAX_LIST_LIMIT`]. Purged subscriptions are skipped. Pass the last id of a page as the next
+/// `start_after`.
+pub fn list_subscriptions(env: &Env, start_after: Option<u32>, limit: u32) -> Vec<u32> {
+    let limit = limit.min(MAX_LIST_LIMIT);
+    let first = match start_after {
+        Some(id) => id.saturating_add(1),
+        None => 0,
+    };
+    let mut ids = Vec::new(env);
+    for id in first..subscription_count(env) {
+        if ids.len() >= limit {
+            break;
+        }
+        if env.storage().instance().has(&id) {
+            ids.push_back(id);
+        }
+    }
+    ids
+}
+
+/// [`list_subscriptions`] filtered to subscriptions whose stored status is `status`. There is no
+/// status index, so the id range after `start_after` is scanned until `limit` matches are found;
+/// sparse statuses make this proportionally more expensive.
+pub fn list_by_status(
+    env: &Env,
+    status: SubscriptionStatus,
+    start_after: Option<u32>,
+    limit: u32,
+) -> Vec<u32> {
+    let limit = limit.min(MAX_LIST_LIMIT);
+    let first = match start_after {
+        Some(id) => id.saturating_add(1),
+        None => 0,
+    };
+    let mut ids = Vec::new(env);
+    for id in first..subscription_count(env) {
+        if ids.len() >= limit {
+            break;
+        }
+        let sub: Option<Subscription> = env.storage().instance().get(&id);
+        if sub.is_some_and(|s| s.status == status) {
+            ids.push_back(id);
+        }
+    }
+    ids
+}
+
+/// Earliest time the next interval charge can be taken: one interval after the last payment,
+/// or after the start for a subscription that has not been charged yet.
+pub fn next_charge_timestamp(env: &Env, subscription_id: u32) -> Result<u64, Error> {
+    let sub = get_subscription(env, subscription_id)?;
+    sub.last_payment_timestamp
+        .checked_add(sub.interval_seconds)
+        .map(|t| t.max(sub.start_timestamp))
+        .ok_or(Error::Overflow)
+}
+
+/// Status as of now, without mutating storage. A stored `Active` subscription whose paid period
+/// has ended is reported `Cancelled` if the next charge attempt would end it instead of billing
+/// it: auto-renew is off (lapse) or a `cancel_at_period_end` is pending. Otherwise the stored
+/// status is returned.
+pub fn effective_status(env: &Env, subscription_id: u32) -> Result<SubscriptionStatus, Error> {
+    let sub = get_subscription(env, subscription_id)?;
+    if sub.status != SubscriptionStatus::Active {
+        return Ok(sub.status);
+    }
+    let period_ended = env.ledger().timestamp() >= next_charge_timestamp(env, subscription_id)?;
+    let ends_at_period_end =
+        !get_auto_renew(env, subscription_id) || get_cancel_pending(env, subscription_id).is_some();
+    if period_ended && ends_at_period_end {
+        Ok(SubscriptionStatus::Cancelled)
+    } else {
+        Ok(sub.status)
+    }
+}
+
+/// Expected interval charges to `merchant` over the next `window_seconds`: for each `Active`
+/// subscription, the number of charges falling due in `[now, now + window_seconds]` (a charge
+/// already due counts now), limited by how many the prepaid balance and spending cap can cover.
+/// Gross amounts, before platform fees; coupons are ignored.
+pub fn merchant_projected_revenue(
+    env: &Env,
+    merchant: &Address,
+    window_seconds: u64,
+) -> Result<i128, Error> {
+    let now = env.ledger().timestamp();
+    let end = now.checked_add(window_seconds).ok_or(Error::Overflow)?;
+    let mut total = 0i128;
+    for id in get_index(env, merchant, true).iter() {
+        let Ok(sub) = get_subscription(env, id) else {
+            continue;
+        };
+        if sub.status != SubscriptionStatus::Active {
+            continue;
+        }
+        let first = next_charge_timestamp(env, id)?.max(now);
+        if first > end {
+            continue;
+        }
+        let due = (end - first) / sub.interval_seconds + 1;
+        let funds = sub.prepaid_balance.min(remaining_spending_cap(&sub));
+        let affordable = u64::try_from(funds / sub.amount).unwrap_or(0);
+        let charges = i128::from(due.min(affordable));
+        let revenue = sub.amount.checked_mul(charges).ok_or(Error::Overflow)?;
+        total = total.checked_add(revenue).ok_or(Error::Overflow)?;
+    }
+    Ok(total)
+}
+
+/// Quotes the next interval charge at the time it becomes due (or now, if it already is), using
+/// the same discount and fee split as [`crate::charge_core::charge_one`].
+pub fn quote_next_charge(env: &Env, subscription_id: u32) -> Result<Quote, Error> {
+    let sub = get_subscription(env, subscription_id)?;
+    let charge_timestamp =
+        next_charge_timestamp(env, subscription_id)?.max(env.ledger().timestamp());
+    // The final installment of an installment plan also carries the division remainder.
+    let base = sub
+        .amount
+        .checked_add(installment_remainder(env, subscription_id, &sub))
+        .ok_or(Error::Overflow)?;
+    let net = effective_charge_amount(&sub, charge_timestamp)?
+        .checked_add(base - sub.amount)
+        .ok_or(Error::Overflow)?;
+    let (merchant_share, platform_fee) = split_fee(env, net)?;
+    Ok(Quote {
+        base,
+        discount: base.checked_sub(net).ok_or(Error::Overflow)?,
+        platform_fee,
+        merchant_share,
+        net,
+        charge_timestamp,
+    })
+}
+
+/// Amount `charge_subscription` would debit from the prepaid balance if called now: the base
+/// amount less any active coupon, plus the remainder on an installment plan's final charge. The
+/// platform fee is split out of this amount, not added to it (see [`quote_next_charge`]).
+/// Whether the charge would succeed is reported by [`can_charge`].
+pub fn preview_charge(env: &Env, subscription_id: u32) -> Result<i128, Error> {
+    let sub = get_subscription(env, subscription_id)?;
+    effective_charge_amount(&sub, env.ledger().timestamp())?
+        .checked_add(installment_remainder(env, subscription_id, &sub))
+        .ok_or(Error::Overflow)
+}
+
+/// Usage charge `charge_usage_units` would report for `units`, rounded with the subscription's
+/// rounding mode. Nothing is written.
+pub fn preview_usage_charge(env: &Env, subscription_id: u32, units: i128) -> Result<i128, Error> {
+    get_subscription(env, subscription_id)?;
+    usage_charge_for_units(env, subscription_id, units)
+}
+
+/// Refund `cancel_subscription` would pay if called now: the prepaid balance and unused credits,
+/// less the merchant's final settlement charge (see `set_cancel_settlement`) and the cancel fee.
+/// Nothing is written.
+/// Fails like the cancel would on a status that cannot be cancelled.
+pub fn preview_cancel(env: &Env, subscription_id: u32) -> Result<i128, Error> {
+    let sub = get_subscription(env, subscription_id)?;
+    validate_status_transition(&sub.status, &SubscriptionStatus::Cancelled)?;
+    let settlement = if sub.status == SubscriptionStatus::Cancelled {
+        0
+    } else {
+        let mode = get_cancel_settlement(env, subscription_id);
+        final_settlement_amount(env, subscription_id, &sub, &mode)?
+    };
+    let balance = sub.prepaid_balance + get_credit_value(env, subscription_id) - settlement;
+    Ok(balance - cancel_fee(env, balance)?)
+}
+
+/// Whether the subscription is past due: `Active`, its charge is due but not covered by the
+/// prepaid balance, and its grace window has not ended yet.
+pub fn is_past_due(env: &Env, subscription_id: u32) -> Result<bool, Error> {
+    let sub = get_subscription(env, subscription_id)?;
+    let now = env.ledger().timestamp();
+    if sub.status != SubscriptionStatus::Active {
+        return Ok(false);
+    }
+    let due_at = sub
+        .last_payment_timestamp
+        .checked_add(sub.interval_seconds)
+        .ok_or(Error::Overflow)?;
+    let unfunded = sub.prepaid_balance < effective_charge_amount(&sub, now)?;
+    Ok(unfunded && now >= due_at && now < grace_period_end(&sub)?)
+}
+
+/// Reports whether `charge_subscription` would currently succeed for this subscription.
+///
+/// Returns `(true, 0)` when a charge would go through, otherwise `(false, code)` where `code` is
+/// the [`Error::to_code`] of the first blocking condition, checked in the same order as the
+/// charge itself: `NotActive`, `NotStarted`, `Replay`, `IntervalNotElapsed`, `NotActive` again
+/// when auto-renew is off (the charge would lapse the subscription), `SpendingCapReached`, then
+/// `InsufficientBalance`. Never mutates state.
+pub fn can_charge(env: &Env, subscription_id: u32) -> Result<(bool, u32), Error> {
+    let sub = get_subscription(env, subscription_id)?;
+    let now = env.ledger().timestamp();
+    let credit_mode = in_credit_mode(env, subscription_id);
+    let amount = if credit_mode {
+        next_credit_cost(env, subscription_id, &sub)
+    } else {
+        effective_charge_amount(&sub, now)?
+            .checked_add(installment_remainder(env, subscription_id, &sub))
+            .ok_or(Error::Overflow)?
+    };
+
+    let blocking = if sub.status != SubscriptionStatus::Active {
+        Err(Error::NotActive)
+    } else if now < sub.start_timestamp {
+        Err(Error::NotStarted)
+    } else if let Err(e) = check_charge_due(env, subscription_id, &sub, now) {
+        Err(e)
+    } else if !get_auto_renew(env, subscription_id) {
+        // A due charge would lapse the subscription rather than bill it.
+        Err(Error::NotActive)
+    } else if authorization_expired(env, subscription_id, now) {
+        Err(Error::AuthorizationExpired)
+    } else if remaining_spending_cap(&sub) < amount {
+        Err(Error::SpendingCapReached)
+    } else if credit_mode {
+        if sub.credits == 0 {
+            Err(Error::InsufficientBalance)
+        } else {
+            Ok(())
+        }
+    } else if sub.prepaid_balance < amount
+        && !(get_autopay(env, subscription_id)
+            && autopay_covers(env, &sub, amount - sub.prepaid_balance))
+    {
+        Err(Error::InsufficientBalance)
+    } else {
+        Ok(())
+    };
+
+    match blocking {
+        Ok(()) => Ok((true, 0)),
+        Err(e) => Ok((false, e.to_code())),
+    }
+}
+
+/// Whether `who` is allowed to perform `action` on the subscription, mirroring the checks in the
+/// corresponding entrypoint (auth itself is not checked). Anyone may fund a subscription with
+/// `deposit`; `pause`, `resume` and `cancel` are limited to the subscriber, merchant or manager.
+pub fn auth_check(
+    env: &Env,
+    subscription_id: u32,
+    action: Symbol,
+    who: Address,
+) -> Result<bool, Error> {
+    let sub = get_subscription(env, subscription_id)?;
+    let allowed = if action == symbol_short!("deposit") {
+        true
+    } else if action == symbol_short!("pause")
+        || action == symbol_short!("resume")
+        || action == symbol_short!("cancel")
+    {
+        is_lifecycle_authorizer(env, subscription_id, &sub, &who)
+    } else {
+        false
+    };
+    Ok(allowed)
+}
+
+/// What the contract owes in each token it holds: prepaid balances, merchants' unwithdrawn
+/// balances, frozen dispute funds and accrued platform fees. Solvent when every token's
+/// contract balance is at least its obligation.
+pub fn obligations(env: &Env) -> Result<Map<Address, i128>, Error> {
+    let mut owed = Map::new(env);
+    for token in get_tracked_tokens(env).iter() {
+        let total = get_total_prepaid(env, &token)
+            .checked_add(get_total_merchant_owed(env, &token))
+            .and_then(|t| t.checked_add(get_frozen_dispute_funds(env, &token)))
+            .and_then(|t| t.checked_add(get_fee_balance(env, &token)))
+            .ok_or(Error::Overflow)?;
+        owed.set(token, total);
+    }
+    Ok(owed)
+}
@@ -18,7 +18,8 @@ use crate::types::{Error, SubscriptionStatus};
 /// | Paused            | Cancelled           | Yes     |
 /// | InsufficientBalance | Active            | Yes     |
 /// | InsufficientBalance | Cancelled         | Yes     |
-/// | Cancelled         | *any*               | No      |
+/// | Cancelled         | Active              | Yes (`reactivate_subscription` only) |
+/// | Cancelled         | Paused, InsufficientBalance | No |
 /// | *any*             | Same status         | Yes (idempotent) |
 ///
 /// # Arguments
@@ -49,7 +50,8 @@ pub fn validate_status_transition(
                 SubscriptionStatus::Active | SubscriptionStatus::Cancelled
             )
         }
-        SubscriptionStatus::Cancelled => false,
+        // Only `reactivate_subscription` performs this; `resume_subscription` refuses it.
+        SubscriptionStatus::Cancelled => matches!(to, SubscriptionStatus::Active),
         SubscriptionStatus::InsufficientBalance => {
             matches!(
                 to,
@@ -76,7 +78,7 @@ pub fn get_allowed_transitions(status: &SubscriptionStatus) -> &'static [Subscri
             SubscriptionStatus::InsufficientBalance,
         ],
         SubscriptionStatus::Paused => &[SubscriptionStatus::Active, SubscriptionStatus::Cancelled],
-        SubscriptionStatus::Cancelled => &[],
+        SubscriptionStatus::Cancelled => &[SubscriptionStatus::Active],
         SubscriptionStatus::InsufficientBalance => {
             &[SubscriptionStatus::Active, SubscriptionStatus::Cancelled]
         }
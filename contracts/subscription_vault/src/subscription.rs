@@ -2,11 +2,169 @@
 //!
 //! **PRs that only change subscription lifecycle or billing should edit this file only.**
 
-use crate::admin::require_admin;
-use crate::charge_core::charge_one;
-use crate::queries::get_subscription;
-use crate::types::{Error, OneOffChargedEvent, Subscription, SubscriptionStatus};
-use soroban_sdk::{symbol_short, Address, Env, Symbol};
+use crate::admin::{
+    extend_storage_ttl, get_auto_charge_on_resume, get_default_grace_seconds, get_max_retries,
+    get_max_subs_per_subscriber, get_require_registration, get_token, require_admin,
+};
+use crate::charge_core::{
+    autopay_covers, charge_final_settlement, charge_interval, charge_one, next_charge_nonce,
+    record_total_charged, ChargeOutcome,
+};
+use crate::credits::{get_credit_value, leave_credit_mode, release_credits};
+use crate::fees::{accrue_fee, cancel_fee, split_fee, BPS_DENOMINATOR};
+use crate::history::{record_deposit, record_merchant_revenue, record_status};
+use crate::index::{add_to_indexes, count_open, get_index};
+use crate::installments::is_installment_plan;
+use crate::merchant::{
+    check_interval_allowed, credit_merchant, get_require_funded_resume, is_merchant_registered,
+    is_subscriber_blocked, publish_merchant_credited,
+};
+use crate::multisig::{action_hash, require_approved};
+use crate::queries::{can_charge, get_subscription};
+use crate::state_machine::validate_status_transition;
+use crate::types::{
+    BalanceRestoredEvent, CancelScheduledEvent, CancelSettlement, Error, FundsDepositedEvent,
+    LowBalanceEvent, OneOffChargedEvent, PrepaidWithdrawnEvent, SetupFeeChargedEvent, Subscription,
+    SubscriptionCancelledEvent, SubscriptionCreatedEvent, SubscriptionParams,
+    SubscriptionPausedEvent, SubscriptionReactivatedEvent, SubscriptionResumedEvent,
+    SubscriptionStatus, UnauthorizedAttemptEvent, UsageChargedEvent, UsageRounding,
+    MAX_EXTERNAL_REF_LEN, MAX_METADATA_LEN, USAGE_RATE_SCALE,
+};
+use crate::yield_adapter::{deposit_to_adapter, withdraw_from_adapter};
+use soroban_sdk::{symbol_short, token, Address, Bytes, BytesN, Env, Map, String, Symbol, Vec};
+
+const KEY_AUTO_PAUSED: Symbol = symbol_short!("apaused");
+const KEY_CANCEL_SETTLEMENT: Symbol = symbol_short!("settle");
+const KEY_LOW_BALANCE_WARNED: Symbol = symbol_short!("lowbal");
+const KEY_MANAGER: Symbol = symbol_short!("manager");
+const KEY_DEPOSIT_IDEM: Symbol = symbol_short!("depidem");
+const KEY_NO_AUTO_RENEW: Symbol = symbol_short!("norenew");
+const KEY_TOTAL_PREPAID: Symbol = symbol_short!("tprepaid");
+const KEY_CANCEL_PENDING: Symbol = symbol_short!("cpending");
+const KEY_PAUSED_BY: Symbol = symbol_short!("pausedby");
+const KEY_AUTOPAY: Symbol = symbol_short!("autopay");
+const KEY_EVENT_SEQ: Symbol = symbol_short!("evseq");
+const KEY_RELEASE_MONTHS: Symbol = symbol_short!("release");
+const KEY_AUTH_EXPIRY: Symbol = symbol_short!("authexp");
+const KEY_USAGE_PRICING: Symbol = symbol_short!("usgprice");
+
+fn auto_paused_key(subscription_id: u32) -> (Symbol, u32) {
+    (KEY_AUTO_PAUSED, subscription_id)
+}
+
+/// True if the subscription was paused by the contract because its balance ran low (as opposed
+/// to a manual pause). Only auto-paused subscriptions are resumed by a deposit.
+pub fn is_auto_paused(env: &Env, subscription_id: u32) -> bool {
+    env.storage()
+        .instance()
+        .get(&auto_paused_key(subscription_id))
+        .unwrap_or(false)
+}
+
+fn paused_by_key(subscription_id: u32) -> (Symbol, u32) {
+    (KEY_PAUSED_BY, subscription_id)
+}
+
+/// Address behind the current manual pause, if the subscription is manually paused.
+pub fn get_paused_by(env: &Env, subscription_id: u32) -> Option<Address> {
+    env.storage()
+        .instance()
+        .get(&paused_by_key(subscription_id))
+}
+
+/// Forgets who paused the subscription; called whenever it is paused by the contract or resumed.
+pub fn clear_paused_by(env: &Env, subscription_id: u32) {
+    env.storage()
+        .instance()
+        .remove(&paused_by_key(subscription_id));
+}
+
+pub fn set_auto_paused(env: &Env, subscription_id: u32, auto_paused: bool) {
+    if auto_paused {
+        env.storage()
+            .instance()
+            .set(&auto_paused_key(subscription_id), &true);
+    } else {
+        env.storage()
+            .instance()
+            .remove(&auto_paused_key(subscription_id));
+    }
+}
+
+/// Sum of `prepaid_balance` over all subscriptions billed in `token`.
+pub fn get_total_prepaid(env: &Env, token: &Address) -> i128 {
+    env.storage()
+        .instance()
+        .get(&(KEY_TOTAL_PREPAID, token.clone()))
+        .unwrap_or(0)
+}
+
+/// Every token that has held prepaid funds, in first-seen order. All contract obligations are in
+/// one of these tokens.
+pub fn get_tracked_tokens(env: &Env) -> Vec<Address> {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "tokens"))
+        .unwrap_or(Vec::new(env))
+}
+
+/// Applies `delta` to the running prepaid total for `token`. Every change to a subscription's
+/// `prepaid_balance` must be mirrored here. A decrease also pulls the same amount back from the
+/// yield adapter, if one holds prepaid funds (see [`crate::yield_adapter`]).
+pub fn adjust_total_prepaid(env: &Env, token: &Address, delta: i128) -> Result<(), Error> {
+    if delta < 0 {
+        withdraw_from_adapter(env, token, -delta)?;
+    }
+    let key = (KEY_TOTAL_PREPAID, token.clone());
+    if !env.storage().instance().has(&key) {
+        let mut tokens = get_tracked_tokens(env);
+        tokens.push_back(token.clone());
+        env.storage()
+            .instance()
+            .set(&Symbol::new(env, "tokens"), &tokens);
+    }
+    let total = get_total_prepaid(env, token)
+        .checked_add(delta)
+        .ok_or(Error::Overflow)?;
+    env.storage()
+        .instance()
+        .set(&(KEY_TOTAL_PREPAID, token.clone()), &total);
+    Ok(())
+}
+
+/// Months over which each interval charge vests to the merchant (0 = released immediately).
+pub fn get_release_months(env: &Env, subscription_id: u32) -> u32 {
+    env.storage()
+        .instance()
+        .get(&(KEY_RELEASE_MONTHS, subscription_id))
+        .unwrap_or(0)
+}
+
+/// Sequence number the subscription's next event will carry (= events emitted so far).
+pub fn get_event_seq(env: &Env, subscription_id: u32) -> u64 {
+    env.storage()
+        .instance()
+        .get(&(KEY_EVENT_SEQ, subscription_id))
+        .unwrap_or(0)
+}
+
+/// Returns the sequence number for an event about to be emitted for the subscription and
+/// advances the counter, so consumers can detect gaps.
+pub fn next_event_seq(env: &Env, subscription_id: u32) -> u64 {
+    let seq = get_event_seq(env, subscription_id);
+    env.storage()
+        .instance()
+        .set(&(KEY_EVENT_SEQ, subscription_id), &(seq + 1));
+    seq
+}
+
+/// Number of subscription ids handed out so far (ids are `0..subscription_count`).
+pub fn subscription_count(env: &Env) -> u32 {
+    env.storage()
+        .instance()
+        .get(&Symbol::new(env, "next_id"))
+        .unwrap_or(0)
+}
 
 pub fn next_id(env: &Env) -> u32 {
     let key = Symbol::new(env, "next_id");
@@ -22,42 +180,411 @@ pub fn do_create_subscription(
     amount: i128,
     interval_seconds: u64,
     usage_enabled: bool,
+    token: Option<Address>,
+    min_deposit: i128,
+    max_total_charge: i128,
 ) -> Result<u32, Error> {
-    subscriber.require_auth();
+    do_create_subscription_from_params(
+        env,
+        SubscriptionParams {
+            subscriber,
+            merchant,
+            amount,
+            interval_seconds,
+            usage_enabled,
+            token,
+            min_deposit,
+            max_total_charge,
+            grace_seconds: None,
+            start_timestamp: None,
+            external_ref: None,
+            metadata: None,
+            release_months: 0,
+            max_cycles: 0,
+            min_charge: 0,
+            initial_deposit: 0,
+            setup_fee: 0,
+            usage_rate: 0,
+            usage_rounding: UsageRounding::Floor,
+        },
+    )
+}
+
+pub fn do_create_subscription_from_params(
+    env: &Env,
+    params: SubscriptionParams,
+) -> Result<u32, Error> {
+    params.subscriber.require_auth();
+    store_new_subscription(env, params)
+}
+
+/// Validates `params` and stores a new `Active` subscription. The caller checks auth.
+fn store_new_subscription(env: &Env, params: SubscriptionParams) -> Result<u32, Error> {
+    params.validate()?;
+    if get_require_registration(env) && !is_merchant_registered(env, &params.merchant) {
+        return Err(Error::MerchantNotRegistered);
+    }
+    if is_subscriber_blocked(env, &params.merchant, &params.subscriber) {
+        return Err(Error::SubscriberBlocked);
+    }
+    check_interval_allowed(env, &params.merchant, params.interval_seconds)?;
+    let max_subs = get_max_subs_per_subscriber(env);
+    if max_subs > 0 && count_open(env, &params.subscriber, false) >= max_subs {
+        return Err(Error::TooManySubscriptions);
+    }
+    let token = match params.token {
+        Some(t) => t,
+        None => get_token(env)?,
+    };
+    let grace_seconds = match params.grace_seconds {
+        Some(g) => g,
+        None => get_default_grace_seconds(env),
+    };
+    let now = env.ledger().timestamp();
+    let start_timestamp = params.start_timestamp.unwrap_or(now).max(now);
     let sub = Subscription {
-        subscriber: subscriber.clone(),
-        merchant,
-        amount,
-        interval_seconds,
-        last_payment_timestamp: env.ledger().timestamp(),
+        subscriber: params.subscriber,
+        merchant: params.merchant,
+        token,
+        amount: params.amount,
+        interval_seconds: params.interval_seconds,
+        // The first interval is billed once it has elapsed from the start.
+        last_payment_timestamp: start_timestamp,
         status: SubscriptionStatus::Active,
         prepaid_balance: 0i128,
-        usage_enabled,
+        usage_enabled: params.usage_enabled,
+        min_deposit: params.min_deposit,
+        max_prepaid: 0,
+        max_total_charge: params.max_total_charge,
+        total_charged: 0,
+        low_balance_threshold: 0,
+        receipt_threshold: 0,
+        discount_bps: 0,
+        discount_expiry: 0,
+        grace_seconds,
+        failed_charge_count: 0,
+        max_cycles: params.max_cycles,
+        cycles_charged: 0,
+        min_charge: params.min_charge,
+        accrued_usage: 0,
+        pause_count: 0,
+        resume_count: 0,
+        credits: 0,
+        start_timestamp,
+        created_at: now,
+        external_ref: params.external_ref.unwrap_or(Bytes::new(env)),
+        metadata: params.metadata.unwrap_or(String::from_str(env, "")),
     };
+    // Native creation closes the migration import window (see `admin::do_import_subscription`).
+    env.storage()
+        .instance()
+        .set(&Symbol::new(env, "created"), &true);
     let id = next_id(env);
     env.storage().instance().set(&id, &sub);
+    if params.release_months > 0 {
+        env.storage()
+            .instance()
+            .set(&(KEY_RELEASE_MONTHS, id), &params.release_months);
+    }
+    if params.usage_rate > 0 {
+        env.storage().instance().set(
+            &(KEY_USAGE_PRICING, id),
+            &(params.usage_rate, params.usage_rounding.clone()),
+        );
+    }
+    add_to_indexes(env, id, &sub);
+    record_status(env, id, SubscriptionStatus::Active);
+    let subscriber = sub.subscriber.clone();
+    env.events().publish(
+        (symbol_short!("sub_new"), id),
+        SubscriptionCreatedEvent {
+            subscription_id: id,
+            subscriber: sub.subscriber,
+            merchant: sub.merchant,
+            amount: sub.amount,
+            interval_seconds: sub.interval_seconds,
+            seq: next_event_seq(env, id),
+        },
+    );
+    if params.initial_deposit > 0 {
+        deposit_from(env, id, subscriber, params.initial_deposit, None, true)?;
+    }
+    if params.setup_fee > 0 {
+        charge_setup_fee(env, id, params.setup_fee)?;
+    }
     Ok(id)
 }
 
+/// Takes the merchant's one-time setup fee from the freshly deposited balance.
+fn charge_setup_fee(env: &Env, subscription_id: u32, setup_fee: i128) -> Result<(), Error> {
+    let mut sub = get_subscription(env, subscription_id)?;
+    if sub.prepaid_balance < setup_fee {
+        return Err(Error::InsufficientBalance);
+    }
+    debit_merchant_charge(env, subscription_id, &mut sub, setup_fee)?;
+    env.events().publish(
+        (symbol_short!("setup_fee"), subscription_id),
+        SetupFeeChargedEvent {
+            subscription_id,
+            merchant: sub.merchant.clone(),
+            amount: setup_fee,
+            remaining_balance: sub.prepaid_balance,
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+    Ok(())
+}
+
+fn deposit_idem_key(subscription_id: u32) -> (Symbol, u32) {
+    (KEY_DEPOSIT_IDEM, subscription_id)
+}
+
+/// Tops up the prepaid balance. With `idempotency_key`, a retry carrying the key of this
+/// subscription's latest deposit returns `Ok(())` without crediting again. Only that one key is
+/// stored per subscription, as for charges.
 pub fn do_deposit_funds(
     env: &Env,
     subscription_id: u32,
     subscriber: Address,
     amount: i128,
+    idempotency_key: Option<BytesN<32>>,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    deposit_from(
+        env,
+        subscription_id,
+        subscriber,
+        amount,
+        idempotency_key,
+        true,
+    )
+}
+
+/// Deposits `amount` from the subscriber and immediately takes the due interval charge, in one
+/// call. Any charge failure (not due yet, still underfunded, not active) is returned as the
+/// error, which reverts the deposit too. Requires auth from the subscriber.
+pub fn do_deposit_and_charge(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    amount: i128,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    if get_subscription(env, subscription_id)?.subscriber != subscriber {
+        return Err(Error::Unauthorized);
+    }
+    deposit_from(env, subscription_id, subscriber, amount, None, true)?;
+    charge_one(env, subscription_id, None)
+}
+
+/// Third-party top-up: pulls `amount` from `payer` (who must authorize) into the subscription's
+/// prepaid balance. Same rules as [`do_deposit_funds`].
+pub fn do_deposit_for(
+    env: &Env,
+    subscription_id: u32,
+    payer: Address,
+    amount: i128,
+) -> Result<(), Error> {
+    payer.require_auth();
+    deposit_from(env, subscription_id, payer, amount, None, true)
+}
+
+/// Tops up several of the subscriber's subscriptions in one call: `amounts[i]` goes to
+/// `subscription_ids[i]`. The total is pulled from the subscriber once per token, then each
+/// subscription is credited under the usual deposit rules. Fails with `InvalidAmount` when the
+/// lengths differ and `Unauthorized` when a subscription belongs to someone else; any failure
+/// reverts the whole batch. Requires auth from the subscriber.
+pub fn do_deposit_batch(
+    env: &Env,
+    subscription_ids: Vec<u32>,
+    subscriber: Address,
+    amounts: Vec<i128>,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    if subscription_ids.len() != amounts.len() {
+        return Err(Error::InvalidAmount);
+    }
+    let mut totals: Map<Address, i128> = Map::new(env);
+    for (id, amount) in subscription_ids.iter().zip(amounts.iter()) {
+        let sub = get_subscription(env, id)?;
+        if sub.subscriber != subscriber {
+            return Err(Error::Unauthorized);
+        }
+        if amount <= 0 {
+            return Err(Error::InvalidAmount);
+        }
+        let total = totals
+            .get(sub.token.clone())
+            .unwrap_or(0)
+            .checked_add(amount)
+            .ok_or(Error::Overflow)?;
+        totals.set(sub.token, total);
+    }
+    for (token, total) in totals.iter() {
+        token::Client::new(env, &token).transfer(
+            &subscriber,
+            &env.current_contract_address(),
+            &total,
+        );
+    }
+    for (id, amount) in subscription_ids.iter().zip(amounts.iter()) {
+        deposit_from(env, id, subscriber.clone(), amount, None, false)?;
+    }
+    Ok(())
+}
+
+/// Returns `amount` of unused prepaid balance to the subscriber without changing the status.
+/// Fails with `InsufficientBalance` when `amount` exceeds the balance.
+pub fn do_withdraw_prepaid(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    amount: i128,
 ) -> Result<(), Error> {
     subscriber.require_auth();
+    let mut sub = get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Unauthorized);
+    }
+    if amount <= 0 {
+        return Err(Error::InvalidAmount);
+    }
+    if amount > sub.prepaid_balance {
+        return Err(Error::InsufficientBalance);
+    }
+    sub.prepaid_balance -= amount;
+    adjust_total_prepaid(env, &sub.token, -amount)?;
+    env.storage().instance().set(&subscription_id, &sub);
+
+    token::Client::new(env, &sub.token).transfer(
+        &env.current_contract_address(),
+        &subscriber,
+        &amount,
+    );
+    env.events().publish(
+        (symbol_short!("prep_wd"), subscription_id),
+        PrepaidWithdrawnEvent {
+            subscription_id,
+            subscriber,
+            amount,
+            new_balance: sub.prepaid_balance,
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+    Ok(())
+}
+
+/// Shared deposit path; the caller has checked `payer`'s auth. Without `pull` the caller has
+/// already moved `amount` into the contract.
+fn deposit_from(
+    env: &Env,
+    subscription_id: u32,
+    payer: Address,
+    amount: i128,
+    idempotency_key: Option<BytesN<32>>,
+    pull: bool,
+) -> Result<(), Error> {
+    extend_storage_ttl(env);
+    if let Some(ref k) = idempotency_key {
+        let stored: Option<BytesN<32>> = env
+            .storage()
+            .instance()
+            .get(&deposit_idem_key(subscription_id));
+        if stored.as_ref() == Some(k) {
+            return Ok(());
+        }
+    }
 
+    if amount <= 0 {
+        return Err(Error::InvalidAmount);
+    }
     let min_topup: i128 = crate::admin::get_min_topup(env)?;
     if amount < min_topup {
         return Err(Error::BelowMinimumTopup);
     }
 
     let mut sub = get_subscription(env, subscription_id)?;
+    if get_cancel_pending(env, subscription_id).is_some() {
+        return Err(Error::PendingCancellation);
+    }
+    if sub.min_deposit > 0 && amount < sub.min_deposit {
+        return Err(Error::DepositTooSmall);
+    }
+    check_max_prepaid(env, subscription_id, &sub, amount)?;
     sub.prepaid_balance = sub
         .prepaid_balance
         .checked_add(amount)
         .ok_or(Error::Overflow)?;
+    adjust_total_prepaid(env, &sub.token, amount)?;
+
+    if pull {
+        token::Client::new(env, &sub.token).transfer(
+            &payer,
+            &env.current_contract_address(),
+            &amount,
+        );
+    }
+    deposit_to_adapter(env, &sub.token, amount)?;
+
+    record_deposit(env, subscription_id, amount);
+
+    if let Some(k) = idempotency_key {
+        env.storage()
+            .instance()
+            .set(&deposit_idem_key(subscription_id), &k);
+    }
+
+    let warned_key = low_balance_warned_key(subscription_id);
+    let restored = sub.prepaid_balance >= sub.low_balance_threshold
+        && env.storage().instance().has(&warned_key);
+    if restored {
+        env.storage().instance().remove(&warned_key);
+    }
+
+    let resume = sub.status == SubscriptionStatus::Paused
+        && is_auto_paused(env, subscription_id)
+        && sub.prepaid_balance >= sub.amount;
+    if resume {
+        validate_status_transition(&sub.status, &SubscriptionStatus::Active)?;
+        sub.status = SubscriptionStatus::Active;
+        set_auto_paused(env, subscription_id, false);
+        record_status(env, subscription_id, SubscriptionStatus::Active);
+    }
     env.storage().instance().set(&subscription_id, &sub);
+
+    env.events().publish(
+        (symbol_short!("deposit"), subscription_id),
+        FundsDepositedEvent {
+            subscription_id,
+            subscriber: sub.subscriber.clone(),
+            payer,
+            amount,
+            new_balance: sub.prepaid_balance,
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+    if restored {
+        env.events().publish(
+            (symbol_short!("bal_rest"), subscription_id),
+            BalanceRestoredEvent {
+                subscription_id,
+                subscriber: sub.subscriber.clone(),
+                prepaid_balance: sub.prepaid_balance,
+                threshold: sub.low_balance_threshold,
+                seq: next_event_seq(env, subscription_id),
+            },
+        );
+    }
+    if resume {
+        env.events().publish(
+            (symbol_short!("resumed"), subscription_id),
+            SubscriptionResumedEvent {
+                subscription_id,
+                authorizer: env.current_contract_address(),
+                seq: next_event_seq(env, subscription_id),
+            },
+        );
+    }
     Ok(())
 }
 
@@ -76,7 +603,14 @@ pub fn do_charge_subscription(
 ) -> Result<(), Error> {
     let admin = require_admin(env)?;
     admin.require_auth();
-    charge_one(env, subscription_id, idempotency_key)
+    match charge_interval(env, subscription_id, idempotency_key)? {
+        ChargeOutcome::Done => Ok(()),
+        // Under dunning an unfunded attempt is a recorded outcome (count, event, possibly the
+        // final cancellation); returning Ok keeps it from being rolled back. Other rejections,
+        // even ones reported as `InsufficientBalance`, still fail the call.
+        ChargeOutcome::Unfunded if get_max_retries(env) > 0 => Ok(()),
+        ChargeOutcome::Unfunded => Err(Error::InsufficientBalance),
+    }
 }
 
 /// Merchant-initiated one-off charge: debits `amount` from the subscription's prepaid balance.
@@ -105,20 +639,984 @@ pub fn do_charge_one_off(
         return Err(Error::InsufficientBalance);
     }
 
+    debit_merchant_charge(env, subscription_id, &mut sub, amount)?;
+    env.events().publish(
+        (symbol_short!("oneoff_ch"), subscription_id),
+        OneOffChargedEvent {
+            subscription_id,
+            merchant,
+            amount,
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+    warn_if_low_balance(env, subscription_id, &sub);
+
+    Ok(())
+}
+
+/// Moves `amount` (already checked against the balance) from the subscription to the merchant
+/// and fee balances, stores the subscription and emits `MerchantCreditedEvent`.
+fn debit_merchant_charge(
+    env: &Env,
+    subscription_id: u32,
+    sub: &mut Subscription,
+    amount: i128,
+) -> Result<(), Error> {
     sub.prepaid_balance = sub
         .prepaid_balance
         .checked_sub(amount)
         .ok_or(Error::Overflow)?;
-    env.storage().instance().set(&subscription_id, &sub);
+    adjust_total_prepaid(env, &sub.token, -amount)?;
+    record_total_charged(sub, amount)?;
+    env.storage().instance().set(&subscription_id, sub);
+    let (merchant_share, fee) = split_fee(env, amount)?;
+    credit_merchant(env, &sub.merchant, &sub.token, merchant_share)?;
+    accrue_fee(env, &sub.token, fee)?;
+    record_merchant_revenue(env, &sub.merchant, amount);
+    publish_merchant_credited(
+        env,
+        subscription_id,
+        &sub.merchant,
+        &sub.token,
+        merchant_share,
+    );
+    Ok(())
+}
+
+/// Merchant-reported usage charge for a `usage_enabled` subscription. `amount` is added to
+/// `accrued_usage`; once the accrued total reaches `min_charge` it is deducted from the prepaid
+/// balance in one charge and the accrual resets. Returns the amount deducted (0 while still
+/// accruing). Requires merchant auth and an Active subscription; fails with
+/// `InsufficientBalance`, leaving the earlier accrual in place, if the balance cannot cover the
+/// accrued total.
+pub fn do_charge_usage(
+    env: &Env,
+    subscription_id: u32,
+    merchant: Address,
+    amount: i128,
+) -> Result<i128, Error> {
+    merchant.require_auth();
 
+    let mut sub = get_subscription(env, subscription_id)?;
+    if sub.merchant != merchant {
+        return Err(Error::Unauthorized);
+    }
+    if !sub.usage_enabled {
+        return Err(Error::UsageNotEnabled);
+    }
+    if sub.status != SubscriptionStatus::Active {
+        return Err(Error::NotActive);
+    }
+    if amount <= 0 {
+        return Err(Error::InvalidAmount);
+    }
+    let accrued = sub
+        .accrued_usage
+        .checked_add(amount)
+        .ok_or(Error::Overflow)?;
+    if accrued < sub.min_charge {
+        sub.accrued_usage = accrued;
+        env.storage().instance().set(&subscription_id, &sub);
+        return Ok(0);
+    }
+    if sub.prepaid_balance < accrued {
+        return Err(Error::InsufficientBalance);
+    }
+
+    sub.accrued_usage = 0;
+    debit_merchant_charge(env, subscription_id, &mut sub, accrued)?;
     env.events().publish(
-        (symbol_short!("oneoff_ch"),),
-        OneOffChargedEvent {
+        (symbol_short!("usage_ch"), subscription_id),
+        UsageChargedEvent {
             subscription_id,
             merchant,
-            amount,
+            amount: accrued,
+            remaining_balance: sub.prepaid_balance,
+            charge_nonce: next_charge_nonce(env),
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+    warn_if_low_balance(env, subscription_id, &sub);
+
+    Ok(accrued)
+}
+
+/// `(usage_rate, rounding)` agreed at creation; a rate of 0 means no per-unit pricing.
+pub fn get_usage_pricing(env: &Env, subscription_id: u32) -> (i128, UsageRounding) {
+    env.storage()
+        .instance()
+        .get(&(KEY_USAGE_PRICING, subscription_id))
+        .unwrap_or((0, UsageRounding::Floor))
+}
+
+/// Charge for `units` at the subscription's usage rate, rounded with its rounding mode. Fails
+/// with `InvalidAmount` for non-positive `units` or when no usage rate was set.
+pub fn usage_charge_for_units(env: &Env, subscription_id: u32, units: i128) -> Result<i128, Error> {
+    let (rate, rounding) = get_usage_pricing(env, subscription_id);
+    if units <= 0 || rate == 0 {
+        return Err(Error::InvalidAmount);
+    }
+    let scaled = units.checked_mul(rate).ok_or(Error::Overflow)?;
+    let (whole, fraction) = (scaled / USAGE_RATE_SCALE, scaled % USAGE_RATE_SCALE);
+    let round_up = match rounding {
+        UsageRounding::Floor => false,
+        UsageRounding::Ceil => fraction > 0,
+        UsageRounding::Nearest => fraction * 2 >= USAGE_RATE_SCALE,
+    };
+    Ok(whole + round_up as i128)
+}
+
+/// [`do_charge_usage`] for `units` priced with [`usage_charge_for_units`]. A charge that rounds
+/// to zero fails with `InvalidAmount` like an explicit zero amount.
+pub fn do_charge_usage_units(
+    env: &Env,
+    subscription_id: u32,
+    merchant: Address,
+    units: i128,
+) -> Result<i128, Error> {
+    let amount = usage_charge_for_units(env, subscription_id, units)?;
+    do_charge_usage(env, subscription_id, merchant, amount)
+}
+
+/// Cancels the subscription and refunds the remaining prepaid balance to the subscriber.
+///
+/// If the merchant configured a [`CancelSettlement`], a final charge of up to one `amount` is
+/// taken first (see [`charge_final_settlement`]), so the refund is reduced accordingly.
+/// Cancelling an already-cancelled subscription is an idempotent no-op refund of zero.
+pub fn do_cancel_subscription(
+    env: &Env,
+    subscription_id: u32,
+    authorizer: Address,
+) -> Result<(), Error> {
+    authorizer.require_auth();
+    let sub = get_subscription(env, subscription_id)?;
+    require_lifecycle_authorizer(
+        env,
+        subscription_id,
+        &sub,
+        &authorizer,
+        symbol_short!("cancel"),
+    )?;
+    let refund_to = sub.subscriber.clone();
+    cancel_and_settle(env, subscription_id, sub, authorizer, Some(refund_to), true)?;
+    Ok(())
+}
+
+/// [`do_cancel_subscription`] with the refund sent to `refund_to` instead of the subscriber.
+/// Only the subscriber may redirect their refund.
+pub fn do_cancel_subscription_to(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    refund_to: Address,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    let sub = get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Unauthorized);
+    }
+    cancel_and_settle(env, subscription_id, sub, subscriber, Some(refund_to), true)?;
+    Ok(())
+}
+
+/// Merchant cancels and keeps the remaining prepaid balance: it is credited to the merchant's
+/// withdrawable balance instead of refunded. No final settlement charge is taken since the whole
+/// balance goes to the merchant. Merchant only.
+pub fn do_cancel_no_refund(
+    env: &Env,
+    subscription_id: u32,
+    merchant: Address,
+) -> Result<(), Error> {
+    merchant.require_auth();
+    let mut sub = get_subscription(env, subscription_id)?;
+    if sub.merchant != merchant {
+        return Err(Error::Unauthorized);
+    }
+    validate_status_transition(&sub.status, &SubscriptionStatus::Cancelled)?;
+    release_credits(env, subscription_id, &mut sub)?;
+
+    let swept = sub.prepaid_balance;
+    sub.prepaid_balance = 0;
+    adjust_total_prepaid(env, &sub.token, -swept)?;
+    credit_merchant(env, &merchant, &sub.token, swept)?;
+    clear_cancel_pending(env, subscription_id);
+    if sub.status != SubscriptionStatus::Cancelled {
+        sub.status = SubscriptionStatus::Cancelled;
+        record_status(env, subscription_id, SubscriptionStatus::Cancelled);
+    }
+    env.storage().instance().set(&subscription_id, &sub);
+
+    if swept > 0 {
+        publish_merchant_credited(env, subscription_id, &merchant, &sub.token, swept);
+    }
+    env.events().publish(
+        (symbol_short!("cancelled"), subscription_id),
+        SubscriptionCancelledEvent {
+            subscription_id,
+            authorizer: merchant,
+            refund_amount: 0,
+            refund_to: sub.subscriber,
+            swept_to_merchant: swept,
+            seq: next_event_seq(env, subscription_id),
         },
     );
+    Ok(())
+}
+
+/// Multisig action hash for force-cancelling `subscription_id`.
+pub fn force_cancel_action_hash(env: &Env, subscription_id: u32) -> BytesN<32> {
+    action_hash(env, symbol_short!("fcancel"), subscription_id)
+}
 
+/// Admin cancels a subscription regardless of who owns it; the balance is refunded to the
+/// subscriber. Needs multisig approval when configured (see [`crate::multisig`]).
+pub fn do_force_cancel(env: &Env, admin: Address, subscription_id: u32) -> Result<(), Error> {
+    require_approved(env, &admin, &force_cancel_action_hash(env, subscription_id))?;
+    let sub = get_subscription(env, subscription_id)?;
+    let refund_to = sub.subscriber.clone();
+    cancel_and_settle(env, subscription_id, sub, admin, Some(refund_to), false)?;
     Ok(())
 }
+
+/// Shared cancel path: final settlement, zero the balance, save as `Cancelled` and emit
+/// `SubscriptionCancelledEvent`. With `charge_cancel_fee` the cancel fee is deducted from the
+/// refund and accrued as a platform fee. Returns the net refund, which is transferred to
+/// `refund_to`, or kept in the contract for the caller when `refund_to` is `None`.
+fn cancel_and_settle(
+    env: &Env,
+    subscription_id: u32,
+    mut sub: Subscription,
+    authorizer: Address,
+    refund_to: Option<Address>,
+    charge_cancel_fee: bool,
+) -> Result<(Subscription, i128), Error> {
+    validate_status_transition(&sub.status, &SubscriptionStatus::Cancelled)?;
+    release_credits(env, subscription_id, &mut sub)?;
+
+    if sub.status != SubscriptionStatus::Cancelled {
+        let settlement = get_cancel_settlement(env, subscription_id);
+        charge_final_settlement(env, subscription_id, &mut sub, &settlement)?;
+    }
+
+    let balance = sub.prepaid_balance;
+    sub.prepaid_balance = 0;
+    adjust_total_prepaid(env, &sub.token, -balance)?;
+    let fee = if charge_cancel_fee {
+        cancel_fee(env, balance)?
+    } else {
+        0
+    };
+    accrue_fee(env, &sub.token, fee)?;
+    let refund = balance - fee;
+    clear_cancel_pending(env, subscription_id);
+    if sub.status != SubscriptionStatus::Cancelled {
+        sub.status = SubscriptionStatus::Cancelled;
+        record_status(env, subscription_id, SubscriptionStatus::Cancelled);
+    }
+    env.storage().instance().set(&subscription_id, &sub);
+
+    if let Some(ref to) = refund_to {
+        if refund > 0 {
+            token::Client::new(env, &sub.token).transfer(
+                &env.current_contract_address(),
+                to,
+                &refund,
+            );
+        }
+    }
+
+    env.events().publish(
+        (symbol_short!("cancelled"), subscription_id),
+        SubscriptionCancelledEvent {
+            subscription_id,
+            authorizer,
+            refund_amount: refund,
+            refund_to: refund_to.unwrap_or_else(|| env.current_contract_address()),
+            swept_to_merchant: 0,
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+
+    Ok((sub, refund))
+}
+
+/// Cancels `old_id` and opens a subscription to `new_merchant` in the same token, funded with the
+/// old subscription's refund (the tokens never leave the contract). Subscriber only. The refund
+/// is carried as-is: `min_topup` does not apply.
+pub fn do_cancel_and_resubscribe(
+    env: &Env,
+    old_id: u32,
+    subscriber: Address,
+    new_merchant: Address,
+    amount: i128,
+    interval_seconds: u64,
+) -> Result<u32, Error> {
+    subscriber.require_auth();
+    let old = get_subscription(env, old_id)?;
+    if old.subscriber != subscriber {
+        return Err(Error::Unauthorized);
+    }
+    let (old, refund) = cancel_and_settle(env, old_id, old, subscriber.clone(), None, false)?;
+
+    let params =
+        SubscriptionParams::new(subscriber.clone(), new_merchant, amount, interval_seconds)?
+            .with_token(old.token.clone());
+    let new_id = store_new_subscription(env, params)?;
+    let mut new_sub = get_subscription(env, new_id)?;
+    new_sub.prepaid_balance = refund;
+    adjust_total_prepaid(env, &new_sub.token, refund)?;
+    env.storage().instance().set(&new_id, &new_sub);
+
+    // Account for the carried refund like a deposit so indexers and the yield adapter see it.
+    if refund > 0 {
+        deposit_to_adapter(env, &new_sub.token, refund)?;
+        record_deposit(env, new_id, refund);
+        env.events().publish(
+            (symbol_short!("deposit"), new_id),
+            FundsDepositedEvent {
+                subscription_id: new_id,
+                subscriber: subscriber.clone(),
+                payer: subscriber,
+                amount: refund,
+                new_balance: refund,
+                seq: next_event_seq(env, new_id),
+            },
+        );
+    }
+    Ok(new_id)
+}
+
+fn cancel_settlement_key(subscription_id: u32) -> (Symbol, u32) {
+    (KEY_CANCEL_SETTLEMENT, subscription_id)
+}
+
+/// Final-settlement policy applied when the subscription is cancelled (default `Off`).
+pub fn get_cancel_settlement(env: &Env, subscription_id: u32) -> CancelSettlement {
+    env.storage()
+        .instance()
+        .get(&cancel_settlement_key(subscription_id))
+        .unwrap_or(CancelSettlement::Off)
+}
+
+/// Sets the final-settlement policy. Requires auth from the subscription's merchant.
+pub fn do_set_cancel_settlement(
+    env: &Env,
+    subscription_id: u32,
+    merchant: Address,
+    settlement: CancelSettlement,
+) -> Result<(), Error> {
+    merchant.require_auth();
+    let sub = get_subscription(env, subscription_id)?;
+    if sub.merchant != merchant {
+        return Err(Error::Unauthorized);
+    }
+    env.storage()
+        .instance()
+        .set(&cancel_settlement_key(subscription_id), &settlement);
+    Ok(())
+}
+
+fn low_balance_warned_key(subscription_id: u32) -> (Symbol, u32) {
+    (KEY_LOW_BALANCE_WARNED, subscription_id)
+}
+
+/// Emits `LowBalanceEvent` after a debit if the balance is now below the subscription's
+/// threshold, once per crossing: it is not repeated until a deposit re-arms it.
+pub fn warn_if_low_balance(env: &Env, subscription_id: u32, sub: &Subscription) {
+    if sub.low_balance_threshold <= 0 || sub.prepaid_balance >= sub.low_balance_threshold {
+        return;
+    }
+    let key = low_balance_warned_key(subscription_id);
+    if env.storage().instance().has(&key) {
+        return;
+    }
+    env.storage().instance().set(&key, &true);
+    env.events().publish(
+        (symbol_short!("low_bal"), subscription_id),
+        LowBalanceEvent {
+            subscription_id,
+            subscriber: sub.subscriber.clone(),
+            prepaid_balance: sub.prepaid_balance,
+            threshold: sub.low_balance_threshold,
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+}
+
+/// Sets the low-balance warning threshold. Requires auth from the subscription's subscriber.
+pub fn do_set_low_balance_threshold(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    threshold: i128,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    let mut sub = get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Unauthorized);
+    }
+    if threshold < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    sub.low_balance_threshold = threshold;
+    env.storage().instance().set(&subscription_id, &sub);
+    Ok(())
+}
+
+/// Caps the prepaid balance plus unused credits at `max_prepaid` (0 removes the cap). Merchant
+/// only. A balance already above a new cap is left alone; only later deposits are rejected.
+pub fn do_set_max_prepaid(
+    env: &Env,
+    subscription_id: u32,
+    merchant: Address,
+    max_prepaid: i128,
+) -> Result<(), Error> {
+    merchant.require_auth();
+    let mut sub = get_subscription(env, subscription_id)?;
+    if sub.merchant != merchant {
+        return Err(Error::Unauthorized);
+    }
+    if max_prepaid < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    sub.max_prepaid = max_prepaid;
+    env.storage().instance().set(&subscription_id, &sub);
+    Ok(())
+}
+
+/// Rejects with `ExceedsMaxPrepaid` when adding `amount` would take the prepaid balance plus
+/// unused credits above `max_prepaid`.
+pub fn check_max_prepaid(
+    env: &Env,
+    subscription_id: u32,
+    sub: &Subscription,
+    amount: i128,
+) -> Result<(), Error> {
+    if sub.max_prepaid == 0 {
+        return Ok(());
+    }
+    let held = sub
+        .prepaid_balance
+        .checked_add(get_credit_value(env, subscription_id))
+        .and_then(|held| held.checked_add(amount))
+        .ok_or(Error::Overflow)?;
+    if held > sub.max_prepaid {
+        return Err(Error::ExceedsMaxPrepaid);
+    }
+    Ok(())
+}
+
+/// Replaces the subscription's metadata label (empty clears it). Merchant only; at most
+/// [`MAX_METADATA_LEN`] bytes.
+pub fn do_set_metadata(
+    env: &Env,
+    subscription_id: u32,
+    merchant: Address,
+    metadata: String,
+) -> Result<(), Error> {
+    merchant.require_auth();
+    let mut sub = get_subscription(env, subscription_id)?;
+    if sub.merchant != merchant {
+        return Err(Error::Unauthorized);
+    }
+    if metadata.len() > MAX_METADATA_LEN {
+        return Err(Error::InvalidAmount);
+    }
+    sub.metadata = metadata;
+    env.storage().instance().set(&subscription_id, &sub);
+    Ok(())
+}
+
+/// Replaces the subscription's external reference (empty clears it). Merchant only; at most
+/// [`MAX_EXTERNAL_REF_LEN`] bytes.
+pub fn do_set_external_ref(
+    env: &Env,
+    subscription_id: u32,
+    merchant: Address,
+    external_ref: Bytes,
+) -> Result<(), Error> {
+    merchant.require_auth();
+    let mut sub = get_subscription(env, subscription_id)?;
+    if sub.merchant != merchant {
+        return Err(Error::Unauthorized);
+    }
+    if external_ref.len() > MAX_EXTERNAL_REF_LEN {
+        return Err(Error::InvalidAmount);
+    }
+    sub.external_ref = external_ref;
+    env.storage().instance().set(&subscription_id, &sub);
+    Ok(())
+}
+
+/// Sets the minimum charge amount that is flagged as a receipt. Subscriber only; 0 flags every
+/// charge.
+pub fn do_set_receipt_threshold(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    threshold: i128,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    let mut sub = get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Unauthorized);
+    }
+    if threshold < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    sub.receipt_threshold = threshold;
+    env.storage().instance().set(&subscription_id, &sub);
+    Ok(())
+}
+
+/// Applies a merchant coupon: interval charges are reduced by `discount_bps` until
+/// `expiry_timestamp`. Replaces any existing coupon; `discount_bps = 0` removes it.
+pub fn do_apply_coupon(
+    env: &Env,
+    subscription_id: u32,
+    authorizer: Address,
+    discount_bps: u32,
+    expiry_timestamp: u64,
+) -> Result<(), Error> {
+    authorizer.require_auth();
+    let mut sub = get_subscription(env, subscription_id)?;
+    if sub.merchant != authorizer {
+        return Err(Error::Unauthorized);
+    }
+    if i128::from(discount_bps) > BPS_DENOMINATOR {
+        return Err(Error::InvalidAmount);
+    }
+    sub.discount_bps = discount_bps;
+    sub.discount_expiry = expiry_timestamp;
+    env.storage().instance().set(&subscription_id, &sub);
+    Ok(())
+}
+
+fn manager_key(subscription_id: u32) -> (Symbol, u32) {
+    (KEY_MANAGER, subscription_id)
+}
+
+/// Address the subscriber has delegated lifecycle control (pause, resume, cancel) to, if any.
+pub fn get_manager(env: &Env, subscription_id: u32) -> Option<Address> {
+    env.storage().instance().get(&manager_key(subscription_id))
+}
+
+/// Sets or clears (`None`) the subscription's manager. Requires auth from the subscriber.
+pub fn do_set_manager(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    manager: Option<Address>,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    let sub = get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Unauthorized);
+    }
+    match manager {
+        Some(m) => env
+            .storage()
+            .instance()
+            .set(&manager_key(subscription_id), &m),
+        None => env
+            .storage()
+            .instance()
+            .remove(&manager_key(subscription_id)),
+    }
+    Ok(())
+}
+
+/// Whether `who` may pause, resume or cancel `sub`: its subscriber, its merchant, or the
+/// subscriber's manager.
+pub fn is_lifecycle_authorizer(
+    env: &Env,
+    subscription_id: u32,
+    sub: &Subscription,
+    who: &Address,
+) -> bool {
+    *who == sub.subscriber
+        || *who == sub.merchant
+        || get_manager(env, subscription_id).as_ref() == Some(who)
+}
+
+/// Rejects with [`Error::Unauthorized`] unless [`is_lifecycle_authorizer`] holds.
+///
+/// A rejection first publishes `UnauthorizedAttemptEvent` naming `who` and `action`. The failed
+/// call rolls the event back with everything else, so it never reaches the ledger's contract
+/// events; hosts running with diagnostics still report it (as an event of a failed call), which
+/// is the best monitoring can get under rollback semantics.
+pub fn require_lifecycle_authorizer(
+    env: &Env,
+    subscription_id: u32,
+    sub: &Subscription,
+    who: &Address,
+    action: Symbol,
+) -> Result<(), Error> {
+    if is_lifecycle_authorizer(env, subscription_id, sub, who) {
+        return Ok(());
+    }
+    env.events().publish(
+        (symbol_short!("unauth"), subscription_id),
+        UnauthorizedAttemptEvent {
+            subscription_id,
+            action,
+            caller: who.clone(),
+        },
+    );
+    Err(Error::Unauthorized)
+}
+
+fn no_auto_renew_key(subscription_id: u32) -> (Symbol, u32) {
+    (KEY_NO_AUTO_RENEW, subscription_id)
+}
+
+/// Whether the subscription renews at the end of each paid period (default `true`).
+pub fn get_auto_renew(env: &Env, subscription_id: u32) -> bool {
+    !env.storage()
+        .instance()
+        .has(&no_auto_renew_key(subscription_id))
+}
+
+/// Turns auto-renew on or off. With it off, the next due charge lapses the subscription instead
+/// (see [`crate::charge_core::charge_one`]). Requires auth from the subscriber.
+pub fn do_set_auto_renew(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    auto_renew: bool,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    let sub = get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Unauthorized);
+    }
+    if auto_renew {
+        env.storage()
+            .instance()
+            .remove(&no_auto_renew_key(subscription_id));
+    } else {
+        env.storage()
+            .instance()
+            .set(&no_auto_renew_key(subscription_id), &true);
+    }
+    Ok(())
+}
+
+fn autopay_key(subscription_id: u32) -> (Symbol, u32) {
+    (KEY_AUTOPAY, subscription_id)
+}
+
+/// Whether a due charge may pull a prepaid shortfall from the subscriber's token allowance.
+pub fn get_autopay(env: &Env, subscription_id: u32) -> bool {
+    env.storage().instance().has(&autopay_key(subscription_id))
+}
+
+/// Turns autopay on or off. The subscriber must also `approve` the contract on the token for
+/// the amounts it should be able to pull. Requires auth from the subscriber.
+pub fn do_set_autopay(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    enabled: bool,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    let sub = get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Unauthorized);
+    }
+    if enabled {
+        env.storage()
+            .instance()
+            .set(&autopay_key(subscription_id), &true);
+    } else {
+        env.storage()
+            .instance()
+            .remove(&autopay_key(subscription_id));
+    }
+    Ok(())
+}
+
+fn auth_expiry_key(subscription_id: u32) -> (Symbol, u32) {
+    (KEY_AUTH_EXPIRY, subscription_id)
+}
+
+/// Time after which interval charges need a renewed subscriber authorization; 0 means the
+/// authorization never expires.
+pub fn get_authorization_expiry(env: &Env, subscription_id: u32) -> u64 {
+    env.storage()
+        .instance()
+        .get(&auth_expiry_key(subscription_id))
+        .unwrap_or(0)
+}
+
+/// Whether the subscriber's charge authorization has expired at `now`.
+pub fn authorization_expired(env: &Env, subscription_id: u32, now: u64) -> bool {
+    let expiry = get_authorization_expiry(env, subscription_id);
+    expiry > 0 && now > expiry
+}
+
+/// Authorizes interval charges until `new_expiry`; 0 removes the expiry. A non-zero expiry must
+/// be in the future (`AuthorizationExpired`). Requires auth from the subscriber.
+pub fn do_renew_authorization(
+    env: &Env,
+    subscription_id: u32,
+    subscriber: Address,
+    new_expiry: u64,
+) -> Result<(), Error> {
+    subscriber.require_auth();
+    let sub = get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Unauthorized);
+    }
+    if new_expiry == 0 {
+        env.storage()
+            .instance()
+            .remove(&auth_expiry_key(subscription_id));
+        return Ok(());
+    }
+    if new_expiry <= env.ledger().timestamp() {
+        return Err(Error::AuthorizationExpired);
+    }
+    env.storage()
+        .instance()
+        .set(&auth_expiry_key(subscription_id), &new_expiry);
+    Ok(())
+}
+
+fn cancel_pending_key(subscription_id: u32) -> (Symbol, u32) {
+    (KEY_CANCEL_PENDING, subscription_id)
+}
+
+/// Who scheduled a cancel-at-period-end for the subscription, if one is pending.
+pub fn get_cancel_pending(env: &Env, subscription_id: u32) -> Option<Address> {
+    env.storage()
+        .instance()
+        .get(&cancel_pending_key(subscription_id))
+}
+
+pub fn clear_cancel_pending(env: &Env, subscription_id: u32) {
+    env.storage()
+        .instance()
+        .remove(&cancel_pending_key(subscription_id));
+}
+
+/// Schedules cancellation for the end of the paid period. The subscription stays `Active` (and
+/// uncharged) until `next_charge_timestamp`; the first charge attempt from then on cancels it and
+/// refunds the remaining balance (see [`crate::charge_core::charge_one`]). Requires auth from a
+/// lifecycle authorizer; `cancel_subscription` still cancels immediately.
+pub fn do_cancel_at_period_end(
+    env: &Env,
+    subscription_id: u32,
+    authorizer: Address,
+) -> Result<(), Error> {
+    authorizer.require_auth();
+    let sub = get_subscription(env, subscription_id)?;
+    require_lifecycle_authorizer(
+        env,
+        subscription_id,
+        &sub,
+        &authorizer,
+        symbol_short!("cancel_pe"),
+    )?;
+    if sub.status == SubscriptionStatus::Cancelled {
+        return Err(Error::InvalidStatusTransition);
+    }
+    let effective_at = sub
+        .last_payment_timestamp
+        .checked_add(sub.interval_seconds)
+        .ok_or(Error::Overflow)?;
+    env.storage()
+        .instance()
+        .set(&cancel_pending_key(subscription_id), &authorizer);
+
+    env.events().publish(
+        (symbol_short!("cncl_schd"), subscription_id),
+        CancelScheduledEvent {
+            subscription_id,
+            authorizer,
+            effective_at,
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+    Ok(())
+}
+
+/// Moves `sub` to `Paused` as a manual pause by `authorizer` (already authorized), saves it and
+/// emits `SubscriptionPausedEvent`. Pausing a paused subscription is a no-op transition.
+pub fn apply_manual_pause(
+    env: &Env,
+    subscription_id: u32,
+    mut sub: Subscription,
+    authorizer: Address,
+) -> Result<(), Error> {
+    validate_status_transition(&sub.status, &SubscriptionStatus::Paused)?;
+    let changed = sub.status != SubscriptionStatus::Paused;
+
+    sub.status = SubscriptionStatus::Paused;
+    if changed {
+        sub.pause_count = sub.pause_count.checked_add(1).ok_or(Error::Overflow)?;
+    }
+    env.storage().instance().set(&subscription_id, &sub);
+    // A manual pause is never lifted automatically by a deposit.
+    set_auto_paused(env, subscription_id, false);
+    env.storage()
+        .instance()
+        .set(&paused_by_key(subscription_id), &authorizer);
+    if changed {
+        record_status(env, subscription_id, SubscriptionStatus::Paused);
+    }
+
+    env.events().publish(
+        (symbol_short!("paused"), subscription_id),
+        SubscriptionPausedEvent {
+            subscription_id,
+            authorizer,
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+    Ok(())
+}
+
+/// Pauses every `Active` subscription in the merchant's index; paused, cancelled and purged ones
+/// are skipped. Requires the merchant's auth. Returns the number paused.
+pub fn do_pause_all_for_merchant(env: &Env, merchant: Address) -> Result<u32, Error> {
+    merchant.require_auth();
+    let mut paused = 0u32;
+    for id in get_index(env, &merchant, true).iter() {
+        let sub = match get_subscription(env, id) {
+            Ok(sub) if sub.status == SubscriptionStatus::Active => sub,
+            _ => continue,
+        };
+        apply_manual_pause(env, id, sub, merchant.clone())?;
+        paused += 1;
+    }
+    Ok(paused)
+}
+
+/// Whether `sub` may be resumed under its merchant's funded-resume rule: always when the rule is
+/// off, otherwise only if the prepaid balance (or autopay) covers one `amount`.
+fn resume_funded(env: &Env, subscription_id: u32, sub: &Subscription) -> bool {
+    if !get_require_funded_resume(env, &sub.merchant) || sub.prepaid_balance >= sub.amount {
+        return true;
+    }
+    get_autopay(env, subscription_id) && autopay_covers(env, sub, sub.amount - sub.prepaid_balance)
+}
+
+/// Moves `sub` back to `Active` on behalf of `authorizer` (already authorized), saves it and
+/// emits `SubscriptionResumedEvent`. An `Active` subscription has nothing to resume and, like a
+/// `Cancelled` one, fails with `InvalidStatusTransition`. Fails with `InsufficientBalance` when
+/// the merchant requires a funded resume and the next charge is not covered. With
+/// `auto_charge_on_resume` set, a charge that fell due during the pause is taken straight away
+/// when it would succeed.
+pub fn apply_manual_resume(
+    env: &Env,
+    subscription_id: u32,
+    mut sub: Subscription,
+    authorizer: Address,
+) -> Result<(), Error> {
+    // A cancelled subscription is reopened by `reactivate_subscription`, never by a resume.
+    if matches!(
+        sub.status,
+        SubscriptionStatus::Active | SubscriptionStatus::Cancelled
+    ) {
+        return Err(Error::InvalidStatusTransition);
+    }
+    validate_status_transition(&sub.status, &SubscriptionStatus::Active)?;
+    if !resume_funded(env, subscription_id, &sub) {
+        return Err(Error::InsufficientBalance);
+    }
+
+    sub.status = SubscriptionStatus::Active;
+    sub.resume_count = sub.resume_count.checked_add(1).ok_or(Error::Overflow)?;
+    env.storage().instance().set(&subscription_id, &sub);
+    set_auto_paused(env, subscription_id, false);
+    clear_paused_by(env, subscription_id);
+    record_status(env, subscription_id, SubscriptionStatus::Active);
+
+    env.events().publish(
+        (symbol_short!("resumed"), subscription_id),
+        SubscriptionResumedEvent {
+            subscription_id,
+            authorizer,
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+
+    // Only attempt the charge when it would succeed, so an unfunded one never undoes the resume.
+    if get_auto_charge_on_resume(env) && can_charge(env, subscription_id)?.0 {
+        charge_one(env, subscription_id, None)?;
+    }
+    Ok(())
+}
+
+/// Moves a `Cancelled` subscription back to `Active` with its billing clock restarted at now, so
+/// the first charge is due one interval later. Subscriber only; fails with
+/// `InvalidStatusTransition` for any other status and for installment plans, whose fixed total
+/// cannot be restarted. The creation checks that still make sense (blocked subscriber, open
+/// subscription limit) apply again.
+///
+/// The subscription comes back as a plain renewing plan billed from its prepaid balance: auto-renew
+/// is turned back on, credit mode is left, and the retry and cycle counters restart (a
+/// `max_cycles` subscription gets a fresh term). The lifetime `total_charged` is kept.
+pub fn do_reactivate(env: &Env, subscription_id: u32, subscriber: Address) -> Result<(), Error> {
+    subscriber.require_auth();
+    let mut sub = get_subscription(env, subscription_id)?;
+    if sub.subscriber != subscriber {
+        return Err(Error::Unauthorized);
+    }
+    if sub.status != SubscriptionStatus::Cancelled || is_installment_plan(env, subscription_id) {
+        return Err(Error::InvalidStatusTransition);
+    }
+    validate_status_transition(&sub.status, &SubscriptionStatus::Active)?;
+    if is_subscriber_blocked(env, &sub.merchant, &subscriber) {
+        return Err(Error::SubscriberBlocked);
+    }
+    let max_subs = get_max_subs_per_subscriber(env);
+    if max_subs > 0 && count_open(env, &subscriber, false) >= max_subs {
+        return Err(Error::TooManySubscriptions);
+    }
+
+    sub.status = SubscriptionStatus::Active;
+    sub.last_payment_timestamp = env.ledger().timestamp();
+    sub.failed_charge_count = 0;
+    sub.cycles_charged = 0;
+    env.storage().instance().set(&subscription_id, &sub);
+    // A lapse left auto-renew off; keeping it would lapse the subscription again unbilled.
+    env.storage()
+        .instance()
+        .remove(&no_auto_renew_key(subscription_id));
+    clear_cancel_pending(env, subscription_id);
+    set_auto_paused(env, subscription_id, false);
+    clear_paused_by(env, subscription_id);
+    leave_credit_mode(env, subscription_id);
+    record_status(env, subscription_id, SubscriptionStatus::Active);
+
+    env.events().publish(
+        (symbol_short!("reactiv"), subscription_id),
+        SubscriptionReactivatedEvent {
+            subscription_id,
+            subscriber,
+            seq: next_event_seq(env, subscription_id),
+        },
+    );
+    Ok(())
+}
+
+/// Resumes every subscription in the merchant's index that the merchant itself paused. Requires
+/// the merchant's auth. Returns the number resumed.
+pub fn do_resume_all_for_merchant(env: &Env, merchant: Address) -> Result<u32, Error> {
+    merchant.require_auth();
+    let mut resumed = 0u32;
+    for id in get_index(env, &merchant, true).iter() {
+        let sub = match get_subscription(env, id) {
+            Ok(sub) if sub.status == SubscriptionStatus::Paused => sub,
+            _ => continue,
+        };
+        if get_paused_by(env, id) != Some(merchant.clone()) || !resume_funded(env, id, &sub) {
+            continue;
+        }
+        apply_manual_resume(env, id, sub, merchant.clone())?;
+        resumed += 1;
+    }
+    Ok(resumed)
+}
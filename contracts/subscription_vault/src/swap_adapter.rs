@@ -0,0 +1,104 @@
+//! Optional swap adapter for merchant payouts in another token: set_swap_adapter,
+//! withdraw_merchant_funds_as.
+//!
+//! **PRs that only change how merchant payouts are swapped should edit this file only.**
+//!
+//! Merchant balances stay denominated in the token they were charged in. A swapped withdrawal
+//! debits the balance like a normal withdrawal, sends the tokens to the adapter and has it pay
+//! the output token back to this contract, which forwards it to the merchant. The output is
+//! measured from this contract's own balance rather than trusted from the adapter's return
+//! value, and the call reverts when it is below the merchant's `min_out`.
+
+use crate::admin::{get_token, require_admin};
+use crate::merchant::debit_withdrawal;
+use crate::types::{Error, MerchantWithdrawalEvent, PayoutSwappedEvent};
+use soroban_sdk::{contractclient, symbol_short, token, Address, Env, Symbol};
+
+const KEY_ADAPTER: Symbol = symbol_short!("swapadpt");
+
+/// Interface a swap adapter contract must implement.
+#[contractclient(name = "SwapAdapterClient")]
+pub trait SwapAdapter {
+    /// Called after `amount_in` of `token_in` was transferred to the adapter; pays at least
+    /// `min_out` of `token_out` to `to` and returns the amount paid.
+    fn swap(
+        env: Env,
+        token_in: Address,
+        amount_in: i128,
+        token_out: Address,
+        min_out: i128,
+        to: Address,
+    ) -> i128;
+}
+
+pub fn get_swap_adapter(env: &Env) -> Option<Address> {
+    env.storage().instance().get(&KEY_ADAPTER)
+}
+
+/// Sets or clears (`None`) the swap adapter. Admin only.
+pub fn do_set_swap_adapter(
+    env: &Env,
+    admin: Address,
+    adapter: Option<Address>,
+) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    match adapter {
+        Some(a) => env.storage().instance().set(&KEY_ADAPTER, &a),
+        None => env.storage().instance().remove(&KEY_ADAPTER),
+    }
+    Ok(())
+}
+
+/// Withdraws `amount` of the merchant's balance in the default (init) token and pays it out in
+/// `out_token` through the swap adapter. Fails with `NotInitialized` without an adapter and
+/// `SlippageExceeded` when the swap returns less than `min_out`. Returns the amount paid out.
+pub fn withdraw_merchant_funds_as(
+    env: &Env,
+    merchant: Address,
+    amount: i128,
+    out_token: Address,
+    min_out: i128,
+) -> Result<i128, Error> {
+    merchant.require_auth();
+    let adapter = get_swap_adapter(env).ok_or(Error::NotInitialized)?;
+    if min_out < 0 {
+        return Err(Error::InvalidAmount);
+    }
+    let token_in = get_token(env)?;
+    let charge_count = debit_withdrawal(env, &merchant, &token_in, amount)?;
+
+    let vault = env.current_contract_address();
+    let out = token::Client::new(env, &out_token);
+    token::Client::new(env, &token_in).transfer(&vault, &adapter, &amount);
+    let before = out.balance(&vault);
+    SwapAdapterClient::new(env, &adapter).swap(&token_in, &amount, &out_token, &min_out, &vault);
+    let amount_out = out.balance(&vault) - before;
+    if amount_out < min_out {
+        return Err(Error::SlippageExceeded);
+    }
+    out.transfer(&vault, &merchant, &amount_out);
+
+    env.events().publish(
+        (symbol_short!("withdraw"),),
+        MerchantWithdrawalEvent {
+            merchant: merchant.clone(),
+            token: token_in.clone(),
+            amount,
+            charge_count,
+        },
+    );
+    env.events().publish(
+        (symbol_short!("swapped"),),
+        PayoutSwappedEvent {
+            merchant,
+            token_in,
+            amount_in: amount,
+            token_out: out_token,
+            amount_out,
+        },
+    );
+    Ok(amount_out)
+}
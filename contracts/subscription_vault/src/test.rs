@@ -1,9 +1,22 @@
 use crate::{
-    can_transition, get_allowed_transitions, validate_status_transition, Error, Subscription,
-    SubscriptionStatus, SubscriptionVault, SubscriptionVaultClient,
+    can_transition, get_allowed_transitions, validate_status_transition, BalanceRestoredEvent,
+    CancelScheduledEvent, CancelSettlement, ChargeFailedEvent, ChargeRecord, CreditsPurchasedEvent,
+    DepositRecord, Dispute, DustSweptEvent, Error, FundsDepositedEvent, InitializedEvent,
+    LowBalanceEvent, MerchantCreditedEvent, MerchantWithdrawalEvent, PayoutSwappedEvent,
+    PrepaidWithdrawnEvent, Quote, RoundingRemainderPolicy, ScheduleCorrectedEvent,
+    SetupFeeChargedEvent, Subscription, SubscriptionCancelledEvent, SubscriptionChargedEvent,
+    SubscriptionCreatedEvent, SubscriptionLapsedEvent, SubscriptionParams, SubscriptionPausedEvent,
+    SubscriptionReactivatedEvent, SubscriptionResumedEvent, SubscriptionStatus, SubscriptionVault,
+    SubscriptionVaultClient, UnauthorizedAttemptEvent, UsageChargedEvent, UsageRounding,
+    VaultStats,
+};
+use soroban_sdk::testutils::storage::Instance as _;
+use soroban_sdk::testutils::{Address as _, Events, Ledger as _, MockAuth, MockAuthInvoke};
+use soroban_sdk::token::{StellarAssetClient, TokenClient};
+use soroban_sdk::{
+    contract, contractimpl, symbol_short, Address, Bytes, Env, IntoVal, String, Symbol, TryFromVal,
+    Val, Vec,
 };
-use soroban_sdk::testutils::{Address as _, Events, Ledger as _};
-use soroban_sdk::{Address, Env, IntoVal, TryFromVal, Val, Vec};
 
 // ---------------------------------------------------------------------------
 // Helper: decode the event data payload (3rd element of event tuple)
@@ -15,6 +28,21 @@ fn last_event_data<T: TryFromVal<Env, Val>>(env: &Env) -> T {
     T::try_from_val(env, &last.2).unwrap()
 }
 
+// ---------------------------------------------------------------------------
+// Helpers: payment tokens
+// ---------------------------------------------------------------------------
+
+/// Registers a Stellar asset contract to act as a payment token.
+fn create_token(env: &Env) -> Address {
+    env.register_stellar_asset_contract_v2(Address::generate(env))
+        .address()
+}
+
+/// Mints a generous token balance to `to` so deposits can be pulled from it.
+fn mint(env: &Env, token: &Address, to: &Address) {
+    StellarAssetClient::new(env, token).mint(to, &1_000_000_000_000i128);
+}
+
 // =============================================================================
 // State Machine Helper Tests
 // =============================================================================
@@ -117,11 +145,11 @@ fn test_validate_insufficient_balance_transitions() {
 }
 
 #[test]
-fn test_validate_cancelled_transitions_all_blocked() {
-    // Cancelled is a terminal state - no outgoing transitions allowed
+fn test_validate_cancelled_transitions_only_reactivation() {
+    // Cancelled only leaves through reactivate_subscription, back to Active
     assert_eq!(
         validate_status_transition(&SubscriptionStatus::Cancelled, &SubscriptionStatus::Active),
-        Err(Error::InvalidStatusTransition)
+        Ok(())
     );
     assert_eq!(
         validate_status_transition(&SubscriptionStatus::Cancelled, &SubscriptionStatus::Paused),
@@ -151,12 +179,12 @@ fn test_can_transition_helper() {
         &SubscriptionStatus::Paused,
         &SubscriptionStatus::Active
     ));
-
-    // False cases
-    assert!(!can_transition(
+    assert!(can_transition(
         &SubscriptionStatus::Cancelled,
         &SubscriptionStatus::Active
     ));
+
+    // False cases
     assert!(!can_transition(
         &SubscriptionStatus::Cancelled,
         &SubscriptionStatus::Paused
@@ -184,7 +212,7 @@ fn test_get_allowed_transitions() {
 
     // Cancelled
     let cancelled_targets = get_allowed_transitions(&SubscriptionStatus::Cancelled);
-    assert_eq!(cancelled_targets.len(), 0);
+    assert_eq!(cancelled_targets, &[SubscriptionStatus::Active]);
 
     // InsufficientBalance
     let ib_targets = get_allowed_transitions(&SubscriptionStatus::InsufficientBalance);
@@ -193,6 +221,29 @@ fn test_get_allowed_transitions() {
     assert!(ib_targets.contains(&SubscriptionStatus::Cancelled));
 }
 
+#[test]
+fn test_returned_error_decodes_into_enum() {
+    let (_, client, _, _) = setup_test_env();
+    // Clients get the named variant back from a failed call...
+    assert_eq!(client.try_get_subscription(&999), Err(Ok(Error::NotFound)));
+    // ...and a raw contract error with a known code decodes into the same variant.
+    let raw = soroban_sdk::Error::from_contract_error(404);
+    assert_eq!(Error::try_from(raw), Ok(Error::NotFound));
+
+    // Numeric values are part of the ABI and must stay stable.
+    for (err, code) in [
+        (Error::InvalidStatusTransition, 400),
+        (Error::Unauthorized, 401),
+        (Error::BelowMinimumTopup, 402),
+        (Error::Overflow, 403),
+        (Error::NotFound, 404),
+        (Error::InsufficientBalance, 1003),
+    ] {
+        assert_eq!(err.clone().to_code(), code);
+        assert_eq!(err as u32, code);
+    }
+}
+
 // =============================================================================
 // Contract Entrypoint State Transition Tests
 // =============================================================================
@@ -203,10 +254,10 @@ fn setup_test_env() -> (Env, SubscriptionVaultClient<'static>, Address, Address)
     let contract_id = env.register(SubscriptionVault, ());
     let client = SubscriptionVaultClient::new(&env, &contract_id);
 
-    let token = Address::generate(&env);
+    let token = create_token(&env);
     let admin = Address::generate(&env);
     let min_topup = 1_000000i128; // 1 USDC
-    client.init(&token, &admin, &min_topup);
+    client.init(&token, &admin, &min_topup, &false);
 
     (env, client, token, admin)
 }
@@ -229,7 +280,11 @@ fn create_test_subscription(
         &amount,
         &interval_seconds,
         &usage_enabled,
+        &None,
+        &0,
+        &0,
     );
+    mint(env, &client.get_subscription(&id).token, &subscriber);
 
     // Manually set status if not Active (bypassing state machine for test setup)
     // Note: In production, this would go through proper transitions
@@ -277,14 +332,85 @@ fn test_init_with_min_topup() {
     env.mock_all_auths();
     let contract_id = env.register(SubscriptionVault, ());
     let client = SubscriptionVaultClient::new(&env, &contract_id);
-    let token = Address::generate(&env);
+    let token = create_token(&env);
     let admin = Address::generate(&env);
     let min_topup = 1_000000i128; // 1 USDC
-    client.init(&token, &admin, &min_topup);
+    client.init(&token, &admin, &min_topup, &false);
 
     assert_eq!(client.get_min_topup(), min_topup);
 }
 
+#[test]
+fn test_init_emits_event_once() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let client = SubscriptionVaultClient::new(&env, &env.register(SubscriptionVault, ()));
+    let token = create_token(&env);
+    let admin = Address::generate(&env);
+    client.init(&token, &admin, &1_000000i128, &true);
+
+    let events = env.events().all();
+    assert_eq!(events.len(), 1);
+    let event = events.get(0).unwrap();
+    assert_eq!(event.1, (symbol_short!("init"),).into_val(&env));
+    assert_eq!(
+        InitializedEvent::try_from_val(&env, &event.2).unwrap(),
+        InitializedEvent {
+            token: token.clone(),
+            admin: admin.clone(),
+            min_topup: 1_000000,
+            require_registration: true,
+        }
+    );
+
+    let other = Address::generate(&env);
+    assert_eq!(
+        client.try_init(&token, &other, &0, &false),
+        Err(Ok(Error::AlreadyInitialized))
+    );
+    assert_eq!(client.get_admin(), admin);
+    assert_eq!(client.get_min_topup(), 1_000000);
+}
+
+#[test]
+fn test_get_token_and_admin() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let client = SubscriptionVaultClient::new(&env, &env.register(SubscriptionVault, ()));
+    assert_eq!(client.try_get_token(), Err(Ok(Error::NotInitialized)));
+    assert_eq!(client.try_get_admin(), Err(Ok(Error::NotInitialized)));
+
+    let token = create_token(&env);
+    let admin = Address::generate(&env);
+    client.init(&token, &admin, &1_000000i128, &false);
+    assert_eq!(client.get_token(), token);
+    assert_eq!(client.get_admin(), admin);
+    // Stellar asset contracts use 7 decimals.
+    assert_eq!(client.token_decimals(), 7);
+}
+
+/// Minimal token stand-in that only reports its decimals.
+#[contract]
+struct SixDecimalToken;
+
+#[contractimpl]
+impl SixDecimalToken {
+    pub fn decimals(_env: Env) -> u32 {
+        6
+    }
+}
+
+#[test]
+fn test_init_stores_token_decimals() {
+    let env = Env::default();
+    env.mock_all_auths();
+    let client = SubscriptionVaultClient::new(&env, &env.register(SubscriptionVault, ()));
+    assert_eq!(client.try_token_decimals(), Err(Ok(Error::NotInitialized)));
+    let token = env.register(SixDecimalToken, ());
+    client.init(&token, &Address::generate(&env), &1_000000i128, &false);
+    assert_eq!(client.token_decimals(), 6);
+}
+
 #[test]
 fn test_pause_subscription_from_paused_is_idempotent() {
     // Idempotent transition: Paused -> Paused should succeed (no-op)
@@ -382,6 +508,133 @@ fn test_resume_subscription_from_cancelled_should_fail() {
     client.resume_subscription(&id, &subscriber);
 }
 
+#[test]
+fn test_reactivate_cancelled_subscription_can_be_charged_again() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let sub = client.get_subscription(&id);
+    client.cancel_subscription(&id, &sub.subscriber);
+
+    env.ledger().set_timestamp(T0 + 5 * INTERVAL);
+    client.reactivate_subscription(&id, &sub.subscriber);
+    let event: SubscriptionReactivatedEvent = last_event_data(&env);
+    assert_eq!(event.subscription_id, id);
+    assert_eq!(event.subscriber, sub.subscriber);
+    let reactivated = client.get_subscription(&id);
+    assert_eq!(reactivated.status, SubscriptionStatus::Active);
+    assert_eq!(reactivated.last_payment_timestamp, T0 + 5 * INTERVAL);
+
+    // The cancel refunded the balance; the billing clock restarted at reactivation.
+    client.deposit_funds(&id, &sub.subscriber, &10_000000i128, &None);
+    let res = client.try_charge_subscription(&id, &None);
+    assert_eq!(res, Err(Ok(Error::IntervalNotElapsed)));
+    env.ledger().set_timestamp(T0 + 6 * INTERVAL);
+    client.charge_subscription(&id, &None);
+    assert_eq!(
+        client.get_subscription(&id).prepaid_balance,
+        10_000000i128 - 1000
+    );
+}
+
+#[test]
+fn test_reactivate_rejects_non_cancelled_and_other_callers() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let sub = client.get_subscription(&id);
+    let res = client.try_reactivate_subscription(&id, &sub.subscriber);
+    assert_eq!(res, Err(Ok(Error::InvalidStatusTransition)));
+
+    client.cancel_subscription(&id, &sub.subscriber);
+    let res = client.try_reactivate_subscription(&id, &sub.merchant);
+    assert_eq!(res, Err(Ok(Error::Unauthorized)));
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Cancelled
+    );
+}
+
+#[test]
+fn test_reactivate_lapsed_subscription_bills_next_charge() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let sub = client.get_subscription(&id);
+    client.set_auto_renew(&id, &sub.subscriber, &false);
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    client.charge_subscription(&id, &None);
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Cancelled
+    );
+
+    client.reactivate_subscription(&id, &sub.subscriber);
+    assert!(client.get_auto_renew(&id));
+    client.deposit_funds(&id, &sub.subscriber, &10_000000i128, &None);
+    env.ledger().set_timestamp(T0 + 2 * INTERVAL);
+    client.charge_subscription(&id, &None);
+    let event: SubscriptionChargedEvent = last_event_data(&env);
+    assert_eq!(event.amount, 1000);
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.status, SubscriptionStatus::Active);
+    assert_eq!(sub.prepaid_balance, 10_000000i128 - 1000);
+}
+
+#[test]
+fn test_reactivate_leaves_credit_mode() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let sub = client.get_subscription(&id);
+    client.buy_credits(&id, &sub.subscriber, &2);
+    client.cancel_subscription(&id, &sub.subscriber);
+
+    client.reactivate_subscription(&id, &sub.subscriber);
+    client.deposit_funds(&id, &sub.subscriber, &5_000000i128, &None);
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    client.charge_subscription(&id, &None);
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.credits, 0);
+    assert_eq!(sub.prepaid_balance, 5_000000i128 - 1000);
+}
+
+#[test]
+fn test_reactivate_installment_plan_rejected() {
+    let (env, client, token, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    mint(&env, &token, &subscriber);
+    let id =
+        client.create_installment_plan(&subscriber, &Address::generate(&env), &99, &3, &INTERVAL);
+    client.cancel_subscription(&id, &subscriber);
+    assert_eq!(
+        client.try_reactivate_subscription(&id, &subscriber),
+        Err(Ok(Error::InvalidStatusTransition))
+    );
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #400)")]
+fn test_resume_subscription_from_active_should_fail() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+
+    // Nothing to resume
+    client.resume_subscription(&id, &subscriber);
+}
+
+#[test]
+fn test_resume_active_leaves_subscription_untouched() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let seq = client.get_event_seq(&id);
+
+    assert_eq!(
+        client.try_resume_subscription(&id, &subscriber),
+        Err(Ok(Error::InvalidStatusTransition))
+    );
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.status, SubscriptionStatus::Active);
+    assert_eq!(sub.resume_count, 0);
+    assert_eq!(client.get_event_seq(&id), seq);
+}
+
 #[test]
 fn test_state_transition_idempotent_same_status() {
     let (env, client, _, _) = setup_test_env();
@@ -419,6 +672,22 @@ fn test_full_lifecycle_active_pause_resume() {
     assert_eq!(sub.status, SubscriptionStatus::Paused);
 }
 
+#[test]
+fn test_pause_and_resume_counts() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+
+    for _ in 0..2 {
+        client.pause_subscription(&id, &subscriber);
+        // A repeated pause is a no-op and is not counted.
+        client.pause_subscription(&id, &subscriber);
+        client.resume_subscription(&id, &subscriber);
+    }
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.pause_count, 2);
+    assert_eq!(sub.resume_count, 2);
+}
+
 #[test]
 fn test_full_lifecycle_active_cancel() {
     let (env, client, _, _) = setup_test_env();
@@ -587,12 +856,34 @@ fn test_subscription_struct_status_field() {
     let sub = Subscription {
         subscriber: Address::generate(&env),
         merchant: Address::generate(&env),
-        amount: 10_000_0000,
+        token: Address::generate(&env),
+        amount: 100_000_000,
         interval_seconds: 30 * 24 * 60 * 60,
         last_payment_timestamp: 0,
         status: SubscriptionStatus::Active,
-        prepaid_balance: 50_000_0000,
+        prepaid_balance: 500_000_000,
         usage_enabled: false,
+        min_deposit: 0,
+        max_prepaid: 0,
+        max_total_charge: 0,
+        total_charged: 0,
+        low_balance_threshold: 0,
+        receipt_threshold: 0,
+        discount_bps: 0,
+        discount_expiry: 0,
+        grace_seconds: 0,
+        failed_charge_count: 0,
+        max_cycles: 0,
+        cycles_charged: 0,
+        min_charge: 0,
+        accrued_usage: 0,
+        pause_count: 0,
+        resume_count: 0,
+        credits: 0,
+        start_timestamp: 0,
+        created_at: 0,
+        external_ref: Bytes::new(&env),
+        metadata: String::from_str(&env, ""),
     };
     assert_eq!(sub.status, SubscriptionStatus::Active);
 }
@@ -609,14 +900,23 @@ fn setup(env: &Env, interval_seconds: u64) -> (SubscriptionVaultClient<'static>,
     env.ledger().set_timestamp(T0);
     let contract_id = env.register(SubscriptionVault, ());
     let client = SubscriptionVaultClient::new(env, &contract_id);
-    let token = Address::generate(env);
+    let token = create_token(env);
     let admin = Address::generate(env);
-    client.init(&token, &admin, &1_000000i128);
+    client.init(&token, &admin, &1_000000i128, &false);
     let subscriber = Address::generate(env);
+    mint(env, &token, &subscriber);
     let merchant = Address::generate(env);
-    let id =
-        client.create_subscription(&subscriber, &merchant, &1000i128, &interval_seconds, &false);
-    client.deposit_funds(&id, &subscriber, &10_000000i128); // 10 USDC so charge can succeed
+    let id = client.create_subscription(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &interval_seconds,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    client.deposit_funds(&id, &subscriber, &10_000000i128, &None); // 10 USDC so charge can succeed
     (client, id)
 }
 
@@ -654,6 +954,35 @@ fn test_charge_succeeds_at_exact_interval() {
     assert_eq!(sub.last_payment_timestamp, T0 + INTERVAL);
 }
 
+/// The boundary stays inclusive after the window resets, and `can_charge` agrees with the
+/// charge on both sides of it.
+#[test]
+fn test_boundary_inclusive_after_window_reset() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    client.charge_subscription(&id, &None);
+
+    let boundary = T0 + 2 * INTERVAL;
+    env.ledger().set_timestamp(boundary - 1);
+    assert_eq!(
+        client.can_charge(&id),
+        (false, Error::IntervalNotElapsed.to_code())
+    );
+    assert_eq!(
+        client.try_charge_subscription(&id, &None),
+        Err(Ok(Error::IntervalNotElapsed))
+    );
+
+    env.ledger().set_timestamp(boundary);
+    assert_eq!(client.can_charge(&id), (true, 0));
+    client.charge_subscription(&id, &None);
+    assert_eq!(
+        client.get_subscription(&id).last_payment_timestamp,
+        boundary
+    );
+}
+
 /// After interval: charge well past the interval boundary.
 /// Must succeed and set last_payment_timestamp to the current ledger time.
 #[test]
@@ -844,14 +1173,14 @@ fn test_min_topup_below_threshold() {
     let contract_id = env.register(SubscriptionVault, ());
     let client = SubscriptionVaultClient::new(&env, &contract_id);
 
-    let token = Address::generate(&env);
+    let token = create_token(&env);
     let admin = Address::generate(&env);
     let subscriber = Address::generate(&env);
     let min_topup = 5_000000i128; // 5 USDC
 
-    client.init(&token, &admin, &min_topup);
+    client.init(&token, &admin, &min_topup, &false);
 
-    let result = client.try_deposit_funds(&0, &subscriber, &4_999999);
+    let result = client.try_deposit_funds(&0, &subscriber, &4_999999, &None);
     assert!(result.is_err());
 }
 
@@ -861,19 +1190,29 @@ fn test_charge_subscription_auth() {
     let contract_id = env.register(SubscriptionVault, ());
     let client = SubscriptionVaultClient::new(&env, &contract_id);
 
-    let token = Address::generate(&env);
+    let token = create_token(&env);
     let admin = Address::generate(&env);
     let min_topup = 1_000000i128;
-    client.init(&token, &admin, &min_topup);
+    client.init(&token, &admin, &min_topup, &false);
 
     // Test authorized call
     env.mock_all_auths();
 
     // Create a subscription so ID 0 exists
     let subscriber = Address::generate(&env);
+    mint(&env, &token, &subscriber);
     let merchant = Address::generate(&env);
-    client.create_subscription(&subscriber, &merchant, &1000i128, &3600u64, &false);
-    client.deposit_funds(&0, &subscriber, &10_000000i128);
+    client.create_subscription(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &3600u64,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    client.deposit_funds(&0, &subscriber, &10_000000i128, &None);
     env.ledger().set_timestamp(3600); // interval elapsed so charge is allowed
 
     client.charge_subscription(&0, &None);
@@ -886,16 +1225,25 @@ fn test_charge_subscription_unauthorized() {
     let contract_id = env.register(SubscriptionVault, ());
     let client = SubscriptionVaultClient::new(&env, &contract_id);
 
-    let token = Address::generate(&env);
+    let token = create_token(&env);
     let admin = Address::generate(&env);
     let min_topup = 1_000000i128;
-    client.init(&token, &admin, &min_topup);
+    client.init(&token, &admin, &min_topup, &false);
 
     // Create a subscription so ID 0 exists (using mock_all_auths for setup)
     let subscriber = Address::generate(&env);
     let merchant = Address::generate(&env);
     env.mock_all_auths();
-    client.create_subscription(&subscriber, &merchant, &1000i128, &3600u64, &false);
+    client.create_subscription(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &3600u64,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
 
     let non_admin = Address::generate(&env);
 
@@ -914,23 +1262,117 @@ fn test_charge_subscription_unauthorized() {
     client.charge_subscription(&0, &None);
 }
 
+#[test]
+fn test_create_subscription_requires_subscriber_auth() {
+    let env = Env::default();
+    let contract_id = env.register(SubscriptionVault, ());
+    let client = SubscriptionVaultClient::new(&env, &contract_id);
+    client.init(
+        &create_token(&env),
+        &Address::generate(&env),
+        &1_000000i128,
+        &false,
+    );
+
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let no_token: Option<Address> = None;
+    let args = (
+        subscriber.clone(),
+        merchant.clone(),
+        1000i128,
+        3600u64,
+        false,
+        no_token,
+        0i128,
+        0i128,
+    );
+
+    // No auths at all: rejected.
+    let res = client.try_create_subscription(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &3600u64,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    assert!(res.is_err());
+
+    // Only the merchant signs: still rejected, the subscriber must consent.
+    env.mock_auths(&[soroban_sdk::testutils::MockAuth {
+        address: &merchant,
+        invoke: &soroban_sdk::testutils::MockAuthInvoke {
+            contract: &contract_id,
+            fn_name: "create_subscription",
+            args: args.clone().into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    let res = client.try_create_subscription(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &3600u64,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    assert!(res.is_err());
+
+    // The subscriber signs: created.
+    env.mock_auths(&[soroban_sdk::testutils::MockAuth {
+        address: &subscriber,
+        invoke: &soroban_sdk::testutils::MockAuthInvoke {
+            contract: &contract_id,
+            fn_name: "create_subscription",
+            args: args.into_val(&env),
+            sub_invokes: &[],
+        },
+    }]);
+    let id = client.create_subscription(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &3600u64,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    assert_eq!(client.get_subscription(&id).subscriber, subscriber);
+}
+
 #[test]
 fn test_charge_subscription_admin() {
     let env = Env::default();
     let contract_id = env.register(SubscriptionVault, ());
     let client = SubscriptionVaultClient::new(&env, &contract_id);
 
-    let token = Address::generate(&env);
+    let token = create_token(&env);
     let admin = Address::generate(&env);
     let min_topup = 1_000000i128;
-    client.init(&token, &admin, &min_topup);
+    client.init(&token, &admin, &min_topup, &false);
 
     // Create a subscription so ID 0 exists (using mock_all_auths for setup)
     let subscriber = Address::generate(&env);
     let merchant = Address::generate(&env);
     env.mock_all_auths();
-    client.create_subscription(&subscriber, &merchant, &1000i128, &3600u64, &false);
-    client.deposit_funds(&0, &subscriber, &10_000000i128);
+    client.create_subscription(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &3600u64,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    mint(&env, &token, &subscriber);
+    client.deposit_funds(&0, &subscriber, &10_000000i128, &None);
     env.ledger().set_timestamp(3600); // interval elapsed so charge is allowed
 
     // Mock auth for the admin address (args: subscription_id, idempotency_key)
@@ -955,16 +1397,26 @@ fn test_min_topup_exactly_at_threshold() {
     let contract_id = env.register(SubscriptionVault, ());
     let client = SubscriptionVaultClient::new(&env, &contract_id);
 
-    let token = Address::generate(&env);
+    let token = create_token(&env);
     let admin = Address::generate(&env);
     let subscriber = Address::generate(&env);
+    mint(&env, &token, &subscriber);
     let merchant = Address::generate(&env);
     let min_topup = 5_000000i128; // 5 USDC
 
-    client.init(&token, &admin, &min_topup);
-    client.create_subscription(&subscriber, &merchant, &1000i128, &86400u64, &false);
+    client.init(&token, &admin, &min_topup, &false);
+    client.create_subscription(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &86400u64,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
 
-    let result = client.try_deposit_funds(&0, &subscriber, &min_topup);
+    let result = client.try_deposit_funds(&0, &subscriber, &min_topup, &None);
     assert!(result.is_ok());
 }
 
@@ -975,16 +1427,26 @@ fn test_min_topup_above_threshold() {
     let contract_id = env.register(SubscriptionVault, ());
     let client = SubscriptionVaultClient::new(&env, &contract_id);
 
-    let token = Address::generate(&env);
+    let token = create_token(&env);
     let admin = Address::generate(&env);
     let subscriber = Address::generate(&env);
+    mint(&env, &token, &subscriber);
     let merchant = Address::generate(&env);
     let min_topup = 5_000000i128; // 5 USDC
 
-    client.init(&token, &admin, &min_topup);
-    client.create_subscription(&subscriber, &merchant, &1000i128, &86400u64, &false);
+    client.init(&token, &admin, &min_topup, &false);
+    client.create_subscription(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &86400u64,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
 
-    let result = client.try_deposit_funds(&0, &subscriber, &10_000000);
+    let result = client.try_deposit_funds(&0, &subscriber, &10_000000, &None);
     assert!(result.is_ok());
 }
 
@@ -995,12 +1457,12 @@ fn test_set_min_topup_by_admin() {
     let contract_id = env.register(SubscriptionVault, ());
     let client = SubscriptionVaultClient::new(&env, &contract_id);
 
-    let token = Address::generate(&env);
+    let token = create_token(&env);
     let admin = Address::generate(&env);
     let initial_min = 1_000000i128;
     let new_min = 10_000000i128;
 
-    client.init(&token, &admin, &initial_min);
+    client.init(&token, &admin, &initial_min, &false);
     assert_eq!(client.get_min_topup(), initial_min);
 
     client.set_min_topup(&admin, &new_min);
@@ -1014,65 +1476,333 @@ fn test_set_min_topup_unauthorized() {
     let contract_id = env.register(SubscriptionVault, ());
     let client = SubscriptionVaultClient::new(&env, &contract_id);
 
-    let token = Address::generate(&env);
+    let token = create_token(&env);
     let admin = Address::generate(&env);
     let non_admin = Address::generate(&env);
     let min_topup = 1_000000i128;
 
-    client.init(&token, &admin, &min_topup);
+    client.init(&token, &admin, &min_topup, &false);
 
     let result = client.try_set_min_topup(&non_admin, &5_000000);
     assert!(result.is_err());
 }
 
-// =============================================================================
-// estimate_topup_for_intervals tests (#28)
-// =============================================================================
+// -- Per-subscription minimum deposit ----------------------------------------
 
-#[test]
-fn test_estimate_topup_zero_intervals_returns_zero() {
-    let (env, client, _, _) = setup_test_env();
-    let (id, _, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
-    let topup = client.estimate_topup_for_intervals(&id, &0);
-    assert_eq!(topup, 0);
+/// Creates a subscription requiring deposits of at least `min_deposit` (global min_topup is
+/// 1 USDC).
+fn setup_min_deposit(
+    env: &Env,
+    min_deposit: i128,
+) -> (SubscriptionVaultClient<'static>, u32, Address) {
+    let (client, token) = {
+        env.mock_all_auths();
+        let contract_id = env.register(SubscriptionVault, ());
+        let client = SubscriptionVaultClient::new(env, &contract_id);
+        let token = create_token(env);
+        client.init(&token, &Address::generate(env), &1_000000i128, &false);
+        (client, token)
+    };
+    let subscriber = Address::generate(env);
+    let merchant = Address::generate(env);
+    mint(env, &token, &subscriber);
+    let id = client.create_subscription(
+        &subscriber,
+        &merchant,
+        &5_000000i128,
+        &INTERVAL,
+        &false,
+        &None,
+        &min_deposit,
+        &0,
+    );
+    (client, id, subscriber)
 }
 
 #[test]
-fn test_estimate_topup_balance_already_covers_returns_zero() {
-    let (env, client, _, _) = setup_test_env();
-    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
-    // 10 USDC per interval, deposit 30 USDC, ask for 3 intervals -> required 30, balance 30, topup 0
-    client.deposit_funds(&id, &subscriber, &30_000000i128);
-    let sub = client.get_subscription(&id);
-    assert_eq!(sub.amount, 10_000_000); // from create_test_subscription
-    let topup = client.estimate_topup_for_intervals(&id, &3);
-    assert_eq!(topup, 0);
+fn test_min_deposit_below_rejected() {
+    let env = Env::default();
+    let (client, id, subscriber) = setup_min_deposit(&env, 5_000000i128);
+    let res = client.try_deposit_funds(&id, &subscriber, &4_999999i128, &None);
+    assert_eq!(res, Err(Ok(Error::DepositTooSmall)));
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 0);
 }
 
 #[test]
-fn test_estimate_topup_insufficient_balance_returns_shortfall() {
-    let (env, client, _, _) = setup_test_env();
-    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
-    // amount 10_000_000, 3 intervals = 30_000_000 required; deposit 10_000_000 -> topup 20_000_000
-    client.deposit_funds(&id, &subscriber, &10_000000i128);
-    let topup = client.estimate_topup_for_intervals(&id, &3);
-    assert_eq!(topup, 20_000_000);
+fn test_min_deposit_equal_accepted() {
+    let env = Env::default();
+    let (client, id, subscriber) = setup_min_deposit(&env, 5_000000i128);
+    client.deposit_funds(&id, &subscriber, &5_000000i128, &None);
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 5_000000i128);
 }
 
 #[test]
-fn test_estimate_topup_no_balance_returns_full_required() {
-    let (env, client, _, _) = setup_test_env();
-    let (id, _, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
-    // prepaid_balance 0, 5 intervals * 10_000_000 = 50_000_000
-    let topup = client.estimate_topup_for_intervals(&id, &5);
-    assert_eq!(topup, 50_000_000);
+fn test_min_deposit_above_accepted() {
+    let env = Env::default();
+    let (client, id, subscriber) = setup_min_deposit(&env, 5_000000i128);
+    client.deposit_funds(&id, &subscriber, &12_000000i128, &None);
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 12_000000i128);
 }
 
 #[test]
-fn test_estimate_topup_subscription_not_found() {
-    let (_env, client, _, _) = setup_test_env();
-    let result = client.try_estimate_topup_for_intervals(&9999, &1);
-    assert_eq!(result, Err(Ok(Error::NotFound)));
+fn test_min_deposit_zero_disables_check() {
+    let env = Env::default();
+    let (client, id, subscriber) = setup_min_deposit(&env, 0);
+    // Only the global min_topup (1 USDC) applies.
+    client.deposit_funds(&id, &subscriber, &1_000000i128, &None);
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 1_000000i128);
+}
+
+#[test]
+fn test_max_prepaid_deposit_up_to_cap_accepted() {
+    let env = Env::default();
+    let (client, id, subscriber) = setup_min_deposit(&env, 0);
+    let merchant = client.get_subscription(&id).merchant;
+    client.set_max_prepaid(&id, &merchant, &10_000000i128);
+    client.deposit_funds(&id, &subscriber, &6_000000i128, &None);
+    client.deposit_funds(&id, &subscriber, &4_000000i128, &None);
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 10_000000i128);
+}
+
+#[test]
+fn test_max_prepaid_deposit_crossing_cap_rejected() {
+    let env = Env::default();
+    let (client, id, subscriber) = setup_min_deposit(&env, 0);
+    let merchant = client.get_subscription(&id).merchant;
+    client.set_max_prepaid(&id, &merchant, &10_000000i128);
+    client.deposit_funds(&id, &subscriber, &6_000000i128, &None);
+
+    let res = client.try_deposit_funds(&id, &subscriber, &4_000001i128, &None);
+    assert_eq!(res, Err(Ok(Error::ExceedsMaxPrepaid)));
+    assert_eq!(Error::ExceedsMaxPrepaid.to_code(), 424);
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 6_000000i128);
+
+    // Zero removes the cap.
+    client.set_max_prepaid(&id, &merchant, &0);
+    client.deposit_funds(&id, &subscriber, &4_000001i128, &None);
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 10_000001i128);
+}
+
+#[test]
+fn test_set_max_prepaid_merchant_only() {
+    let env = Env::default();
+    let (client, id, subscriber) = setup_min_deposit(&env, 0);
+    let res = client.try_set_max_prepaid(&id, &subscriber, &1_000000i128);
+    assert_eq!(res, Err(Ok(Error::Unauthorized)));
+    let merchant = client.get_subscription(&id).merchant;
+    let res = client.try_set_max_prepaid(&id, &merchant, &-1);
+    assert_eq!(res, Err(Ok(Error::InvalidAmount)));
+}
+
+// =============================================================================
+// deposit_funds idempotency
+// =============================================================================
+
+#[test]
+fn test_deposit_idempotency_key_credits_once() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let sub = client.get_subscription(&id);
+    let key = idempotency_key(&env, 7);
+
+    client.deposit_funds(&id, &sub.subscriber, &5_000000i128, &Some(key.clone()));
+    client.deposit_funds(&id, &sub.subscriber, &5_000000i128, &Some(key));
+    assert_eq!(
+        client.get_subscription(&id).prepaid_balance,
+        sub.prepaid_balance + 5_000000
+    );
+    assert_eq!(
+        TokenClient::new(&env, &sub.token).balance(&client.address),
+        sub.prepaid_balance + 5_000000
+    );
+
+    // A different key is a new deposit.
+    client.deposit_funds(
+        &id,
+        &sub.subscriber,
+        &5_000000i128,
+        &Some(idempotency_key(&env, 8)),
+    );
+    assert_eq!(
+        client.get_subscription(&id).prepaid_balance,
+        sub.prepaid_balance + 10_000000
+    );
+
+    // Only the latest key is kept: storage holds one key, not every key seen.
+    let stored = env.as_contract(&client.address, || {
+        env.storage()
+            .instance()
+            .get::<_, soroban_sdk::BytesN<32>>(&(symbol_short!("depidem"), id))
+    });
+    assert_eq!(stored, Some(idempotency_key(&env, 8)));
+}
+
+#[test]
+fn test_deposit_idempotency_key_not_recorded_on_failure() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let sub = client.get_subscription(&id);
+    let key = idempotency_key(&env, 9);
+
+    // Below min_topup: rejected, so the key must still be usable.
+    let res = client.try_deposit_funds(&id, &sub.subscriber, &1i128, &Some(key.clone()));
+    assert_eq!(res, Err(Ok(Error::BelowMinimumTopup)));
+    client.deposit_funds(&id, &sub.subscriber, &2_000000i128, &Some(key));
+    assert_eq!(
+        client.get_subscription(&id).prepaid_balance,
+        sub.prepaid_balance + 2_000000
+    );
+}
+
+// =============================================================================
+// estimate_topup_for_intervals tests (#28)
+// =============================================================================
+
+#[test]
+fn test_estimate_topup_zero_intervals_returns_zero() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, _, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let topup = client.estimate_topup_for_intervals(&id, &0);
+    assert_eq!(topup, 0);
+}
+
+#[test]
+fn test_estimate_topup_balance_already_covers_returns_zero() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    // 10 USDC per interval, deposit 30 USDC, ask for 3 intervals -> required 30, balance 30, topup 0
+    client.deposit_funds(&id, &subscriber, &30_000000i128, &None);
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.amount, 10_000_000); // from create_test_subscription
+    let topup = client.estimate_topup_for_intervals(&id, &3);
+    assert_eq!(topup, 0);
+}
+
+#[test]
+fn test_estimate_topup_insufficient_balance_returns_shortfall() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    // amount 10_000_000, 3 intervals = 30_000_000 required; deposit 10_000_000 -> topup 20_000_000
+    client.deposit_funds(&id, &subscriber, &10_000000i128, &None);
+    let topup = client.estimate_topup_for_intervals(&id, &3);
+    assert_eq!(topup, 20_000_000);
+}
+
+#[test]
+fn test_estimate_topup_no_balance_returns_full_required() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, _, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    // prepaid_balance 0, 5 intervals * 10_000_000 = 50_000_000
+    let topup = client.estimate_topup_for_intervals(&id, &5);
+    assert_eq!(topup, 50_000_000);
+}
+
+#[test]
+fn test_estimate_topup_subscription_not_found() {
+    let (_env, client, _, _) = setup_test_env();
+    let result = client.try_estimate_topup_for_intervals(&9999, &1);
+    assert_eq!(result, Err(Ok(Error::NotFound)));
+}
+
+// =============================================================================
+// covered_intervals tests
+// =============================================================================
+
+#[test]
+fn test_covered_intervals_draws_down_one_per_charge() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    // 10 USDC per interval; 55 USDC covers 5 full intervals.
+    client.deposit_funds(&id, &subscriber, &55_000000i128, &None);
+    assert_eq!(client.covered_intervals(&id), 5);
+
+    let start = env.ledger().timestamp();
+    env.ledger().set_timestamp(start + INTERVAL);
+    client.charge_subscription(&id, &None);
+    assert_eq!(client.covered_intervals(&id), 4);
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 45_000000i128);
+}
+
+#[test]
+fn test_covered_intervals_empty_balance_is_zero() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, _, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    assert_eq!(client.covered_intervals(&id), 0);
+}
+
+#[test]
+fn test_create_subscription_zero_amount_rejected() {
+    let (env, client, _, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let res = client.try_create_subscription(
+        &subscriber,
+        &merchant,
+        &0i128,
+        &INTERVAL,
+        &false,
+        &None,
+        &0i128,
+        &0i128,
+    );
+    assert_eq!(res, Err(Ok(Error::InvalidAmount)));
+}
+
+// =============================================================================
+// can_charge tests
+// =============================================================================
+
+#[test]
+fn test_can_charge_when_due_and_funded() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    assert_eq!(client.can_charge(&id), (true, 0));
+
+    // The check agrees with an actual charge and does not consume the period itself.
+    client.charge_subscription(&id, &None);
+}
+
+#[test]
+fn test_can_charge_too_early() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    env.ledger().set_timestamp(T0 + INTERVAL - 1);
+    assert_eq!(
+        client.can_charge(&id),
+        (false, Error::IntervalNotElapsed.to_code())
+    );
+}
+
+#[test]
+fn test_can_charge_underfunded() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, _, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    env.ledger()
+        .set_timestamp(env.ledger().timestamp() + INTERVAL);
+    assert_eq!(
+        client.can_charge(&id),
+        (false, Error::InsufficientBalance.to_code())
+    );
+}
+
+#[test]
+fn test_can_charge_not_active() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let subscriber = client.get_subscription(&id).subscriber;
+    client.pause_subscription(&id, &subscriber);
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    assert_eq!(client.can_charge(&id), (false, Error::NotActive.to_code()));
+}
+
+#[test]
+fn test_can_charge_already_charged_this_period() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    client.charge_subscription(&id, &None);
+    assert_eq!(client.can_charge(&id), (false, Error::Replay.to_code()));
 }
 
 // =============================================================================
@@ -1084,15 +1814,34 @@ fn setup_batch_env(env: &Env) -> (SubscriptionVaultClient<'static>, Address, u32
     env.ledger().set_timestamp(T0);
     let contract_id = env.register(SubscriptionVault, ());
     let client = SubscriptionVaultClient::new(env, &contract_id);
-    let token = Address::generate(env);
+    let token = create_token(env);
     let admin = Address::generate(env);
-    client.init(&token, &admin, &1_000000i128);
+    client.init(&token, &admin, &1_000000i128, &false);
     let subscriber = Address::generate(env);
+    mint(env, &token, &subscriber);
     let merchant = Address::generate(env);
-    let id0 = client.create_subscription(&subscriber, &merchant, &1000i128, &INTERVAL, &false);
-    client.deposit_funds(&id0, &subscriber, &10_000000i128);
-    let id1 = client.create_subscription(&subscriber, &merchant, &1000i128, &INTERVAL, &false);
-    client.deposit_funds(&id1, &subscriber, &10_000000i128);
+    let id0 = client.create_subscription(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &INTERVAL,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    client.deposit_funds(&id0, &subscriber, &10_000000i128, &None);
+    let id1 = client.create_subscription(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &INTERVAL,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    client.deposit_funds(&id1, &subscriber, &10_000000i128, &None);
     env.ledger().set_timestamp(T0 + INTERVAL);
     (client, admin, id0, id1)
 }
@@ -1126,14 +1875,33 @@ fn test_batch_charge_partial_failure() {
     env.ledger().set_timestamp(T0);
     let contract_id = env.register(SubscriptionVault, ());
     let client = SubscriptionVaultClient::new(&env, &contract_id);
-    let token = Address::generate(&env);
+    let token = create_token(&env);
     let admin = Address::generate(&env);
-    client.init(&token, &admin, &1_000000i128);
+    client.init(&token, &admin, &1_000000i128, &false);
     let subscriber = Address::generate(&env);
+    mint(&env, &token, &subscriber);
     let merchant = Address::generate(&env);
-    let id0 = client.create_subscription(&subscriber, &merchant, &1000i128, &INTERVAL, &false);
-    client.deposit_funds(&id0, &subscriber, &10_000000i128);
-    let id1 = client.create_subscription(&subscriber, &merchant, &1000i128, &INTERVAL, &false);
+    let id0 = client.create_subscription(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &INTERVAL,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    client.deposit_funds(&id0, &subscriber, &10_000000i128, &None);
+    let id1 = client.create_subscription(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &INTERVAL,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
     // id1 has no deposit -> charge will fail with InsufficientBalance
     env.ledger().set_timestamp(T0 + INTERVAL);
     let mut ids = Vec::new(&env);
@@ -1158,10 +1926,17 @@ fn test_oneoff_charge_valid_debits_balance() {
     let (env, client, _, _) = setup_test_env();
     let (id, subscriber, merchant) =
         create_test_subscription(&env, &client, SubscriptionStatus::Active);
-    client.deposit_funds(&id, &subscriber, &20_000000i128);
+    client.deposit_funds(&id, &subscriber, &20_000000i128, &None);
     let before = client.get_subscription(&id).prepaid_balance;
 
     client.charge_one_off(&id, &merchant, &5_000000i128);
+    let credited = env
+        .events()
+        .all()
+        .iter()
+        .filter(|e| e.1 == (symbol_short!("credited"), id).into_val(&env))
+        .count();
+    assert_eq!(credited, 1);
 
     let sub = client.get_subscription(&id);
     assert_eq!(sub.prepaid_balance, before - 5_000000i128);
@@ -1172,7 +1947,7 @@ fn test_oneoff_charge_exceeds_balance_fails() {
     let (env, client, _, _) = setup_test_env();
     let (id, subscriber, merchant) =
         create_test_subscription(&env, &client, SubscriptionStatus::Active);
-    client.deposit_funds(&id, &subscriber, &3_000000i128);
+    client.deposit_funds(&id, &subscriber, &3_000000i128, &None);
 
     let res = client.try_charge_one_off(&id, &merchant, &5_000000i128);
     assert_eq!(res, Err(Ok(Error::InsufficientBalance)));
@@ -1183,7 +1958,7 @@ fn test_oneoff_charge_wrong_merchant_unauthorized() {
     let (env, client, _, _) = setup_test_env();
     let (id, subscriber, _merchant) =
         create_test_subscription(&env, &client, SubscriptionStatus::Active);
-    client.deposit_funds(&id, &subscriber, &10_000000i128);
+    client.deposit_funds(&id, &subscriber, &10_000000i128, &None);
     let other_merchant = Address::generate(&env);
 
     let res = client.try_charge_one_off(&id, &other_merchant, &1_000000i128);
@@ -1195,7 +1970,7 @@ fn test_oneoff_charge_cancelled_subscription_fails() {
     let (env, client, _, _) = setup_test_env();
     let (id, subscriber, merchant) =
         create_test_subscription(&env, &client, SubscriptionStatus::Active);
-    client.deposit_funds(&id, &subscriber, &10_000000i128);
+    client.deposit_funds(&id, &subscriber, &10_000000i128, &None);
     client.cancel_subscription(&id, &subscriber);
 
     let res = client.try_charge_one_off(&id, &merchant, &1_000000i128);
@@ -1207,7 +1982,7 @@ fn test_oneoff_charge_paused_subscription_succeeds() {
     let (env, client, _, _) = setup_test_env();
     let (id, subscriber, merchant) =
         create_test_subscription(&env, &client, SubscriptionStatus::Active);
-    client.deposit_funds(&id, &subscriber, &20_000000i128);
+    client.deposit_funds(&id, &subscriber, &20_000000i128, &None);
     client.pause_subscription(&id, &subscriber);
 
     client.charge_one_off(&id, &merchant, &2_000000i128);
@@ -1220,7 +1995,7 @@ fn test_oneoff_charge_zero_amount_fails() {
     let (env, client, _, _) = setup_test_env();
     let (id, subscriber, merchant) =
         create_test_subscription(&env, &client, SubscriptionStatus::Active);
-    client.deposit_funds(&id, &subscriber, &10_000000i128);
+    client.deposit_funds(&id, &subscriber, &10_000000i128, &None);
 
     let res = client.try_charge_one_off(&id, &merchant, &0i128);
     assert_eq!(res, Err(Ok(Error::InvalidAmount)));
@@ -1233,13 +2008,23 @@ fn test_oneoff_and_recurring_charge_coexist() {
     env.ledger().set_timestamp(T0);
     let contract_id = env.register(SubscriptionVault, ());
     let client = SubscriptionVaultClient::new(&env, &contract_id);
-    let token = Address::generate(&env);
+    let token = create_token(&env);
     let admin = Address::generate(&env);
-    client.init(&token, &admin, &1_000000i128);
+    client.init(&token, &admin, &1_000000i128, &false);
     let subscriber = Address::generate(&env);
+    mint(&env, &token, &subscriber);
     let merchant = Address::generate(&env);
-    let id = client.create_subscription(&subscriber, &merchant, &1000i128, &INTERVAL, &false);
-    client.deposit_funds(&id, &subscriber, &15_000000i128);
+    let id = client.create_subscription(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &INTERVAL,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    client.deposit_funds(&id, &subscriber, &15_000000i128, &None);
 
     client.charge_one_off(&id, &merchant, &3_000000i128);
     assert_eq!(client.get_subscription(&id).prepaid_balance, 12_000000i128);
@@ -1270,9 +2055,9 @@ fn setup_multi_actor(
     env.ledger().set_timestamp(T0);
     let contract_id = env.register(SubscriptionVault, ());
     let client = SubscriptionVaultClient::new(env, &contract_id);
-    let token = Address::generate(env);
+    let token = create_token(env);
     let admin = Address::generate(env);
-    client.init(&token, &admin, &1_000000i128);
+    client.init(&token, &admin, &1_000000i128, &false);
 
     let merchants = [Address::generate(env), Address::generate(env)];
     let subscribers = [
@@ -1280,6 +2065,9 @@ fn setup_multi_actor(
         Address::generate(env),
         Address::generate(env),
     ];
+    for subscriber in subscribers.iter() {
+        mint(env, &token, subscriber);
+    }
     let amount = 1000i128;
     let interval = INTERVAL;
     let mut ids = Vec::new(env);
@@ -1293,8 +2081,11 @@ fn setup_multi_actor(
             &amount,
             &interval,
             &false,
+            &None,
+            &0,
+            &0,
         );
-        client.deposit_funds(&id, &subscribers[si], &20_000000i128);
+        client.deposit_funds(&id, &subscribers[si], &20_000000i128, &None);
         ids.push_back(id);
     }
     (client, admin, subscribers, merchants, ids)
@@ -1436,3 +2227,4701 @@ fn test_multi_actor_view_helpers_consistent() {
         assert_eq!(topup_2, expected);
     }
 }
+
+// =============================================================================
+// Multiple payment tokens
+// =============================================================================
+
+#[test]
+fn test_subscription_defaults_to_init_token() {
+    let (env, client, token, _) = setup_test_env();
+    let (id, _, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    assert_eq!(client.get_subscription(&id).token, token);
+}
+
+#[test]
+fn test_multi_token_balances_do_not_cross_contaminate() {
+    let env = Env::default();
+    env.mock_all_auths();
+    env.ledger().set_timestamp(T0);
+    let contract_id = env.register(SubscriptionVault, ());
+    let client = SubscriptionVaultClient::new(&env, &contract_id);
+    let token_a = create_token(&env);
+    let token_b = create_token(&env);
+    let admin = Address::generate(&env);
+    client.init(&token_a, &admin, &1_000000i128, &false);
+
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    mint(&env, &token_a, &subscriber);
+    mint(&env, &token_b, &subscriber);
+
+    let id_a = client.create_subscription(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &INTERVAL,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    let id_b = client.create_subscription(
+        &subscriber,
+        &merchant,
+        &2500i128,
+        &INTERVAL,
+        &false,
+        &Some(token_b.clone()),
+        &0,
+        &0,
+    );
+    assert_eq!(client.get_subscription(&id_a).token, token_a);
+    assert_eq!(client.get_subscription(&id_b).token, token_b);
+
+    client.deposit_funds(&id_a, &subscriber, &10_000000i128, &None);
+    client.deposit_funds(&id_b, &subscriber, &20_000000i128, &None);
+    let vault_a = TokenClient::new(&env, &token_a);
+    let vault_b = TokenClient::new(&env, &token_b);
+    assert_eq!(vault_a.balance(&contract_id), 10_000000i128);
+    assert_eq!(vault_b.balance(&contract_id), 20_000000i128);
+
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    client.charge_subscription(&id_a, &None);
+    client.charge_subscription(&id_b, &None);
+    assert_eq!(client.get_merchant_balance(&merchant, &token_a), 1000i128);
+    assert_eq!(client.get_merchant_balance(&merchant, &token_b), 2500i128);
+
+    // Withdrawing token B leaves the token A balance untouched.
+    client.withdraw_merchant_token_funds(&merchant, &token_b, &2500i128);
+    assert_eq!(client.get_merchant_balance(&merchant, &token_b), 0);
+    assert_eq!(client.get_merchant_balance(&merchant, &token_a), 1000i128);
+    assert_eq!(vault_b.balance(&merchant), 2500i128);
+    assert_eq!(vault_a.balance(&merchant), 0);
+
+    // Default-token withdrawal pays out token A.
+    client.withdraw_merchant_funds(&merchant, &1000i128);
+    assert_eq!(vault_a.balance(&merchant), 1000i128);
+    assert_eq!(client.get_merchant_balance(&merchant, &token_a), 0);
+}
+
+#[test]
+fn test_merchant_balances_batch_getter() {
+    let env = Env::default();
+    let (client, _admin, _subscribers, merchants, ids) = setup_multi_actor(&env);
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    client.batch_charge(&ids);
+
+    let idle_merchant = Address::generate(&env);
+    let mut query = Vec::new(&env);
+    query.push_back(merchants[0].clone());
+    query.push_back(merchants[1].clone());
+    query.push_back(idle_merchant.clone());
+    let token = client.get_subscription(&ids.get(0).unwrap()).token;
+
+    let balances = client.merchant_balances(&query, &token);
+    assert_eq!(balances.len(), 3);
+    // Merchant 0 has three subscriptions, merchant 1 has two; 1000 per charge.
+    assert_eq!(balances.get(merchants[0].clone()), Some(3000i128));
+    assert_eq!(balances.get(merchants[1].clone()), Some(2000i128));
+    assert_eq!(balances.get(idle_merchant), Some(0));
+}
+
+#[test]
+fn test_reconcile_merchant_after_charges_and_withdrawal() {
+    let env = Env::default();
+    let (client, _admin, _subscribers, merchants, ids) = setup_multi_actor(&env);
+    let merchant = merchants[0].clone();
+    assert_eq!(client.reconcile_merchant(&merchant), (0, 0));
+
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    client.batch_charge(&ids);
+    client.withdraw_merchant_funds(&merchant, &1200i128);
+
+    // Three 1000 charges, one 1200 withdrawal.
+    assert_eq!(client.reconcile_merchant(&merchant), (1800, 1800));
+    let token = client.get_subscription(&ids.get(0).unwrap()).token;
+    assert_eq!(
+        client.reconcile_merchant_token(&merchant, &token),
+        (1800, 1800)
+    );
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 1800);
+}
+
+#[test]
+fn test_merchant_withdrawal_event_counts_charges_since_last_withdrawal() {
+    let env = Env::default();
+    let (client, _admin, _subscribers, merchants, ids) = setup_multi_actor(&env);
+    let merchant = merchants[0].clone();
+
+    // Merchant 0 owns three of the subscriptions.
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    client.batch_charge(&ids);
+    client.withdraw_merchant_funds(&merchant, &1000i128);
+    let event: MerchantWithdrawalEvent = last_event_data(&env);
+    assert_eq!(event.amount, 1000);
+    assert_eq!(event.charge_count, 3);
+
+    // The counter restarts after each withdrawal.
+    client.withdraw_merchant_funds(&merchant, &1000i128);
+    let event: MerchantWithdrawalEvent = last_event_data(&env);
+    assert_eq!(event.charge_count, 0);
+}
+
+#[test]
+fn test_withdraw_merchant_funds_exceeds_balance_fails() {
+    let env = Env::default();
+    let (client, _admin, id0, _) = setup_batch_env(&env);
+    client.charge_subscription(&id0, &None);
+    let sub = client.get_subscription(&id0);
+
+    let res = client.try_withdraw_merchant_token_funds(&sub.merchant, &sub.token, &1001i128);
+    assert_eq!(res, Err(Ok(Error::InsufficientBalance)));
+}
+
+// =============================================================================
+// Platform fees
+// =============================================================================
+
+#[test]
+fn test_platform_fee_accrues_and_fee_collector_sweeps() {
+    let env = Env::default();
+    let (client, admin, id0, id1) = setup_batch_env(&env);
+    let fee_collector = Address::generate(&env);
+    client.set_platform_fee(&admin, &250u32, &fee_collector); // 2.5%
+
+    client.charge_subscription(&id0, &None);
+    client.charge_subscription(&id1, &None);
+
+    let sub = client.get_subscription(&id0);
+    // 1000 per charge: 25 fee, 975 to the merchant.
+    assert_eq!(client.get_fee_balance(&sub.token), 50);
+    assert_eq!(client.get_merchant_balance(&sub.merchant, &sub.token), 1950);
+
+    let swept = client.withdraw_fees(&fee_collector, &sub.token);
+    assert_eq!(swept, 50);
+    assert_eq!(client.get_fee_balance(&sub.token), 0);
+    assert_eq!(
+        TokenClient::new(&env, &sub.token).balance(&fee_collector),
+        50
+    );
+
+    // Nothing left to sweep.
+    assert_eq!(client.withdraw_fees(&fee_collector, &sub.token), 0);
+}
+
+#[test]
+fn test_sweep_dust_moves_tiny_merchant_balance_to_fee_collector() {
+    let env = Env::default();
+    let (client, admin, id0, _) = setup_batch_env(&env);
+    let fee_collector = Address::generate(&env);
+    client.set_platform_fee(&admin, &250u32, &fee_collector);
+    client.charge_subscription(&id0, &None);
+    let sub = client.get_subscription(&id0);
+    // 975 credited; leave 5 units of dust behind.
+    client.withdraw_merchant_token_funds(&sub.merchant, &sub.token, &970);
+
+    let merchants = Vec::from_array(&env, [sub.merchant.clone()]);
+
+    // Sweeping is off until a limit is set.
+    assert_eq!(client.sweep_dust(&admin, &sub.token, &merchants), 0);
+    client.set_dust_limit(&admin, &10);
+    assert_eq!(client.sweep_dust(&admin, &sub.token, &merchants), 5);
+
+    let event: DustSweptEvent = last_event_data(&env);
+    assert_eq!(event.merchant, sub.merchant);
+    assert_eq!(event.amount, 5);
+    assert_eq!(client.get_merchant_balance(&sub.merchant, &sub.token), 0);
+    assert_eq!(
+        TokenClient::new(&env, &sub.token).balance(&fee_collector),
+        5
+    );
+    let (expected, stored) = client.reconcile_merchant_token(&sub.merchant, &sub.token);
+    assert_eq!(expected, stored);
+    assert_eq!(
+        client.try_sweep_dust(&sub.merchant, &sub.token, &merchants),
+        Err(Ok(Error::Unauthorized))
+    );
+
+    let mut too_many = Vec::new(&env);
+    for _ in 0..101 {
+        too_many.push_back(sub.merchant.clone());
+    }
+    assert_eq!(
+        client.try_sweep_dust(&admin, &sub.token, &too_many),
+        Err(Ok(Error::InvalidAmount))
+    );
+}
+
+#[test]
+fn test_withdraw_fees_admin_allowed_stranger_rejected() {
+    let env = Env::default();
+    let (client, admin, id0, _) = setup_batch_env(&env);
+    let fee_collector = Address::generate(&env);
+    client.set_platform_fee(&admin, &1000u32, &fee_collector); // 10%
+    client.charge_subscription(&id0, &None);
+    let token = client.get_subscription(&id0).token;
+
+    let stranger = Address::generate(&env);
+    let res = client.try_withdraw_fees(&stranger, &token);
+    assert_eq!(res, Err(Ok(Error::Unauthorized)));
+
+    // The admin may trigger the sweep; funds still go to the fee collector.
+    assert_eq!(client.withdraw_fees(&admin, &token), 100);
+    assert_eq!(TokenClient::new(&env, &token).balance(&fee_collector), 100);
+}
+
+#[test]
+fn test_set_platform_fee_rejects_non_admin_and_over_100_percent() {
+    let env = Env::default();
+    let (client, admin, _, _) = setup_batch_env(&env);
+    let fee_collector = Address::generate(&env);
+    let non_admin = Address::generate(&env);
+
+    let res = client.try_set_platform_fee(&non_admin, &100u32, &fee_collector);
+    assert_eq!(res, Err(Ok(Error::Unauthorized)));
+    let res = client.try_set_platform_fee(&admin, &10_001u32, &fee_collector);
+    assert_eq!(res, Err(Ok(Error::InvalidAmount)));
+
+    // Unconfigured: no fee and the admin collects.
+    assert_eq!(client.get_platform_fee(), 0);
+    assert_eq!(client.get_fee_collector(), admin);
+}
+
+#[test]
+fn test_set_fee_collector_redirects_sweeps() {
+    let env = Env::default();
+    let (client, admin, id0, _) = setup_batch_env(&env);
+    client.set_platform_fee(&admin, &1000u32, &admin); // 10%
+    client.charge_subscription(&id0, &None);
+    let token = client.get_subscription(&id0).token;
+
+    let treasury = Address::generate(&env);
+    assert_eq!(
+        client.try_set_fee_collector(&treasury, &treasury),
+        Err(Ok(Error::Unauthorized))
+    );
+    client.set_fee_collector(&admin, &treasury);
+    assert_eq!(client.get_fee_collector(), treasury);
+    assert_eq!(client.get_platform_fee(), 1000);
+
+    assert_eq!(client.withdraw_fees(&treasury, &token), 100);
+    assert_eq!(TokenClient::new(&env, &token).balance(&treasury), 100);
+    assert_eq!(TokenClient::new(&env, &token).balance(&admin), 0);
+}
+
+// =============================================================================
+// Auto-pause on low balance
+// =============================================================================
+
+#[test]
+fn test_charge_auto_pauses_when_balance_cannot_cover_next_charge() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    // amount is 10 USDC; 15 USDC covers one charge and leaves 5.
+    client.deposit_funds(&id, &subscriber, &15_000000i128, &None);
+    env.ledger().set_timestamp(INTERVAL);
+
+    client.charge_subscription(&id, &None);
+
+    // Events are inspected before any further invocation replaces them.
+    let events = env.events().all();
+    let n = events.len();
+    let charged = events.get(n - 2).unwrap();
+    let paused = events.get(n - 1).unwrap();
+    assert_eq!(charged.1, (symbol_short!("charged"), id).into_val(&env));
+    let charged_data = SubscriptionChargedEvent::try_from_val(&env, &charged.2).unwrap();
+    assert_eq!(charged_data.merchant, merchant);
+    assert_eq!(charged_data.amount, 10_000000i128);
+    assert_eq!(paused.1, (symbol_short!("paused"), id).into_val(&env));
+    let paused_data: SubscriptionPausedEvent = last_event_data(&env);
+    assert_eq!(paused_data.subscription_id, id);
+    assert_eq!(paused_data.authorizer, client.address);
+
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.status, SubscriptionStatus::Paused);
+    assert_eq!(sub.prepaid_balance, 5_000000i128);
+}
+
+#[test]
+fn test_deposit_auto_resumes_auto_paused_subscription() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &15_000000i128, &None);
+    env.ledger().set_timestamp(INTERVAL);
+    client.charge_subscription(&id, &None);
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Paused
+    );
+
+    // Topping up to 9 USDC is still short of one charge: stays paused.
+    client.deposit_funds(&id, &subscriber, &4_000000i128, &None);
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Paused
+    );
+
+    client.deposit_funds(&id, &subscriber, &1_000000i128, &None);
+    let resumed: SubscriptionResumedEvent = last_event_data(&env);
+    assert_eq!(resumed.subscription_id, id);
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Active
+    );
+
+    // Billing continues on the next interval.
+    env.ledger().set_timestamp(2 * INTERVAL);
+    client.charge_subscription(&id, &None);
+}
+
+#[test]
+fn test_deposit_does_not_resume_manually_paused_subscription() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.pause_subscription(&id, &subscriber);
+
+    client.deposit_funds(&id, &subscriber, &50_000000i128, &None);
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Paused
+    );
+}
+
+#[test]
+fn test_manual_pause_of_auto_paused_subscription_blocks_auto_resume() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &15_000000i128, &None);
+    env.ledger().set_timestamp(INTERVAL);
+    client.charge_subscription(&id, &None);
+
+    // Subscriber explicitly pauses; the pause is now theirs to lift.
+    client.pause_subscription(&id, &subscriber);
+    client.deposit_funds(&id, &subscriber, &20_000000i128, &None);
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Paused
+    );
+}
+
+// =============================================================================
+// Cancel refunds and final settlement
+// =============================================================================
+
+#[test]
+fn test_cancel_refunds_remaining_balance_to_subscriber() {
+    let (env, client, token, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let tokens = TokenClient::new(&env, &token);
+    let before = tokens.balance(&subscriber);
+    client.deposit_funds(&id, &subscriber, &25_000000i128, &None);
+
+    client.cancel_subscription(&id, &subscriber);
+    let event: SubscriptionCancelledEvent = last_event_data(&env);
+    assert_eq!(event.refund_amount, 25_000000i128);
+    assert_eq!(event.refund_to, subscriber);
+    assert_eq!(tokens.balance(&subscriber), before);
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 0);
+}
+
+#[test]
+fn test_cancel_subscription_to_sends_refund_to_override() {
+    let (env, client, token, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let tokens = TokenClient::new(&env, &token);
+    client.deposit_funds(&id, &subscriber, &25_000000i128, &None);
+    let subscriber_before = tokens.balance(&subscriber);
+    let successor = Address::generate(&env);
+
+    // Only the subscriber can redirect the refund.
+    assert_eq!(
+        client.try_cancel_subscription_to(&id, &merchant, &merchant),
+        Err(Ok(Error::Unauthorized))
+    );
+    client.cancel_subscription_to(&id, &subscriber, &successor);
+    let event: SubscriptionCancelledEvent = last_event_data(&env);
+    assert_eq!(event.refund_amount, 25_000000i128);
+    assert_eq!(event.refund_to, successor);
+    assert_eq!(tokens.balance(&successor), 25_000000i128);
+    assert_eq!(tokens.balance(&subscriber), subscriber_before);
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Cancelled
+    );
+}
+
+#[test]
+fn test_cancel_fee_deducted_from_refund() {
+    let (env, client, token, admin) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let tokens = TokenClient::new(&env, &token);
+    client.deposit_funds(&id, &subscriber, &20_000000i128, &None);
+    let before = tokens.balance(&subscriber);
+    client.set_cancel_fee(&admin, &500u32); // 5%
+    assert_eq!(client.get_cancel_fee(), 500);
+
+    client.cancel_subscription(&id, &subscriber);
+    let event: SubscriptionCancelledEvent = last_event_data(&env);
+    assert_eq!(event.refund_amount, 19_000000i128);
+    assert_eq!(tokens.balance(&subscriber), before + 19_000000i128);
+    assert_eq!(client.get_fee_balance(&token), 1_000000i128);
+    assert_eq!(client.total_prepaid(&token), 0);
+
+    assert_eq!(
+        client.try_set_cancel_fee(&admin, &10_001u32),
+        Err(Ok(Error::InvalidAmount))
+    );
+    assert_eq!(
+        client.try_set_cancel_fee(&subscriber, &100u32),
+        Err(Ok(Error::Unauthorized))
+    );
+}
+
+#[test]
+fn test_cancel_no_refund_sweeps_balance_to_merchant() {
+    let (env, client, token, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let tokens = TokenClient::new(&env, &token);
+    client.deposit_funds(&id, &subscriber, &25_000000i128, &None);
+    let subscriber_before = tokens.balance(&subscriber);
+    let merchant_before = client.get_merchant_balance(&merchant, &token);
+
+    assert_eq!(
+        client.try_cancel_no_refund(&id, &subscriber),
+        Err(Ok(Error::Unauthorized))
+    );
+    client.cancel_no_refund(&id, &merchant);
+    let event: SubscriptionCancelledEvent = last_event_data(&env);
+    assert_eq!(event.authorizer, merchant);
+    assert_eq!(event.refund_amount, 0);
+    assert_eq!(event.swept_to_merchant, 25_000000i128);
+
+    assert_eq!(
+        client.get_merchant_balance(&merchant, &token),
+        merchant_before + 25_000000i128
+    );
+    assert_eq!(tokens.balance(&subscriber), subscriber_before);
+    assert_eq!(client.total_prepaid(&token), 0);
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.status, SubscriptionStatus::Cancelled);
+    assert_eq!(sub.prepaid_balance, 0);
+}
+
+#[test]
+fn test_cancel_with_settlement_always_charges_then_refunds_rest() {
+    let (env, client, token, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let tokens = TokenClient::new(&env, &token);
+    let before = tokens.balance(&subscriber);
+    client.deposit_funds(&id, &subscriber, &25_000000i128, &None);
+    client.set_cancel_settlement(&id, &merchant, &CancelSettlement::Always);
+    assert_eq!(client.get_cancel_settlement(&id), CancelSettlement::Always);
+
+    // Mid-interval: not due, but Always settles anyway.
+    env.ledger().set_timestamp(INTERVAL / 2);
+    client.cancel_subscription(&id, &subscriber);
+
+    let events = env.events().all();
+    let charged =
+        SubscriptionChargedEvent::try_from_val(&env, &events.get(events.len() - 3).unwrap().2)
+            .unwrap();
+    assert_eq!(charged.amount, 10_000000i128);
+    let cancelled: SubscriptionCancelledEvent = last_event_data(&env);
+    assert_eq!(cancelled.refund_amount, 15_000000i128);
+
+    assert_eq!(
+        client.get_merchant_balance(&merchant, &token),
+        10_000000i128
+    );
+    assert_eq!(tokens.balance(&subscriber), before - 10_000000i128);
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Cancelled
+    );
+}
+
+#[test]
+fn test_cancel_with_prorated_settlement_charges_time_used() {
+    let (env, client, token, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let tokens = TokenClient::new(&env, &token);
+    client.deposit_funds(&id, &subscriber, &25_000000i128, &None);
+    let before = tokens.balance(&subscriber);
+    client.set_cancel_settlement(&id, &merchant, &CancelSettlement::Prorated);
+
+    let start = client.get_subscription(&id).last_payment_timestamp;
+    env.ledger().set_timestamp(start + INTERVAL / 2);
+    client.cancel_subscription(&id, &subscriber);
+    let cancelled: SubscriptionCancelledEvent = last_event_data(&env);
+    assert_eq!(cancelled.refund_amount, 20_000000i128);
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.total_charged, 5_000000i128);
+    assert_eq!(sub.status, SubscriptionStatus::Cancelled);
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 5_000000i128);
+    assert_eq!(tokens.balance(&subscriber), before + 20_000000i128);
+
+    // The prorated charge never exceeds the prepaid balance.
+    let (short, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&short, &subscriber, &3_000000i128, &None);
+    client.set_cancel_settlement(&short, &merchant, &CancelSettlement::Prorated);
+    let start = client.get_subscription(&short).last_payment_timestamp;
+    env.ledger().set_timestamp(start + INTERVAL / 2);
+    client.cancel_subscription(&short, &subscriber);
+    let cancelled: SubscriptionCancelledEvent = last_event_data(&env);
+    assert_eq!(cancelled.refund_amount, 0);
+    assert_eq!(client.get_subscription(&short).total_charged, 3_000000i128);
+}
+
+#[test]
+fn test_preview_cancel_matches_cancel_refund() {
+    let (env, client, _, admin) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &25_000000i128, &None);
+    assert_eq!(client.preview_cancel(&id), 25_000000);
+
+    client.set_cancel_settlement(&id, &merchant, &CancelSettlement::Prorated);
+    client.set_cancel_fee(&admin, &1_000u32); // 10%
+    let start = client.get_subscription(&id).last_payment_timestamp;
+    env.ledger().set_timestamp(start + INTERVAL / 2);
+
+    // 5 USDC settlement for half the period, then 10% of the remaining 20.
+    let preview = client.preview_cancel(&id);
+    assert_eq!(preview, 18_000000);
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 25_000000);
+
+    client.cancel_subscription(&id, &subscriber);
+    let cancelled: SubscriptionCancelledEvent = last_event_data(&env);
+    assert_eq!(cancelled.refund_amount, preview);
+    assert_eq!(client.preview_cancel(&id), 0);
+}
+#[test]
+fn test_cancel_with_settlement_if_due_only_charges_when_due() {
+    let (env, client, token, _) = setup_test_env();
+    let (id_early, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id_early, &subscriber, &25_000000i128, &None);
+    client.set_cancel_settlement(&id_early, &merchant, &CancelSettlement::IfDue);
+
+    env.ledger().set_timestamp(INTERVAL - 1);
+    client.cancel_subscription(&id_early, &subscriber);
+    let cancelled: SubscriptionCancelledEvent = last_event_data(&env);
+    assert_eq!(cancelled.refund_amount, 25_000000i128);
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 0);
+
+    let (id_due, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id_due, &subscriber, &25_000000i128, &None);
+    client.set_cancel_settlement(&id_due, &merchant, &CancelSettlement::IfDue);
+    env.ledger().set_timestamp(2 * INTERVAL);
+    client.cancel_subscription(&id_due, &subscriber);
+    let cancelled: SubscriptionCancelledEvent = last_event_data(&env);
+    assert_eq!(cancelled.refund_amount, 15_000000i128);
+    assert_eq!(
+        client.get_merchant_balance(&merchant, &token),
+        10_000000i128
+    );
+}
+
+#[test]
+fn test_set_cancel_settlement_rejects_non_merchant() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let res = client.try_set_cancel_settlement(&id, &subscriber, &CancelSettlement::Always);
+    assert_eq!(res, Err(Ok(Error::Unauthorized)));
+    assert_eq!(client.get_cancel_settlement(&id), CancelSettlement::Off);
+}
+
+// =============================================================================
+// Spending cap
+// =============================================================================
+
+fn setup_spending_cap(
+    env: &Env,
+    max_total_charge: i128,
+) -> (SubscriptionVaultClient<'static>, u32, Address) {
+    env.mock_all_auths();
+    env.ledger().set_timestamp(T0);
+    let contract_id = env.register(SubscriptionVault, ());
+    let client = SubscriptionVaultClient::new(env, &contract_id);
+    let token = create_token(env);
+    client.init(&token, &Address::generate(env), &1_000000i128, &false);
+    let subscriber = Address::generate(env);
+    let merchant = Address::generate(env);
+    mint(env, &token, &subscriber);
+    let id = client.create_subscription(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &INTERVAL,
+        &false,
+        &None,
+        &0,
+        &max_total_charge,
+    );
+    client.deposit_funds(&id, &subscriber, &10_000000i128, &None);
+    (client, id, merchant)
+}
+
+#[test]
+fn test_spending_cap_blocks_charge_that_would_cross_it() {
+    let env = Env::default();
+    let (client, id, _) = setup_spending_cap(&env, 3000i128);
+
+    for i in 1..=3u64 {
+        env.ledger().set_timestamp(T0 + i * INTERVAL);
+        client.charge_subscription(&id, &None);
+    }
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.total_charged, 3000i128);
+    let balance = sub.prepaid_balance;
+
+    env.ledger().set_timestamp(T0 + 4 * INTERVAL);
+    assert_eq!(
+        client.can_charge(&id),
+        (false, Error::SpendingCapReached.to_code())
+    );
+    let res = client.try_charge_subscription(&id, &None);
+    assert_eq!(res, Err(Ok(Error::SpendingCapReached)));
+
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.prepaid_balance, balance);
+    assert_eq!(sub.total_charged, 3000i128);
+}
+
+#[test]
+fn test_spending_cap_counts_one_off_charges() {
+    let env = Env::default();
+    let (client, id, merchant) = setup_spending_cap(&env, 1500i128);
+
+    client.charge_one_off(&id, &merchant, &600i128);
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    // 600 + 1000 would exceed 1500.
+    let res = client.try_charge_subscription(&id, &None);
+    assert_eq!(res, Err(Ok(Error::SpendingCapReached)));
+    let res = client.try_charge_one_off(&id, &merchant, &901i128);
+    assert_eq!(res, Err(Ok(Error::SpendingCapReached)));
+    client.charge_one_off(&id, &merchant, &900i128);
+    assert_eq!(client.get_subscription(&id).total_charged, 1500i128);
+}
+
+#[test]
+fn test_spending_cap_zero_is_unlimited() {
+    let env = Env::default();
+    let (client, id, _) = setup_spending_cap(&env, 0);
+    for i in 1..=5u64 {
+        env.ledger().set_timestamp(T0 + i * INTERVAL);
+        client.charge_subscription(&id, &None);
+    }
+    assert_eq!(client.get_subscription(&id).total_charged, 5000i128);
+}
+
+// =============================================================================
+// Low balance warning
+// =============================================================================
+
+#[test]
+fn test_low_balance_warning_fires_once_when_crossing_threshold() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let subscriber = client.get_subscription(&id).subscriber;
+    // Balance is 10_000_000 and each charge takes 1000; warn below 9_997_500.
+    client.set_low_balance_threshold(&id, &subscriber, &9_997_500i128);
+
+    let mut warnings = 0;
+    for i in 1..=5u64 {
+        env.ledger().set_timestamp(T0 + i * INTERVAL);
+        client.charge_subscription(&id, &None);
+        let events = env.events().all();
+        for e in events.iter() {
+            if e.1 == (symbol_short!("low_bal"), id).into_val(&env) {
+                let data = LowBalanceEvent::try_from_val(&env, &e.2).unwrap();
+                assert_eq!(i, 3); // 9_997_000 after the third charge
+                assert_eq!(data.prepaid_balance, 9_997_000i128);
+                assert_eq!(data.threshold, 9_997_500i128);
+                warnings += 1;
+            }
+        }
+    }
+    assert_eq!(warnings, 1);
+}
+
+#[test]
+fn test_low_balance_warning_rearmed_by_deposit() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let subscriber = client.get_subscription(&id).subscriber;
+    client.set_low_balance_threshold(&id, &subscriber, &10_000000i128);
+
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    client.charge_subscription(&id, &None);
+    let event: LowBalanceEvent = last_event_data(&env);
+    assert_eq!(event.subscription_id, id);
+
+    client.deposit_funds(&id, &subscriber, &1_000000i128, &None);
+    env.ledger().set_timestamp(T0 + 2 * INTERVAL);
+    client.charge_subscription(&id, &None);
+    // Balance is back above the threshold after the charge: no warning.
+    let event: SubscriptionChargedEvent = last_event_data(&env);
+    assert_eq!(event.subscription_id, id);
+
+    env.ledger().set_timestamp(T0 + 3 * INTERVAL);
+    client.set_low_balance_threshold(&id, &subscriber, &11_000000i128);
+    client.charge_subscription(&id, &None);
+    let event: LowBalanceEvent = last_event_data(&env);
+    assert_eq!(event.threshold, 11_000000i128);
+}
+
+#[test]
+fn test_balance_restored_event_after_low_balance() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let subscriber = client.get_subscription(&id).subscriber;
+    client.set_low_balance_threshold(&id, &subscriber, &12_000000i128);
+
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    client.charge_subscription(&id, &None);
+    let low: LowBalanceEvent = last_event_data(&env);
+    assert_eq!(low.prepaid_balance, 9_999_000i128);
+
+    // Still below the threshold: no restored event.
+    client.deposit_funds(&id, &subscriber, &1_000000i128, &None);
+    let event: FundsDepositedEvent = last_event_data(&env);
+    assert_eq!(event.new_balance, 10_999_000i128);
+
+    client.deposit_funds(&id, &subscriber, &2_000000i128, &None);
+    let event: BalanceRestoredEvent = last_event_data(&env);
+    assert_eq!(event.subscription_id, id);
+    assert_eq!(event.subscriber, subscriber);
+    assert_eq!(event.prepaid_balance, 12_999_000i128);
+    assert_eq!(event.threshold, 12_000000i128);
+
+    // Only once per warning.
+    client.deposit_funds(&id, &subscriber, &1_000000i128, &None);
+    let event: FundsDepositedEvent = last_event_data(&env);
+    assert_eq!(event.new_balance, 13_999_000i128);
+}
+
+#[test]
+fn test_set_low_balance_threshold_rejects_non_subscriber() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let stranger = Address::generate(&env);
+    let res = client.try_set_low_balance_threshold(&id, &stranger, &1i128);
+    assert_eq!(res, Err(Ok(Error::Unauthorized)));
+}
+
+#[test]
+fn test_receipt_threshold_flags_only_large_charges() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.receipt_threshold, 0);
+    client.set_receipt_threshold(&id, &sub.subscriber, &900i128);
+    // 25% off the first charge: 750 is below the threshold.
+    client.apply_coupon(&id, &sub.merchant, &2_500u32, &(T0 + INTERVAL + 1));
+
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    client.charge_subscription(&id, &None);
+    let event: SubscriptionChargedEvent = last_event_data(&env);
+    assert_eq!(event.amount, 750);
+    assert!(!event.receipt);
+
+    env.ledger().set_timestamp(T0 + 2 * INTERVAL);
+    client.charge_subscription(&id, &None);
+    let event: SubscriptionChargedEvent = last_event_data(&env);
+    assert_eq!(event.amount, 1000);
+    assert!(event.receipt);
+
+    // Exactly at the threshold counts.
+    client.set_receipt_threshold(&id, &sub.subscriber, &1000i128);
+    env.ledger().set_timestamp(T0 + 3 * INTERVAL);
+    client.charge_subscription(&id, &None);
+    let event: SubscriptionChargedEvent = last_event_data(&env);
+    assert!(event.receipt);
+
+    assert_eq!(
+        client.try_set_receipt_threshold(&id, &sub.merchant, &1i128),
+        Err(Ok(Error::Unauthorized))
+    );
+}
+
+// =============================================================================
+// SubscriptionParams builder
+// =============================================================================
+
+#[test]
+fn test_subscription_params_rejects_invalid_values_at_construction() {
+    let env = Env::default();
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+
+    assert_eq!(
+        SubscriptionParams::new(subscriber.clone(), merchant.clone(), 0, INTERVAL).err(),
+        Some(Error::InvalidAmount)
+    );
+    assert_eq!(
+        SubscriptionParams::new(subscriber.clone(), merchant.clone(), -5, INTERVAL).err(),
+        Some(Error::InvalidAmount)
+    );
+    let params = SubscriptionParams::new(subscriber, merchant, 1000, INTERVAL).unwrap();
+    assert_eq!(
+        params.clone().with_min_deposit(-1).err(),
+        Some(Error::InvalidAmount)
+    );
+    assert_eq!(
+        params.with_max_total_charge(-1).err(),
+        Some(Error::InvalidAmount)
+    );
+}
+
+#[test]
+fn test_create_subscription_full_returns_stored_record() {
+    let (env, client, token, _) = setup_test_env();
+    env.ledger().set_timestamp(5_000);
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+
+    let (id, sub) = client.create_subscription_full(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &INTERVAL,
+        &true,
+        &None,
+        &2_000000i128,
+        &50_000i128,
+    );
+    assert_eq!(
+        sub,
+        Subscription {
+            subscriber,
+            merchant,
+            token,
+            amount: 1000,
+            interval_seconds: INTERVAL,
+            last_payment_timestamp: 5_000,
+            status: SubscriptionStatus::Active,
+            prepaid_balance: 0,
+            usage_enabled: true,
+            min_deposit: 2_000000,
+            max_prepaid: 0,
+            max_total_charge: 50_000,
+            total_charged: 0,
+            low_balance_threshold: 0,
+            receipt_threshold: 0,
+            discount_bps: 0,
+            discount_expiry: 0,
+            grace_seconds: 0,
+            failed_charge_count: 0,
+            max_cycles: 0,
+            cycles_charged: 0,
+            min_charge: 0,
+            accrued_usage: 0,
+            pause_count: 0,
+            resume_count: 0,
+            credits: 0,
+            start_timestamp: 5_000,
+            created_at: 5_000,
+            external_ref: Bytes::new(&env),
+            metadata: String::from_str(&env, ""),
+        }
+    );
+    assert_eq!(client.get_subscription(&id), sub);
+}
+
+#[test]
+fn test_create_subscription_from_params() {
+    let (env, client, token, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let other_token = create_token(&env);
+
+    let params = SubscriptionParams::new(subscriber.clone(), merchant.clone(), 1000, INTERVAL)
+        .unwrap()
+        .with_usage_enabled(true)
+        .with_token(other_token.clone())
+        .with_min_deposit(5_000000)
+        .unwrap()
+        .with_max_total_charge(50_000)
+        .unwrap();
+    let id = client.create_subscription_from_params(&params);
+
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.subscriber, subscriber);
+    assert_eq!(sub.merchant, merchant);
+    assert_eq!(sub.amount, 1000);
+    assert_eq!(sub.interval_seconds, INTERVAL);
+    assert!(sub.usage_enabled);
+    assert_eq!(sub.token, other_token);
+    assert_ne!(sub.token, token);
+    assert_eq!(sub.min_deposit, 5_000000);
+    assert_eq!(sub.max_total_charge, 50_000);
+}
+
+#[test]
+fn test_create_subscription_from_params_revalidates_on_entry() {
+    let (env, client, _, _) = setup_test_env();
+    // Encoded directly, bypassing the builder, as a non-Rust caller could.
+    let params = SubscriptionParams {
+        subscriber: Address::generate(&env),
+        merchant: Address::generate(&env),
+        amount: 0,
+        interval_seconds: INTERVAL,
+        usage_enabled: false,
+        token: None,
+        min_deposit: 0,
+        max_total_charge: 0,
+        grace_seconds: None,
+        start_timestamp: None,
+        external_ref: None,
+        metadata: None,
+        release_months: 0,
+        max_cycles: 0,
+        min_charge: 0,
+        initial_deposit: 0,
+        setup_fee: 0,
+        usage_rate: 0,
+        usage_rounding: UsageRounding::Floor,
+    };
+    let res = client.try_create_subscription_from_params(&params);
+    assert_eq!(res, Err(Ok(Error::InvalidAmount)));
+}
+
+#[test]
+fn test_create_subscription_rejects_zero_interval() {
+    let (env, client, _, _) = setup_test_env();
+    let res = client.try_create_subscription(
+        &Address::generate(&env),
+        &Address::generate(&env),
+        &1000i128,
+        &0u64,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    assert_eq!(res, Err(Ok(Error::InvalidInterval)));
+    assert_eq!(Error::InvalidInterval.to_code(), 419);
+    assert!(matches!(
+        SubscriptionParams::new(Address::generate(&env), Address::generate(&env), 1000, 0),
+        Err(Error::InvalidInterval)
+    ));
+}
+
+#[test]
+#[should_panic(expected = "Error(Contract, #422)")]
+fn test_create_subscription_rejects_self_subscription() {
+    let (env, client, _, _) = setup_test_env();
+    let account = Address::generate(&env);
+    client.create_subscription(
+        &account, &account, &1000i128, &INTERVAL, &false, &None, &0, &0,
+    );
+}
+
+#[test]
+fn test_self_subscription_rejected_on_every_creation_path() {
+    let (env, client, _, _) = setup_test_env();
+    let account = Address::generate(&env);
+    assert!(matches!(
+        SubscriptionParams::new(account.clone(), account.clone(), 1000, INTERVAL),
+        Err(Error::SelfSubscription)
+    ));
+    assert_eq!(
+        client.try_create_subscription_days(
+            &account, &account, &1000i128, &30u32, &false, &None, &0, &0,
+        ),
+        Err(Ok(Error::SelfSubscription))
+    );
+    // Distinct addresses still succeed.
+    let merchant = Address::generate(&env);
+    let id = client.create_subscription(
+        &account, &merchant, &1000i128, &INTERVAL, &false, &None, &0, &0,
+    );
+    assert_eq!(client.get_subscription(&id).merchant, merchant);
+}
+
+#[test]
+fn test_create_subscription_days_matches_seconds() {
+    let (env, client, _, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let days = client.create_subscription_days(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &30u32,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    let seconds = client.create_subscription(
+        &subscriber,
+        &merchant,
+        &1000i128,
+        &(30 * 86_400u64),
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    let (by_days, by_seconds) = (
+        client.get_subscription(&days),
+        client.get_subscription(&seconds),
+    );
+    assert_eq!(by_days.interval_seconds, 2_592_000);
+    assert_eq!(by_days, by_seconds);
+    assert_eq!(
+        client.try_create_subscription_days(
+            &subscriber,
+            &merchant,
+            &1000i128,
+            &0u32,
+            &false,
+            &None,
+            &0,
+            &0,
+        ),
+        Err(Ok(Error::InvalidInterval))
+    );
+}
+
+// =============================================================================
+// Vested monthly release
+// =============================================================================
+
+#[test]
+fn test_annual_charge_vests_to_merchant_monthly() {
+    let (env, client, token, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    mint(&env, &token, &subscriber);
+    let annual = 12_000000i128;
+    let params = SubscriptionParams::new(subscriber.clone(), merchant.clone(), annual, 31_536_000)
+        .unwrap()
+        .with_release_months(12);
+    let id = client.create_subscription_from_params(&params);
+    client.deposit_funds(&id, &subscriber, &annual, &None);
+
+    let charged_at = client.next_charge_timestamp(&id);
+    env.ledger().set_timestamp(charged_at);
+    client.charge_subscription(&id, &None);
+    assert_eq!(client.get_merchant_balance(&merchant, &token), annual);
+    assert_eq!(client.get_merchant_withdrawable(&merchant, &token), 0);
+    assert_eq!(
+        client.try_withdraw_merchant_token_funds(&merchant, &token, &1),
+        Err(Ok(Error::InsufficientBalance))
+    );
+
+    // One twelfth vests per elapsed month.
+    env.ledger().set_timestamp(charged_at + 2_592_000);
+    assert_eq!(
+        client.get_merchant_withdrawable(&merchant, &token),
+        1_000000
+    );
+    client.withdraw_merchant_token_funds(&merchant, &token, &1_000000);
+    assert_eq!(
+        client.try_withdraw_merchant_token_funds(&merchant, &token, &1),
+        Err(Ok(Error::InsufficientBalance))
+    );
+
+    env.ledger().set_timestamp(charged_at + 3 * 2_592_000);
+    assert_eq!(
+        client.get_merchant_withdrawable(&merchant, &token),
+        2_000000
+    );
+
+    // Fully vested after twelve months.
+    env.ledger().set_timestamp(charged_at + 12 * 2_592_000);
+    assert_eq!(
+        client.get_merchant_withdrawable(&merchant, &token),
+        annual - 1_000000
+    );
+    client.withdraw_merchant_token_funds(&merchant, &token, &(annual - 1_000000));
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 0);
+}
+
+fn release_tranche_count(env: &Env, client: &SubscriptionVaultClient, merchant: &Address) -> u32 {
+    let token = client.get_subscription(&0).token;
+    env.as_contract(&client.address, || {
+        env.storage()
+            .instance()
+            .get::<_, Vec<crate::types::ReleaseTranche>>(&(
+                symbol_short!("mrelease"),
+                merchant.clone(),
+                token,
+            ))
+            .map_or(0, |t| t.len())
+    })
+}
+
+#[test]
+fn test_release_tranches_merge_and_stay_bounded() {
+    let (env, client, token, _) = setup_test_env();
+    let merchant = Address::generate(&env);
+    let mut ids = Vec::new(&env);
+    for _ in 0..2 {
+        let subscriber = Address::generate(&env);
+        mint(&env, &token, &subscriber);
+        let params = SubscriptionParams::new(subscriber.clone(), merchant.clone(), 1_000000, 60)
+            .unwrap()
+            .with_release_months(12);
+        let id = client.create_subscription_from_params(&params);
+        client.deposit_funds(&id, &subscriber, &100_000000i128, &None);
+        ids.push_back(id);
+    }
+
+    // Charged at the same time on the same schedule: one tranche.
+    env.ledger()
+        .set_timestamp(client.next_charge_timestamp(&ids.get(0).unwrap()));
+    client.batch_charge(&ids);
+    assert_eq!(release_tranche_count(&env, &client, &merchant), 1);
+
+    for _ in 0..40 {
+        env.ledger()
+            .set_timestamp(client.next_charge_timestamp(&ids.get(0).unwrap()));
+        client.charge_subscription(&ids.get(0).unwrap(), &None);
+    }
+    assert_eq!(
+        release_tranche_count(&env, &client, &merchant),
+        crate::merchant::MAX_RELEASE_TRANCHES
+    );
+    // Folding loses nothing and vests nothing early.
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 42_000000);
+    assert_eq!(client.get_merchant_withdrawable(&merchant, &token), 0);
+
+    // Fully vested tranches are dropped on the next credit.
+    env.ledger()
+        .set_timestamp(env.ledger().timestamp() + 12 * 2_592_000);
+    client.charge_subscription(&ids.get(1).unwrap(), &None);
+    assert_eq!(release_tranche_count(&env, &client, &merchant), 1);
+    assert_eq!(
+        client.get_merchant_withdrawable(&merchant, &token),
+        42_000000
+    );
+}
+
+// =============================================================================
+// Coupons
+// =============================================================================
+
+#[test]
+fn test_coupon_discounts_charges_until_expiry() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let merchant = client.get_subscription(&id).merchant;
+    // 25% off the 1000 charge for the first two intervals.
+    client.apply_coupon(&id, &merchant, &2_500u32, &(T0 + 2 * INTERVAL + 1));
+
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    client.charge_subscription(&id, &None);
+    let event: SubscriptionChargedEvent = last_event_data(&env);
+    assert_eq!(event.amount, 750);
+
+    env.ledger().set_timestamp(T0 + 2 * INTERVAL);
+    client.charge_subscription(&id, &None);
+    let event: SubscriptionChargedEvent = last_event_data(&env);
+    assert_eq!(event.amount, 750);
+
+    // Expired: full amount again.
+    env.ledger().set_timestamp(T0 + 3 * INTERVAL);
+    client.charge_subscription(&id, &None);
+    let event: SubscriptionChargedEvent = last_event_data(&env);
+    assert_eq!(event.amount, 1000);
+
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.prepaid_balance, 10_000000 - 2_500);
+    assert_eq!(sub.total_charged, 2_500);
+    assert_eq!(client.get_merchant_balance(&merchant, &sub.token), 2_500);
+}
+
+#[test]
+fn test_apply_coupon_requires_merchant() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let subscriber = client.get_subscription(&id).subscriber;
+    let res = client.try_apply_coupon(&id, &subscriber, &1_000u32, &(T0 + INTERVAL));
+    assert_eq!(res, Err(Ok(Error::Unauthorized)));
+}
+
+#[test]
+fn test_apply_coupon_rejects_discount_above_100_percent() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let merchant = client.get_subscription(&id).merchant;
+    let res = client.try_apply_coupon(&id, &merchant, &10_001u32, &(T0 + INTERVAL));
+    assert_eq!(res, Err(Ok(Error::InvalidAmount)));
+}
+
+#[test]
+fn test_quote_next_charge_decomposes_discount_and_fee() {
+    let (env, client, token, admin) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &20_000000i128, &None);
+    client.set_platform_fee(&admin, &250u32, &Address::generate(&env)); // 2.5%
+    let due = client.next_charge_timestamp(&id);
+    client.apply_coupon(&id, &merchant, &2_500u32, &(due + 1)); // 25% off
+
+    let quote = client.quote_next_charge(&id);
+    assert_eq!(
+        quote,
+        Quote {
+            base: 10_000_000,
+            discount: 2_500_000,
+            platform_fee: 187_500,
+            merchant_share: 7_312_500,
+            net: 7_500_000,
+            charge_timestamp: due,
+        }
+    );
+    assert_eq!(quote.base - quote.discount, quote.net);
+    assert_eq!(quote.merchant_share + quote.platform_fee, quote.net);
+
+    env.ledger().set_timestamp(due);
+    client.charge_subscription(&id, &None);
+    assert_eq!(
+        client.get_subscription(&id).prepaid_balance,
+        20_000000 - quote.net
+    );
+    assert_eq!(
+        client.get_merchant_balance(&merchant, &token),
+        quote.merchant_share
+    );
+    assert_eq!(client.get_fee_balance(&token), quote.platform_fee);
+
+    // The coupon has expired by the following charge.
+    assert_eq!(client.quote_next_charge(&id).discount, 0);
+}
+
+#[test]
+fn test_preview_charge_matches_actual_debit() {
+    let (env, client, _, admin) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &30_000000i128, &None);
+    client.set_platform_fee(&admin, &250u32, &Address::generate(&env));
+    let due = client.next_charge_timestamp(&id);
+    client.apply_coupon(&id, &merchant, &2_000u32, &(due + 1)); // 20% off
+
+    env.ledger().set_timestamp(due);
+    let preview = client.preview_charge(&id);
+    assert_eq!(preview, 8_000_000);
+    let before = client.get_subscription(&id).prepaid_balance;
+    client.charge_subscription(&id, &None);
+    assert_eq!(
+        before - client.get_subscription(&id).prepaid_balance,
+        preview
+    );
+
+    // Coupon expired: the preview is the full amount again, and so is the next debit.
+    let next = client.next_charge_timestamp(&id);
+    env.ledger().set_timestamp(next);
+    let preview = client.preview_charge(&id);
+    assert_eq!(preview, 10_000_000);
+    let before = client.get_subscription(&id).prepaid_balance;
+    client.charge_subscription(&id, &None);
+    assert_eq!(
+        before - client.get_subscription(&id).prepaid_balance,
+        preview
+    );
+}
+
+// =============================================================================
+// Managers and auth_check
+// =============================================================================
+
+#[test]
+fn test_auth_check_cancel() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let manager = Address::generate(&env);
+    let stranger = Address::generate(&env);
+    let cancel = symbol_short!("cancel");
+
+    assert!(!client.auth_check(&id, &cancel, &manager));
+    client.set_manager(&id, &subscriber, &Some(manager.clone()));
+    assert_eq!(client.get_manager(&id), Some(manager.clone()));
+
+    assert!(client.auth_check(&id, &cancel, &subscriber));
+    assert!(client.auth_check(&id, &cancel, &merchant));
+    assert!(client.auth_check(&id, &cancel, &manager));
+    assert!(!client.auth_check(&id, &cancel, &stranger));
+    // Funding is open to anyone; unknown actions are never authorized.
+    assert!(client.auth_check(&id, &symbol_short!("deposit"), &stranger));
+    assert!(!client.auth_check(&id, &symbol_short!("withdraw"), &subscriber));
+    // Read-only: state is untouched.
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Active
+    );
+}
+
+#[test]
+fn test_manager_can_pause_resume_and_cancel() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let manager = Address::generate(&env);
+    client.set_manager(&id, &subscriber, &Some(manager.clone()));
+
+    client.pause_subscription(&id, &manager);
+    client.resume_subscription(&id, &manager);
+    client.cancel_subscription(&id, &manager);
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Cancelled
+    );
+}
+
+#[test]
+fn test_lifecycle_calls_reject_stranger() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let stranger = Address::generate(&env);
+
+    assert_eq!(
+        client.try_pause_subscription(&id, &stranger),
+        Err(Ok(Error::Unauthorized))
+    );
+    assert_eq!(
+        client.try_cancel_subscription(&id, &stranger),
+        Err(Ok(Error::Unauthorized))
+    );
+    // Clearing the manager revokes access.
+    client.set_manager(&id, &subscriber, &Some(stranger.clone()));
+    client.set_manager(&id, &subscriber, &None);
+    assert_eq!(
+        client.try_resume_subscription(&id, &stranger),
+        Err(Ok(Error::Unauthorized))
+    );
+}
+
+/// The rejected call is rolled back, but the attempt is still reported as an event of the
+/// failed call, naming the caller.
+#[test]
+fn test_lifecycle_rejection_surfaces_attempted_caller() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, _, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let stranger = Address::generate(&env);
+    let seq_before = client.get_event_seq(&id);
+
+    assert_eq!(
+        client.try_cancel_subscription(&id, &stranger),
+        Err(Ok(Error::Unauthorized))
+    );
+    let attempt: UnauthorizedAttemptEvent = last_event_data(&env);
+    assert_eq!(
+        attempt,
+        UnauthorizedAttemptEvent {
+            subscription_id: id,
+            action: symbol_short!("cancel"),
+            caller: stranger,
+        }
+    );
+    assert_eq!(client.get_event_seq(&id), seq_before);
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Active
+    );
+}
+
+#[test]
+fn test_set_manager_requires_subscriber() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, _, merchant) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let res = client.try_set_manager(&id, &merchant, &Some(Address::generate(&env)));
+    assert_eq!(res, Err(Ok(Error::Unauthorized)));
+}
+
+// =============================================================================
+// Rounding remainder policy
+// =============================================================================
+
+/// Charges `id0` from `setup_batch_env` with a 33.33% coupon and a 2.5% fee, so both the
+/// discount (333.3) and the fee (16.675) are fractional. Returns (debited, merchant, fee).
+fn charge_with_fractional_amounts(
+    env: &Env,
+    policy: Option<RoundingRemainderPolicy>,
+) -> (i128, i128, i128) {
+    let (client, admin, id0, _) = setup_batch_env(env);
+    client.set_platform_fee(&admin, &250u32, &Address::generate(env));
+    if let Some(p) = policy {
+        client.set_rounding_remainder_policy(&admin, &p);
+    }
+    let sub = client.get_subscription(&id0);
+    client.apply_coupon(&id0, &sub.merchant, &3_333u32, &u64::MAX);
+
+    client.charge_subscription(&id0, &None);
+    let after = client.get_subscription(&id0);
+    (
+        sub.prepaid_balance - after.prepaid_balance,
+        client.get_merchant_balance(&sub.merchant, &sub.token),
+        client.get_fee_balance(&sub.token),
+    )
+}
+
+#[test]
+fn test_rounding_remainder_defaults_to_merchant() {
+    let env = Env::default();
+    let (debited, merchant, fee) = charge_with_fractional_amounts(&env, None);
+    assert_eq!(debited, 667); // 1000 - floor(333.3)
+    assert_eq!(fee, 16); // floor(16.675)
+    assert_eq!(merchant, 651);
+    assert_eq!(merchant + fee, debited);
+}
+
+#[test]
+fn test_rounding_remainder_to_platform() {
+    let env = Env::default();
+    let (debited, merchant, fee) =
+        charge_with_fractional_amounts(&env, Some(RoundingRemainderPolicy::Platform));
+    assert_eq!(debited, 667);
+    assert_eq!(fee, 17); // ceil(16.675)
+    assert_eq!(merchant, 650);
+    assert_eq!(merchant + fee, debited);
+}
+
+#[test]
+fn test_rounding_remainder_policy_getter_and_admin_only() {
+    let env = Env::default();
+    let (client, admin, _, _) = setup_batch_env(&env);
+    assert_eq!(
+        client.get_rounding_remainder_policy(),
+        RoundingRemainderPolicy::Merchant
+    );
+    let stranger = Address::generate(&env);
+    let res =
+        client.try_set_rounding_remainder_policy(&stranger, &RoundingRemainderPolicy::Platform);
+    assert_eq!(res, Err(Ok(Error::Unauthorized)));
+    client.set_rounding_remainder_policy(&admin, &RoundingRemainderPolicy::Platform);
+    assert_eq!(
+        client.get_rounding_remainder_policy(),
+        RoundingRemainderPolicy::Platform
+    );
+}
+
+// =============================================================================
+// Migration export/import
+// =============================================================================
+
+#[test]
+fn test_export_import_between_instances() {
+    let (env, old, token, admin) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &old, SubscriptionStatus::Active);
+    // Give the exported record some non-default state.
+    old.deposit_funds(&id, &subscriber, &30_000000i128, &None);
+    env.ledger()
+        .set_timestamp(env.ledger().timestamp() + INTERVAL);
+    old.charge_subscription(&id, &None);
+    old.pause_subscription(&id, &subscriber);
+    let exported = old.export_subscription(&admin, &id);
+
+    let new = SubscriptionVaultClient::new(&env, &env.register(SubscriptionVault, ()));
+    new.init(&token, &admin, &1_000000i128, &false);
+    let new_id = new.import_subscription(&admin, &exported);
+
+    let imported = new.get_subscription(&new_id);
+    assert_eq!(imported, exported);
+    assert_eq!(imported.status, SubscriptionStatus::Paused);
+    assert_eq!(imported.prepaid_balance, 20_000000i128);
+    assert_eq!(imported.total_charged, 10_000000i128);
+}
+
+#[test]
+fn test_export_subscription_admin_only() {
+    let (env, client, _, admin) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    assert_eq!(
+        client.export_subscription(&admin, &id),
+        client.get_subscription(&id)
+    );
+    assert_eq!(
+        client.try_export_subscription(&subscriber, &id),
+        Err(Ok(Error::Unauthorized))
+    );
+}
+
+#[test]
+fn test_import_rejected_after_native_creation() {
+    let (env, client, _, admin) = setup_test_env();
+    let (id, _, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let sub = client.get_subscription(&id);
+    assert_eq!(
+        client.try_import_subscription(&admin, &sub),
+        Err(Ok(Error::NotEmpty))
+    );
+}
+
+// =============================================================================
+// Event topics
+// =============================================================================
+
+#[test]
+fn test_events_filterable_by_subscription_id_topic() {
+    let env = Env::default();
+    let (client, _, id0, id1) = setup_batch_env(&env);
+    let ids = Vec::from_array(&env, [id0, id1]);
+    client.batch_charge(&ids);
+
+    // Both charges share the `charged` symbol; the second topic selects one subscription.
+    let topic: Vec<Val> = (symbol_short!("charged"), id1).into_val(&env);
+    let mut matching = 0;
+    for e in env.events().all().iter() {
+        if e.1 == topic {
+            let data = SubscriptionChargedEvent::try_from_val(&env, &e.2).unwrap();
+            assert_eq!(data.subscription_id, id1);
+            matching += 1;
+        }
+    }
+    assert_eq!(matching, 1);
+}
+
+#[test]
+fn test_event_seq_counts_per_subscription() {
+    let (env, client, token, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    mint(&env, &token, &subscriber);
+    let id = client.create_subscription(
+        &subscriber,
+        &Address::generate(&env),
+        &1_000000i128,
+        &INTERVAL,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    let created: SubscriptionCreatedEvent = last_event_data(&env);
+    assert_eq!(created.subscription_id, id);
+    assert_eq!(created.seq, 0);
+
+    client.deposit_funds(&id, &subscriber, &5_000000i128, &None);
+    let deposited: FundsDepositedEvent = last_event_data(&env);
+    assert_eq!(deposited.seq, 1);
+
+    env.ledger().set_timestamp(INTERVAL);
+    client.charge_subscription(&id, &None);
+    // The charge emits MerchantCreditedEvent (seq 2) before SubscriptionChargedEvent.
+    let charged: SubscriptionChargedEvent = last_event_data(&env);
+    assert_eq!(charged.seq, 3);
+    assert_eq!(client.get_event_seq(&id), 4);
+
+    // Counters are independent per subscription.
+    let (other, _, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    assert_eq!(client.get_event_seq(&other), 1);
+}
+
+// =============================================================================
+// Autopay
+// =============================================================================
+
+#[test]
+fn test_charge_emits_merchant_credited_and_charged_events() {
+    let (env, client, token, admin) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &30_000000i128, &None);
+    client.set_platform_fee(&admin, &250u32, &Address::generate(&env)); // 2.5%
+
+    env.ledger()
+        .set_timestamp(client.next_charge_timestamp(&id));
+    client.charge_subscription(&id, &None);
+    let events = env.events().all();
+    let credited = events
+        .iter()
+        .find(|e| e.1 == (symbol_short!("credited"), id).into_val(&env))
+        .expect("credited event");
+    let credited = MerchantCreditedEvent::try_from_val(&env, &credited.2).unwrap();
+    assert_eq!(credited.subscription_id, id);
+    assert_eq!(credited.merchant, merchant);
+    assert_eq!(credited.token, token);
+    assert_eq!(credited.amount, 9_750_000);
+    assert_eq!(credited.new_balance, 9_750_000);
+
+    let charged = SubscriptionChargedEvent::try_from_val(&env, &events.last().unwrap().2).unwrap();
+    assert_eq!(charged.amount, 10_000_000);
+    assert_eq!(charged.merchant, merchant);
+    assert_eq!(charged.seq, credited.seq + 1);
+    assert_eq!(
+        client.get_merchant_balance(&merchant, &token),
+        credited.new_balance
+    );
+}
+
+#[test]
+fn test_autopay_pulls_shortfall_from_allowance() {
+    let (env, client, token, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let sub = client.get_subscription(&id);
+    let token_client = TokenClient::new(&env, &token);
+    client.set_autopay(&id, &subscriber, &true);
+    assert!(client.get_autopay(&id));
+    token_client.approve(
+        &subscriber,
+        &client.address,
+        &sub.amount,
+        &(env.ledger().sequence() + 1000),
+    );
+
+    let before = token_client.balance(&subscriber);
+    env.ledger()
+        .set_timestamp(sub.last_payment_timestamp + sub.interval_seconds);
+    assert_eq!(client.can_charge(&id), (true, 0));
+    client.charge_subscription(&id, &None);
+
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.status, SubscriptionStatus::Active);
+    assert_eq!(sub.prepaid_balance, 0);
+    assert_eq!(sub.total_charged, sub.amount);
+    assert_eq!(token_client.balance(&subscriber), before - sub.amount);
+    assert_eq!(client.get_merchant_balance(&merchant, &token), sub.amount);
+    assert_eq!(token_client.allowance(&subscriber, &client.address), 0);
+}
+
+#[test]
+fn test_autopay_allowance_too_small_fails_charge() {
+    let (env, client, token, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let sub = client.get_subscription(&id);
+    let token_client = TokenClient::new(&env, &token);
+    client.set_autopay(&id, &subscriber, &true);
+    token_client.approve(
+        &subscriber,
+        &client.address,
+        &(sub.amount - 1),
+        &(env.ledger().sequence() + 1000),
+    );
+
+    let before = token_client.balance(&subscriber);
+    env.ledger()
+        .set_timestamp(sub.last_payment_timestamp + sub.interval_seconds);
+    assert_eq!(
+        client.can_charge(&id),
+        (false, Error::InsufficientBalance.to_code())
+    );
+    assert_eq!(
+        client.try_charge_subscription(&id, &None),
+        Err(Ok(Error::InsufficientBalance))
+    );
+    assert_eq!(token_client.balance(&subscriber), before);
+    assert_eq!(client.get_subscription(&id).total_charged, 0);
+}
+
+// =============================================================================
+// Auto-renew and lapse
+// =============================================================================
+
+#[test]
+fn test_lapse_when_auto_renew_disabled() {
+    let (env, client, token, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &30_000000i128, &None);
+    assert!(client.get_auto_renew(&id));
+    client.set_auto_renew(&id, &subscriber, &false);
+    assert!(!client.get_auto_renew(&id));
+
+    let sub = client.get_subscription(&id);
+    let before = TokenClient::new(&env, &token).balance(&subscriber);
+    env.ledger()
+        .set_timestamp(sub.last_payment_timestamp + sub.interval_seconds + 10);
+    assert_eq!(client.can_charge(&id), (false, Error::NotActive.to_code()));
+
+    client.charge_subscription(&id, &None);
+    let event: SubscriptionLapsedEvent = last_event_data(&env);
+    assert_eq!(event.subscription_id, id);
+    assert_eq!(
+        event.last_cycle_end,
+        sub.last_payment_timestamp + sub.interval_seconds
+    );
+    assert_eq!(event.refund_amount, 30_000000i128);
+
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.status, SubscriptionStatus::Cancelled);
+    assert_eq!(sub.prepaid_balance, 0);
+    assert_eq!(sub.total_charged, 0);
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 0);
+    assert_eq!(
+        TokenClient::new(&env, &token).balance(&subscriber),
+        before + 30_000000
+    );
+}
+
+#[test]
+fn test_effective_status_reports_lazy_lapse() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let (scheduled, scheduled_subscriber, _) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.set_auto_renew(&id, &subscriber, &false);
+    client.cancel_at_period_end(&scheduled, &scheduled_subscriber);
+
+    let period_end = client.next_charge_timestamp(&id);
+    env.ledger().set_timestamp(period_end - 1);
+    assert_eq!(client.effective_status(&id), SubscriptionStatus::Active);
+
+    env.ledger().set_timestamp(period_end);
+    for sub_id in [id, scheduled] {
+        assert_eq!(
+            client.get_subscription(&sub_id).status,
+            SubscriptionStatus::Active
+        );
+        assert_eq!(
+            client.effective_status(&sub_id),
+            SubscriptionStatus::Cancelled
+        );
+    }
+}
+
+#[test]
+fn test_auto_renew_off_does_not_lapse_before_period_end() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.set_auto_renew(&id, &subscriber, &false);
+    let res = client.try_charge_subscription(&id, &None);
+    assert_eq!(res, Err(Ok(Error::IntervalNotElapsed)));
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Active
+    );
+}
+
+#[test]
+fn test_set_auto_renew_requires_subscriber() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, _, merchant) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let res = client.try_set_auto_renew(&id, &merchant, &false);
+    assert_eq!(res, Err(Ok(Error::Unauthorized)));
+}
+
+// =============================================================================
+// Version and upgrade
+// =============================================================================
+
+#[test]
+fn test_version_returns_initial_constant() {
+    let (_env, client, _, _) = setup_test_env();
+    assert_eq!(client.version(), crate::admin::CONTRACT_VERSION);
+    assert_eq!(client.version(), 1);
+}
+
+#[test]
+fn test_upgrade_rejects_non_admin() {
+    let (env, client, _, _) = setup_test_env();
+    let stranger = Address::generate(&env);
+    let hash = soroban_sdk::BytesN::from_array(&env, &[7u8; 32]);
+    let res = client.try_upgrade(&stranger, &hash);
+    assert_eq!(res, Err(Ok(Error::Unauthorized)));
+    assert_eq!(client.version(), 1);
+}
+
+// =============================================================================
+// Admin multisig
+// =============================================================================
+
+#[test]
+fn test_force_cancel_needs_two_of_three_approvals() {
+    let (env, client, token, admin) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &25_000000i128, &None);
+    let signers = Vec::from_array(
+        &env,
+        [
+            Address::generate(&env),
+            Address::generate(&env),
+            Address::generate(&env),
+        ],
+    );
+    let (s0, s1) = (signers.get(0).unwrap(), signers.get(1).unwrap());
+    client.set_admin_multisig(&admin, &signers, &2);
+    assert_eq!(client.get_admin_multisig(), (signers.clone(), 2));
+    assert_eq!(
+        client.try_set_admin_multisig(&admin, &signers, &4),
+        Err(Ok(Error::InvalidAmount))
+    );
+
+    let hash = client.force_cancel_action_hash(&id);
+    assert_eq!(
+        client.try_approve_action(&Address::generate(&env), &hash),
+        Err(Ok(Error::Unauthorized))
+    );
+    assert_eq!(client.approve_action(&s0, &hash), 1);
+    // Repeat approvals count once.
+    assert_eq!(client.approve_action(&s0, &hash), 1);
+    assert_eq!(
+        client.try_force_cancel(&s0, &id),
+        Err(Ok(Error::Unauthorized))
+    );
+    // The single admin alone is no longer enough either.
+    assert_eq!(
+        client.try_force_cancel(&admin, &id),
+        Err(Ok(Error::Unauthorized))
+    );
+
+    // Approvals are per action: another subscription's hash differs.
+    let (other, _, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    assert_ne!(client.force_cancel_action_hash(&other), hash);
+
+    assert_eq!(client.approve_action(&s1, &hash), 2);
+    let before = TokenClient::new(&env, &token).balance(&subscriber);
+    client.force_cancel(&s1, &id);
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Cancelled
+    );
+    assert_eq!(
+        TokenClient::new(&env, &token).balance(&subscriber),
+        before + 25_000000
+    );
+    // Execution consumed the approvals.
+    assert_eq!(client.get_action_approvals(&hash).len(), 0);
+
+    // Upgrades go through the same gate.
+    let wasm = soroban_sdk::BytesN::from_array(&env, &[7u8; 32]);
+    assert_eq!(
+        client.try_upgrade(&admin, &wasm),
+        Err(Ok(Error::Unauthorized))
+    );
+}
+
+fn three_signers(env: &Env) -> Vec<Address> {
+    Vec::from_array(
+        env,
+        [
+            Address::generate(env),
+            Address::generate(env),
+            Address::generate(env),
+        ],
+    )
+}
+
+#[test]
+fn test_lone_admin_cannot_disable_multisig() {
+    let (env, client, _, admin) = setup_test_env();
+    let signers = three_signers(&env);
+    let (s0, s1) = (signers.get(0).unwrap(), signers.get(1).unwrap());
+    client.set_admin_multisig(&admin, &signers, &2);
+
+    let empty = Vec::new(&env);
+    assert_eq!(
+        client.try_set_admin_multisig(&admin, &empty, &0),
+        Err(Ok(Error::Unauthorized))
+    );
+    let own = Vec::from_array(&env, [admin.clone()]);
+    assert_eq!(
+        client.try_set_admin_multisig(&admin, &own, &1),
+        Err(Ok(Error::Unauthorized))
+    );
+    assert_eq!(client.get_admin_multisig(), (signers.clone(), 2));
+
+    // One approval is not enough; two signers can turn it off.
+    let hash = client.set_multisig_action_hash(&empty, &0);
+    client.approve_action(&s0, &hash);
+    assert_eq!(
+        client.try_set_admin_multisig(&s0, &empty, &0),
+        Err(Ok(Error::Unauthorized))
+    );
+    client.approve_action(&s1, &hash);
+    client.set_admin_multisig(&s0, &empty, &0);
+    assert_eq!(client.get_admin_multisig(), (empty, 0));
+    assert_eq!(client.get_action_approvals(&hash).len(), 0);
+}
+
+#[test]
+fn test_removed_signers_approvals_do_not_count() {
+    let (env, client, _, admin) = setup_test_env();
+    let (id, _, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let signers = three_signers(&env);
+    let (s0, s1, s2) = (
+        signers.get(0).unwrap(),
+        signers.get(1).unwrap(),
+        signers.get(2).unwrap(),
+    );
+    client.set_admin_multisig(&admin, &signers, &2);
+    let cancel = client.force_cancel_action_hash(&id);
+    client.approve_action(&s0, &cancel);
+
+    // Replace s0 with a new signer s3.
+    let s3 = Address::generate(&env);
+    let next = Vec::from_array(&env, [s1.clone(), s2.clone(), s3.clone()]);
+    let change = client.set_multisig_action_hash(&next, &2);
+    client.approve_action(&s1, &change);
+    client.approve_action(&s2, &change);
+    client.set_admin_multisig(&s1, &next, &2);
+
+    // s0's stale approval plus s1's is not two current approvals.
+    assert_eq!(client.approve_action(&s1, &cancel), 1);
+    assert_eq!(
+        client.try_force_cancel(&s1, &id),
+        Err(Ok(Error::Unauthorized))
+    );
+    assert_eq!(client.approve_action(&s3, &cancel), 2);
+    client.force_cancel(&s1, &id);
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Cancelled
+    );
+}
+
+#[test]
+fn test_force_cancel_single_admin_without_multisig() {
+    let (env, client, _, admin) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    assert_eq!(
+        client.try_force_cancel(&subscriber, &id),
+        Err(Ok(Error::Unauthorized))
+    );
+    client.force_cancel(&admin, &id);
+    let event: SubscriptionCancelledEvent = last_event_data(&env);
+    assert_eq!(event.authorizer, admin);
+    assert_eq!(event.refund_to, subscriber);
+}
+
+// =============================================================================
+// Grace period / past due
+// =============================================================================
+
+#[test]
+fn test_per_subscription_grace_windows() {
+    let (env, client, token, admin) = setup_test_env();
+    const DAY: u64 = 24 * 60 * 60;
+    client.set_default_grace_seconds(&admin, &DAY);
+    assert_eq!(client.get_default_grace_seconds(), DAY);
+
+    let merchant = Address::generate(&env);
+    let sub_short = Address::generate(&env);
+    let sub_long = Address::generate(&env);
+    mint(&env, &token, &sub_long);
+    // Falls back to the 1-day default.
+    let short = client.create_subscription(
+        &sub_short, &merchant, &1000i128, &INTERVAL, &false, &None, &0, &0,
+    );
+    let long = client.create_subscription_from_params(
+        &SubscriptionParams::new(sub_long.clone(), merchant.clone(), 1000, INTERVAL)
+            .unwrap()
+            .with_grace_seconds(3 * DAY),
+    );
+    assert_eq!(client.get_subscription(&short).grace_seconds, DAY);
+    assert_eq!(client.get_subscription(&long).grace_seconds, 3 * DAY);
+
+    // Both due and unfunded: each charge fails and both enter past due, still Active.
+    let due = client.get_subscription(&short).last_payment_timestamp + INTERVAL;
+    env.ledger().set_timestamp(due);
+    for id in [short, long] {
+        assert_eq!(
+            client.try_charge_subscription(&id, &None),
+            Err(Ok(Error::InsufficientBalance))
+        );
+        assert!(client.is_past_due(&id));
+        assert_eq!(
+            client.get_subscription(&id).status,
+            SubscriptionStatus::Active
+        );
+    }
+
+    // Two days later the short window is over: it leaves past due and the next charge attempt
+    // moves it to InsufficientBalance. The long one is still in grace.
+    env.ledger().set_timestamp(due + 2 * DAY);
+    assert!(!client.is_past_due(&short));
+    assert!(client.is_past_due(&long));
+    let results = client.batch_charge(&Vec::from_array(&env, [short, long]));
+    assert_eq!(
+        results.get(0).unwrap().error_code,
+        Error::InsufficientBalance.to_code()
+    );
+    assert_eq!(
+        client.get_subscription(&short).status,
+        SubscriptionStatus::InsufficientBalance
+    );
+    assert_eq!(
+        client.get_subscription(&long).status,
+        SubscriptionStatus::Active
+    );
+
+    // Funding within grace recovers the long one.
+    client.deposit_funds(&long, &sub_long, &5_000000i128, &None);
+    client.charge_subscription(&long, &None);
+    assert!(!client.is_past_due(&long));
+    assert_eq!(client.get_subscription(&long).total_charged, 1000);
+}
+
+#[test]
+fn test_expire_stale_cancels_only_past_grace() {
+    let (env, client, token, _) = setup_test_env();
+    const DAY: u64 = 24 * 60 * 60;
+    let merchant = Address::generate(&env);
+    let subscriber = Address::generate(&env);
+    mint(&env, &token, &subscriber);
+    let create = |grace: u64| {
+        client.create_subscription_from_params(
+            &SubscriptionParams::new(subscriber.clone(), merchant.clone(), 10_000000, INTERVAL)
+                .unwrap()
+                .with_grace_seconds(grace),
+        )
+    };
+    let short = create(DAY);
+    let long = create(5 * DAY);
+    // Partly funded, so neither covers its charge.
+    client.deposit_funds(&short, &subscriber, &4_000000i128, &None);
+    client.deposit_funds(&long, &subscriber, &4_000000i128, &None);
+    let tokens = TokenClient::new(&env, &token);
+    let before = tokens.balance(&subscriber);
+
+    let due = client.get_subscription(&short).last_payment_timestamp + INTERVAL;
+    env.ledger().set_timestamp(due + 2 * DAY);
+    assert!(client.is_past_due(&long));
+    let ids = Vec::from_array(&env, [short, long, 999]);
+    assert_eq!(client.expire_stale(&ids), 1);
+    let event: SubscriptionCancelledEvent = last_event_data(&env);
+    assert_eq!(event.subscription_id, short);
+    assert_eq!(event.authorizer, client.address);
+    assert_eq!(event.refund_amount, 4_000000i128);
+
+    assert_eq!(
+        client.get_subscription(&short).status,
+        SubscriptionStatus::Cancelled
+    );
+    assert_eq!(
+        client.get_subscription(&long).status,
+        SubscriptionStatus::Active
+    );
+    assert_eq!(tokens.balance(&subscriber), before + 4_000000i128);
+
+    // Running it again changes nothing until the long window ends too.
+    assert_eq!(client.expire_stale(&ids), 0);
+    env.ledger().set_timestamp(due + 5 * DAY);
+    assert_eq!(client.expire_stale(&ids), 1);
+}
+
+#[test]
+fn test_set_default_grace_seconds_admin_only() {
+    let (env, client, _, _) = setup_test_env();
+    let stranger = Address::generate(&env);
+    assert_eq!(
+        client.try_set_default_grace_seconds(&stranger, &60),
+        Err(Ok(Error::Unauthorized))
+    );
+    assert_eq!(client.get_default_grace_seconds(), 0);
+}
+
+// =============================================================================
+// Dunning retries
+// =============================================================================
+
+#[test]
+fn test_dunning_retries_then_cancels_at_limit() {
+    let (env, client, token, admin) = setup_test_env();
+    client.set_max_retries(&admin, &3u32);
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    // 5 USDC against a 10 USDC charge: every attempt fails.
+    client.deposit_funds(&id, &subscriber, &5_000000i128, &None);
+    let start = env.ledger().timestamp();
+    let before = TokenClient::new(&env, &token).balance(&subscriber);
+
+    for attempt in 1..=2u32 {
+        env.ledger()
+            .set_timestamp(start + INTERVAL + u64::from(attempt) * 3600);
+        // Recorded rather than reverted.
+        client.charge_subscription(&id, &None);
+        let event: ChargeFailedEvent = last_event_data(&env);
+        assert_eq!(event.failed_charge_count, attempt);
+        assert_eq!(event.amount, 10_000_000);
+        assert_eq!(event.prepaid_balance, 5_000000);
+        let sub = client.get_subscription(&id);
+        assert_eq!(sub.failed_charge_count, attempt);
+        assert_eq!(sub.status, SubscriptionStatus::Active);
+    }
+
+    env.ledger().set_timestamp(start + INTERVAL + 3 * 3600);
+    client.charge_subscription(&id, &None);
+    let failed_topic: Vec<Val> = (symbol_short!("chg_fail"), id).into_val(&env);
+    let failed = env
+        .events()
+        .all()
+        .iter()
+        .find(|e| e.1 == failed_topic)
+        .unwrap();
+    let failed = ChargeFailedEvent::try_from_val(&env, &failed.2).unwrap();
+    assert_eq!(failed.failed_charge_count, 3);
+    let cancelled: SubscriptionCancelledEvent = last_event_data(&env);
+    assert_eq!(cancelled.authorizer, client.address);
+    assert_eq!(cancelled.refund_amount, 5_000000);
+
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.status, SubscriptionStatus::Cancelled);
+    assert_eq!(sub.prepaid_balance, 0);
+    assert_eq!(
+        TokenClient::new(&env, &token).balance(&subscriber),
+        before + 5_000000
+    );
+}
+
+#[test]
+fn test_dunning_count_resets_after_successful_charge() {
+    let (env, client, _, admin) = setup_test_env();
+    client.set_max_retries(&admin, &3u32);
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let start = env.ledger().timestamp();
+    env.ledger().set_timestamp(start + INTERVAL);
+    client.charge_subscription(&id, &None);
+    assert_eq!(client.get_subscription(&id).failed_charge_count, 1);
+
+    client.deposit_funds(&id, &subscriber, &20_000000i128, &None);
+    client.charge_subscription(&id, &None);
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.failed_charge_count, 0);
+    assert_eq!(sub.total_charged, 10_000_000);
+}
+
+#[test]
+fn test_dunning_does_not_swallow_other_charge_rejections() {
+    let env = Env::default();
+    let (client, id, _) = setup_spending_cap(&env, 1000i128);
+    client.set_max_retries(&client.get_admin(), &3u32);
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    client.charge_subscription(&id, &None);
+
+    env.ledger().set_timestamp(T0 + 2 * INTERVAL);
+    assert_eq!(
+        client.try_charge_subscription(&id, &None),
+        Err(Ok(Error::SpendingCapReached))
+    );
+    assert_eq!(client.get_subscription(&id).failed_charge_count, 0);
+}
+
+#[test]
+fn test_without_dunning_unfunded_charge_still_errors() {
+    let (env, client, _, _) = setup_test_env();
+    assert_eq!(client.get_max_retries(), 0);
+    let (id, _, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    env.ledger()
+        .set_timestamp(env.ledger().timestamp() + INTERVAL);
+    assert_eq!(
+        client.try_charge_subscription(&id, &None),
+        Err(Ok(Error::InsufficientBalance))
+    );
+}
+
+// =============================================================================
+// Total value locked
+// =============================================================================
+
+fn assert_holdings_covered(env: &Env, client: &SubscriptionVaultClient, token: &Address) {
+    let holdings = TokenClient::new(env, token).balance(&client.address);
+    assert_eq!(
+        client.total_prepaid(token)
+            + client.total_merchant_owed(token)
+            + client.get_fee_balance(token),
+        holdings
+    );
+}
+
+#[test]
+fn test_totals_match_holdings_through_lifecycle() {
+    let (env, client, token, admin) = setup_test_env();
+    client.set_platform_fee(&admin, &250u32, &Address::generate(&env));
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let (id2, subscriber2, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    assert_holdings_covered(&env, &client, &token);
+
+    client.deposit_funds(&id, &subscriber, &35_000000i128, &None);
+    client.deposit_funds(&id2, &subscriber2, &20_000000i128, &None);
+    assert_eq!(client.total_prepaid(&token), 55_000000);
+    assert_holdings_covered(&env, &client, &token);
+
+    let start = env.ledger().timestamp();
+    env.ledger().set_timestamp(start + INTERVAL);
+    client.charge_subscription(&id, &None);
+    client.charge_subscription(&id2, &None);
+    assert_holdings_covered(&env, &client, &token);
+
+    client.charge_one_off(&id, &merchant, &3_000000i128);
+    assert_holdings_covered(&env, &client, &token);
+
+    client.withdraw_merchant_funds(&merchant, &5_000000i128);
+    assert_holdings_covered(&env, &client, &token);
+
+    client.cancel_subscription(&id, &subscriber);
+    assert_holdings_covered(&env, &client, &token);
+
+    let fee_collector = client.get_fee_collector();
+    client.withdraw_fees(&fee_collector, &token);
+    assert_holdings_covered(&env, &client, &token);
+    assert_eq!(client.total_prepaid(&token), 10_000000);
+    assert_eq!(
+        client.total_prepaid(&token),
+        client.get_subscription(&id2).prepaid_balance
+    );
+}
+
+#[test]
+fn test_obligations_per_token() {
+    let (env, client, token, admin) = setup_test_env();
+    client.set_platform_fee(&admin, &1_000u32, &Address::generate(&env)); // 10%
+    let other_token = create_token(&env);
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let other_subscriber = Address::generate(&env);
+    mint(&env, &other_token, &other_subscriber);
+    let other_id = client.create_subscription_from_params(
+        &SubscriptionParams::new(
+            other_subscriber.clone(),
+            Address::generate(&env),
+            2_000000,
+            INTERVAL,
+        )
+        .unwrap()
+        .with_token(other_token.clone()),
+    );
+    assert_eq!(client.obligations().len(), 0);
+
+    client.deposit_funds(&id, &subscriber, &30_000000i128, &None);
+    client.deposit_funds(&other_id, &other_subscriber, &8_000000i128, &None);
+    env.ledger().set_timestamp(INTERVAL);
+    client.charge_subscription(&id, &None);
+    client.charge_subscription(&other_id, &None);
+    client.dispute_charge(&id, &subscriber, &1_000000i128);
+
+    // Charges only move funds between buckets; disputes freeze part of the merchant balance.
+    let obligations = client.obligations();
+    assert_eq!(obligations.len(), 2);
+    assert_eq!(obligations.get(token.clone()), Some(30_000000));
+    assert_eq!(obligations.get(other_token.clone()), Some(8_000000));
+    assert_eq!(
+        client.total_prepaid(&token) + client.total_merchant_owed(&token),
+        30_000000 - 1_000000 - 1_000000 // frozen dispute and platform fee
+    );
+    for (t, owed) in obligations.iter() {
+        assert_eq!(TokenClient::new(&env, &t).balance(&client.address), owed);
+    }
+
+    // The refunded prepaid balance leaves; the charged 2 USDC is still owed (merchant + fee).
+    client.cancel_subscription(&other_id, &other_subscriber);
+    assert_eq!(client.obligations().get(other_token), Some(2_000000));
+}
+
+// =============================================================================
+// cancel_and_resubscribe
+// =============================================================================
+
+#[test]
+fn test_cancel_and_resubscribe_carries_refund() {
+    let (env, client, token, _) = setup_test_env();
+    let (old_id, subscriber, old_merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&old_id, &subscriber, &25_000000i128, &None);
+    let wallet_before = TokenClient::new(&env, &token).balance(&subscriber);
+
+    let new_merchant = Address::generate(&env);
+    let new_id = client.cancel_and_resubscribe(
+        &old_id,
+        &subscriber,
+        &new_merchant,
+        &5_000000i128,
+        &INTERVAL,
+    );
+    // The carried refund is reported as a deposit into the new subscription.
+    let deposited: FundsDepositedEvent = last_event_data(&env);
+    assert_eq!(deposited.subscription_id, new_id);
+    assert_eq!(deposited.payer, subscriber);
+    assert_eq!(deposited.amount, 25_000000i128);
+    // Find the cancellation by topic.
+    let cancelled = env
+        .events()
+        .all()
+        .iter()
+        .find(|e| e.1 == (symbol_short!("cancelled"), old_id).into_val(&env))
+        .unwrap();
+    let cancelled: SubscriptionCancelledEvent =
+        TryFromVal::try_from_val(&env, &cancelled.2).unwrap();
+    assert_eq!(cancelled.subscription_id, old_id);
+    assert_eq!(cancelled.refund_amount, 25_000000i128);
+
+    let old = client.get_subscription(&old_id);
+    assert_eq!(old.status, SubscriptionStatus::Cancelled);
+    assert_eq!(old.prepaid_balance, 0);
+    assert_eq!(old.merchant, old_merchant);
+
+    let new = client.get_subscription(&new_id);
+    assert_ne!(new_id, old_id);
+    assert_eq!(new.subscriber, subscriber);
+    assert_eq!(new.merchant, new_merchant);
+    assert_eq!(new.amount, 5_000000i128);
+    assert_eq!(new.status, SubscriptionStatus::Active);
+    assert_eq!(new.prepaid_balance, 25_000000i128);
+    assert_eq!(new.token, token);
+    assert_eq!(client.get_deposit_history(&new_id).len(), 1);
+
+    // Nothing was paid out: the refund stayed in the vault as the new balance.
+    assert_eq!(
+        TokenClient::new(&env, &token).balance(&subscriber),
+        wallet_before
+    );
+    assert_eq!(client.total_prepaid(&token), 25_000000i128);
+}
+
+#[test]
+fn test_cancel_and_resubscribe_requires_subscriber() {
+    let (env, client, _, _) = setup_test_env();
+    let (old_id, _, merchant) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let res = client.try_cancel_and_resubscribe(
+        &old_id,
+        &merchant,
+        &Address::generate(&env),
+        &1000i128,
+        &INTERVAL,
+    );
+    assert_eq!(res, Err(Ok(Error::Unauthorized)));
+    assert_eq!(
+        client.get_subscription(&old_id).status,
+        SubscriptionStatus::Active
+    );
+}
+
+// =============================================================================
+// Scheduled start
+// =============================================================================
+
+#[test]
+fn test_scheduled_start_rejects_charges_before_start() {
+    let (env, client, token, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    mint(&env, &token, &subscriber);
+    let now = env.ledger().timestamp();
+    let start = now + 7 * 24 * 60 * 60;
+    let id = client.create_subscription_from_params(
+        &SubscriptionParams::new(subscriber.clone(), Address::generate(&env), 1000, INTERVAL)
+            .unwrap()
+            .with_start_timestamp(start),
+    );
+    assert_eq!(client.get_subscription(&id).start_timestamp, start);
+    assert_eq!(client.next_charge_timestamp(&id), start + INTERVAL);
+
+    // Funding ahead of the start is allowed; charging is not.
+    client.deposit_funds(&id, &subscriber, &5_000000i128, &None);
+    env.ledger().set_timestamp(start - 1);
+    assert_eq!(client.can_charge(&id), (false, Error::NotStarted.to_code()));
+    assert_eq!(
+        client.try_charge_subscription(&id, &None),
+        Err(Ok(Error::NotStarted))
+    );
+}
+
+#[test]
+fn test_scheduled_start_charges_after_first_interval() {
+    let (env, client, token, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    mint(&env, &token, &subscriber);
+    let start = env.ledger().timestamp() + 3600;
+    let id = client.create_subscription_from_params(
+        &SubscriptionParams::new(subscriber.clone(), Address::generate(&env), 1000, INTERVAL)
+            .unwrap()
+            .with_start_timestamp(start),
+    );
+    client.deposit_funds(&id, &subscriber, &5_000000i128, &None);
+
+    env.ledger().set_timestamp(start);
+    assert_eq!(
+        client.try_charge_subscription(&id, &None),
+        Err(Ok(Error::IntervalNotElapsed))
+    );
+    env.ledger().set_timestamp(start + INTERVAL);
+    client.charge_subscription(&id, &None);
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.total_charged, 1000);
+    assert_eq!(client.next_charge_timestamp(&id), start + 2 * INTERVAL);
+}
+
+// =============================================================================
+// Fixed number of cycles
+// =============================================================================
+
+#[test]
+fn test_max_cycles_expires_after_last_charge() {
+    let (env, client, token, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    mint(&env, &token, &subscriber);
+    let id = client.create_subscription_from_params(
+        &SubscriptionParams::new(subscriber.clone(), Address::generate(&env), 1000, INTERVAL)
+            .unwrap()
+            .with_max_cycles(3),
+    );
+    client.deposit_funds(&id, &subscriber, &5_000000i128, &None);
+    let tokens = TokenClient::new(&env, &token);
+    let before = tokens.balance(&subscriber);
+
+    let start = env.ledger().timestamp();
+    for i in 1..=3u64 {
+        env.ledger().set_timestamp(start + i * INTERVAL);
+        client.charge_subscription(&id, &None);
+    }
+    let event: SubscriptionCancelledEvent = last_event_data(&env);
+    assert_eq!(event.authorizer, client.address);
+    assert_eq!(event.refund_amount, 5_000000 - 3000);
+    assert_eq!(event.refund_to, subscriber);
+
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.status, SubscriptionStatus::Cancelled);
+    assert_eq!(sub.cycles_charged, 3);
+    assert_eq!(sub.total_charged, 3000);
+    assert_eq!(sub.prepaid_balance, 0);
+    assert_eq!(tokens.balance(&subscriber), before + 5_000000 - 3000);
+
+    env.ledger().set_timestamp(start + 4 * INTERVAL);
+    assert_eq!(
+        client.try_charge_subscription(&id, &None),
+        Err(Ok(Error::NotActive))
+    );
+}
+
+// =============================================================================
+// Installment plans
+// =============================================================================
+
+#[test]
+fn test_installment_plan_last_charge_takes_remainder() {
+    let (env, client, token, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    mint(&env, &token, &subscriber);
+    let id = client.create_installment_plan(&subscriber, &merchant, &100, &3, &INTERVAL);
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.amount, 33);
+    assert_eq!(sub.max_cycles, 3);
+    client.deposit_funds(&id, &subscriber, &1_000000i128, &None);
+
+    let start = env.ledger().timestamp();
+    let mut charged = Vec::new(&env);
+    let mut total = 0i128;
+    for i in 1..=3u64 {
+        env.ledger().set_timestamp(start + i * INTERVAL);
+        let expected = if i == 3 { 34 } else { 33 };
+        assert_eq!(client.preview_charge(&id), expected);
+        let quote = client.quote_next_charge(&id);
+        assert_eq!(
+            (quote.base, quote.net, quote.discount),
+            (expected, expected, 0)
+        );
+        assert_eq!(quote.merchant_share + quote.platform_fee, expected);
+        client.charge_subscription(&id, &None);
+        if i == 3 {
+            // The final installment expires the plan and refunds the rest of the deposit.
+            let event: SubscriptionCancelledEvent = last_event_data(&env);
+            assert_eq!(event.refund_amount, 1_000000 - 100);
+        }
+        let now_total = client.get_subscription(&id).total_charged;
+        charged.push_back(now_total - total);
+        total = now_total;
+    }
+    assert_eq!(charged, Vec::from_array(&env, [33i128, 33, 34]));
+
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.status, SubscriptionStatus::Cancelled);
+    assert_eq!(sub.total_charged, 100);
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 100);
+}
+
+#[test]
+fn test_installment_plan_settled_on_final_cycle_collects_total() {
+    let (env, client, token, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    mint(&env, &token, &subscriber);
+    let id = client.create_installment_plan(&subscriber, &merchant, &100, &3, &INTERVAL);
+    client.set_cancel_settlement(&id, &merchant, &CancelSettlement::IfDue);
+    client.deposit_funds(&id, &subscriber, &1_000000i128, &None);
+
+    let start = env.ledger().timestamp();
+    for i in 1..=2u64 {
+        env.ledger().set_timestamp(start + i * INTERVAL);
+        client.charge_subscription(&id, &None);
+    }
+    // Cancelling when the final installment is due settles it with the remainder.
+    env.ledger().set_timestamp(start + 3 * INTERVAL);
+    assert_eq!(client.preview_cancel(&id), 1_000000 - 100);
+    client.cancel_subscription(&id, &subscriber);
+    assert_eq!(client.get_subscription(&id).total_charged, 100);
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 100);
+}
+
+#[test]
+fn test_installment_plan_invalid_split_rejected() {
+    let (env, client, _, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    for (total, installments) in [(100i128, 0u32), (2, 3), (-100, 3)] {
+        assert_eq!(
+            client.try_create_installment_plan(
+                &subscriber,
+                &merchant,
+                &total,
+                &installments,
+                &INTERVAL
+            ),
+            Err(Ok(Error::InvalidAmount))
+        );
+    }
+}
+
+// =============================================================================
+// History buffers
+// =============================================================================
+
+#[test]
+fn test_history_cap_keeps_latest_charges() {
+    let (env, client, _, admin) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &100_000000i128, &None);
+    assert_eq!(client.get_history_cap(), 10);
+    client.set_history_cap(&admin, &3);
+
+    let start = env.ledger().timestamp();
+    for i in 1..=5u64 {
+        env.ledger().set_timestamp(start + i * INTERVAL);
+        client.charge_subscription(&id, &None);
+    }
+
+    let history = client.get_charge_history(&id);
+    assert_eq!(history.len(), 3);
+    for (i, record) in history.iter().enumerate() {
+        assert_eq!(
+            record,
+            ChargeRecord {
+                timestamp: start + (i as u64 + 3) * INTERVAL,
+                amount: 10_000_000,
+            }
+        );
+    }
+    assert_eq!(client.get_deposit_history(&id).len(), 1);
+}
+
+#[test]
+fn test_merchant_revenue_since_sums_window() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &100_000000i128, &None);
+    let amount = client.get_subscription(&id).amount;
+
+    let start = env.ledger().timestamp();
+    let first = start + INTERVAL;
+    let second = start + 2 * INTERVAL;
+    env.ledger().set_timestamp(first);
+    client.charge_subscription(&id, &None);
+    env.ledger().set_timestamp(second);
+    client.charge_subscription(&id, &None);
+
+    assert_eq!(client.merchant_revenue_since(&merchant, &0), 2 * amount);
+    assert_eq!(
+        client.merchant_revenue_since(&merchant, &(first + 86_400)),
+        amount
+    );
+    assert_eq!(client.merchant_revenue_since(&merchant, &second), amount);
+    assert_eq!(
+        client.merchant_revenue_since(&merchant, &(second + 86_400)),
+        0
+    );
+    assert_eq!(
+        client.merchant_revenue_since(&Address::generate(&env), &0),
+        0
+    );
+}
+
+#[test]
+fn test_lowering_history_cap_trims_existing_buffers() {
+    let (env, client, _, admin) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    for _ in 0..4 {
+        client.deposit_funds(&id, &subscriber, &1_000000i128, &None);
+    }
+    client.pause_subscription(&id, &subscriber);
+    client.resume_subscription(&id, &subscriber);
+    assert_eq!(client.get_deposit_history(&id).len(), 4);
+    let statuses = client.get_status_history(&id);
+    assert_eq!(statuses.len(), 3);
+    assert_eq!(statuses.get(1).unwrap().status, SubscriptionStatus::Paused);
+
+    client.set_history_cap(&admin, &2);
+    assert_eq!(client.get_deposit_history(&id).len(), 2);
+    let statuses = client.get_status_history(&id);
+    assert_eq!(statuses.len(), 2);
+    assert_eq!(statuses.get(1).unwrap().status, SubscriptionStatus::Active);
+
+    // The next write persists the trimmed buffer.
+    client.deposit_funds(&id, &subscriber, &2_000000i128, &None);
+    let deposits = client.get_deposit_history(&id);
+    assert_eq!(deposits.len(), 2);
+    assert_eq!(deposits.get(0).unwrap().amount, 1_000000);
+    assert_eq!(deposits.get(1).unwrap().amount, 2_000000);
+    let stored_len = env.as_contract(&client.address, || {
+        env.storage()
+            .instance()
+            .get::<_, Vec<DepositRecord>>(&(symbol_short!("dep_hist"), id))
+            .unwrap()
+            .len()
+    });
+    assert_eq!(stored_len, 2);
+
+    assert_eq!(
+        client.try_set_history_cap(&admin, &0),
+        Err(Ok(Error::InvalidAmount))
+    );
+    assert_eq!(
+        client.try_set_history_cap(&subscriber, &5),
+        Err(Ok(Error::Unauthorized))
+    );
+}
+
+#[test]
+fn test_status_history_walks_full_lifecycle() {
+    let (env, client, _, _) = setup_test_env();
+    env.ledger().set_timestamp(100);
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    env.ledger().set_timestamp(200);
+    client.pause_subscription(&id, &subscriber);
+    client.pause_subscription(&id, &subscriber);
+    env.ledger().set_timestamp(300);
+    client.resume_subscription(&id, &merchant);
+    env.ledger().set_timestamp(400);
+    client.cancel_subscription(&id, &subscriber);
+
+    let mut expected = Vec::new(&env);
+    expected.push_back((100u64, SubscriptionStatus::Active));
+    expected.push_back((200u64, SubscriptionStatus::Paused));
+    expected.push_back((300u64, SubscriptionStatus::Active));
+    expected.push_back((400u64, SubscriptionStatus::Cancelled));
+    assert_eq!(client.status_history(&id), expected);
+
+    // Idempotent pause/cancel calls do not add entries.
+    client.cancel_subscription(&id, &subscriber);
+    assert_eq!(client.status_history(&id).len(), 4);
+}
+
+#[test]
+fn test_created_between_filters_by_creation_time() {
+    let (env, client, _, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let mut ids = Vec::new(&env);
+    for ts in [1_000u64, 2_000, 3_000, 4_000] {
+        env.ledger().set_timestamp(ts);
+        ids.push_back(client.create_subscription(
+            &subscriber,
+            &merchant,
+            &1000i128,
+            &INTERVAL,
+            &false,
+            &None,
+            &0,
+            &0,
+        ));
+    }
+
+    let window = client.created_between(&2_000, &3_000, &None, &10);
+    assert_eq!(
+        window,
+        Vec::from_array(&env, [ids.get(1).unwrap(), ids.get(2).unwrap()])
+    );
+    assert_eq!(client.created_between(&1_500, &4_000, &None, &1).len(), 1);
+    assert_eq!(client.created_between(&5_000, &6_000, &None, &10).len(), 0);
+
+    // Paginate with the last id of the previous page.
+    let page = client.created_between(&1_500, &4_000, &Some(ids.get(1).unwrap()), &10);
+    assert_eq!(
+        page,
+        Vec::from_array(&env, [ids.get(2).unwrap(), ids.get(3).unwrap()])
+    );
+}
+
+#[test]
+fn test_created_between_caps_limit() {
+    let (env, client, _, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    for _ in 0..101 {
+        client.create_subscription(
+            &subscriber,
+            &merchant,
+            &1000i128,
+            &INTERVAL,
+            &false,
+            &None,
+            &0,
+            &0,
+        );
+    }
+    let now = env.ledger().timestamp();
+    assert_eq!(
+        client.created_between(&0, &now, &None, &u32::MAX).len(),
+        100
+    );
+}
+
+#[test]
+fn test_list_subscriptions_paginates() {
+    let (env, client, _, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let mut ids = Vec::new(&env);
+    for _ in 0..5 {
+        ids.push_back(client.create_subscription(
+            &subscriber,
+            &merchant,
+            &1000i128,
+            &INTERVAL,
+            &false,
+            &None,
+            &0,
+            &0,
+        ));
+    }
+
+    let mut seen = Vec::new(&env);
+    let mut cursor = None;
+    let mut pages = 0;
+    loop {
+        let page = client.list_subscriptions(&cursor, &2);
+        if page.is_empty() {
+            break;
+        }
+        assert!(page.len() <= 2);
+        pages += 1;
+        cursor = page.last();
+        seen.append(&page);
+    }
+    assert_eq!(pages, 3);
+    assert_eq!(seen, ids);
+    assert_eq!(client.list_subscriptions(&None, &1_000).len(), 5);
+}
+
+#[test]
+fn test_list_by_status_filters_and_paginates() {
+    let (env, client, _, _) = setup_test_env();
+    let statuses = [
+        SubscriptionStatus::Active,
+        SubscriptionStatus::Paused,
+        SubscriptionStatus::Active,
+        SubscriptionStatus::Cancelled,
+        SubscriptionStatus::Active,
+        SubscriptionStatus::InsufficientBalance,
+    ];
+    for status in statuses {
+        create_test_subscription(&env, &client, status);
+    }
+
+    let active = client.list_by_status(&SubscriptionStatus::Active, &None, &10);
+    assert_eq!(active, Vec::from_array(&env, [0, 2, 4]));
+    assert_eq!(
+        client.list_by_status(&SubscriptionStatus::Paused, &None, &10),
+        Vec::from_array(&env, [1])
+    );
+    assert_eq!(
+        client.list_by_status(&SubscriptionStatus::Cancelled, &None, &10),
+        Vec::from_array(&env, [3])
+    );
+    assert_eq!(
+        client.list_by_status(&SubscriptionStatus::InsufficientBalance, &None, &10),
+        Vec::from_array(&env, [5])
+    );
+
+    let first = client.list_by_status(&SubscriptionStatus::Active, &None, &2);
+    assert_eq!(first, Vec::from_array(&env, [0, 2]));
+    let second = client.list_by_status(&SubscriptionStatus::Active, &first.last(), &2);
+    assert_eq!(second, Vec::from_array(&env, [4]));
+    assert!(client
+        .list_by_status(&SubscriptionStatus::Active, &Some(4), &2)
+        .is_empty());
+}
+
+// =============================================================================
+// Cancel at period end
+// =============================================================================
+
+#[test]
+fn test_cancel_at_period_end_keeps_service_then_finalizes() {
+    let (env, client, token, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &25_000000i128, &None);
+    let period_end = client.next_charge_timestamp(&id);
+
+    client.cancel_at_period_end(&id, &subscriber);
+    let scheduled = env
+        .events()
+        .all()
+        .iter()
+        .find(|e| e.1 == (symbol_short!("cncl_schd"), id).into_val(&env))
+        .unwrap();
+    let scheduled: CancelScheduledEvent = TryFromVal::try_from_val(&env, &scheduled.2).unwrap();
+    assert_eq!(scheduled.authorizer, subscriber);
+    assert_eq!(scheduled.effective_at, period_end);
+
+    // Still Active and not yet chargeable during the paid period.
+    env.ledger().set_timestamp(period_end - 1);
+    assert_eq!(
+        client.try_charge_subscription(&id, &None),
+        Err(Ok(Error::IntervalNotElapsed))
+    );
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Active
+    );
+
+    // The first due charge finalizes the cancellation without charging.
+    env.ledger().set_timestamp(period_end);
+    let before = TokenClient::new(&env, &token).balance(&subscriber);
+    client.charge_subscription(&id, &None);
+    let cancelled = env
+        .events()
+        .all()
+        .iter()
+        .find(|e| e.1 == (symbol_short!("cancelled"), id).into_val(&env))
+        .unwrap();
+    let cancelled: SubscriptionCancelledEvent =
+        TryFromVal::try_from_val(&env, &cancelled.2).unwrap();
+    assert_eq!(cancelled.authorizer, subscriber);
+    assert_eq!(cancelled.refund_amount, 25_000000);
+
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.status, SubscriptionStatus::Cancelled);
+    assert_eq!(sub.prepaid_balance, 0);
+    assert_eq!(sub.total_charged, 0);
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 0);
+    assert_eq!(
+        TokenClient::new(&env, &token).balance(&subscriber),
+        before + 25_000000
+    );
+}
+
+#[test]
+fn test_deposit_rejected_while_cancel_pending() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &25_000000i128, &None);
+    client.cancel_at_period_end(&id, &subscriber);
+
+    assert_eq!(
+        client.try_deposit_funds(&id, &subscriber, &5_000000i128, &None),
+        Err(Ok(Error::PendingCancellation))
+    );
+    assert_eq!(
+        client.try_deposit_for(&id, &Address::generate(&env), &5_000000i128),
+        Err(Ok(Error::PendingCancellation))
+    );
+    assert_eq!(Error::PendingCancellation.to_code(), 452);
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 25_000000);
+
+    // The scheduled cancellation still finalizes at period end.
+    env.ledger()
+        .set_timestamp(client.next_charge_timestamp(&id));
+    client.charge_subscription(&id, &None);
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.status, SubscriptionStatus::Cancelled);
+    assert_eq!(sub.prepaid_balance, 0);
+}
+
+#[test]
+fn test_cancel_at_period_end_rejects_cancelled_and_outsiders() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    assert_eq!(
+        client.try_cancel_at_period_end(&id, &Address::generate(&env)),
+        Err(Ok(Error::Unauthorized))
+    );
+    client.cancel_subscription(&id, &subscriber);
+    assert_eq!(
+        client.try_cancel_at_period_end(&id, &subscriber),
+        Err(Ok(Error::InvalidStatusTransition))
+    );
+}
+
+// =============================================================================
+// Charge nonce
+// =============================================================================
+
+#[test]
+fn test_charge_nonce_increments_across_subscriptions() {
+    let (env, client, _, _) = setup_test_env();
+    let (a, subscriber_a, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let (b, subscriber_b, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&a, &subscriber_a, &50_000000i128, &None);
+    client.deposit_funds(&b, &subscriber_b, &50_000000i128, &None);
+    assert_eq!(client.get_charge_nonce(), 0);
+
+    let charges = [(a, INTERVAL), (b, INTERVAL), (a, 2 * INTERVAL)];
+    for (expected, (id, at)) in (1u64..).zip(charges) {
+        env.ledger().set_timestamp(at);
+        client.charge_subscription(&id, &None);
+        let event: SubscriptionChargedEvent = last_event_data(&env);
+        assert_eq!(event.subscription_id, id);
+        assert_eq!(event.charge_nonce, expected);
+    }
+    assert_eq!(client.get_charge_nonce(), 3);
+}
+
+#[test]
+fn test_charge_nonce_shared_by_interval_and_usage_charges() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, merchant) = create_usage_subscription(&env, &client, 0);
+    client.deposit_funds(&id, &subscriber, &50_000000i128, &None);
+    let event_data = |topic: Symbol| {
+        let topic: Vec<Val> = (topic, id).into_val(&env);
+        env.events().all().iter().find(|e| e.1 == topic).unwrap().2
+    };
+
+    for i in [0u64, 2] {
+        env.ledger()
+            .set_timestamp(client.next_charge_timestamp(&id));
+        client.charge_subscription(&id, &None);
+        let charged =
+            SubscriptionChargedEvent::try_from_val(&env, &event_data(symbol_short!("charged")))
+                .unwrap();
+        assert_eq!(charged.charge_nonce, i + 1);
+        client.charge_usage(&id, &merchant, &500);
+        let usage =
+            UsageChargedEvent::try_from_val(&env, &event_data(symbol_short!("usage_ch"))).unwrap();
+        assert_eq!(usage.charge_nonce, i + 2);
+    }
+    assert_eq!(client.get_charge_nonce(), 4);
+}
+
+#[test]
+fn test_total_charges_counts_successful_charges() {
+    let (env, client, _, _) = setup_test_env();
+    let (a, subscriber_a, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let (b, subscriber_b, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&a, &subscriber_a, &50_000000i128, &None);
+    client.deposit_funds(&b, &subscriber_b, &20_000000i128, &None);
+
+    for i in 1..=3u64 {
+        env.ledger().set_timestamp(i * INTERVAL);
+        client.charge_subscription(&a, &None);
+        // `b` can pay for two intervals; the third attempt fails and is not counted.
+        let _ = client.try_charge_subscription(&b, &None);
+    }
+    assert_eq!(client.get_subscription(&b).total_charged, 20_000000);
+    assert_eq!(client.get_total_charges(), 5);
+}
+
+// =============================================================================
+// Disputes
+// =============================================================================
+
+#[test]
+fn test_dispute_auto_pauses_and_resolve_resumes() {
+    let (env, client, token, admin) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &30_000000i128, &None);
+    env.ledger()
+        .set_timestamp(client.next_charge_timestamp(&id));
+    client.charge_subscription(&id, &None);
+    client.set_auto_pause_on_dispute(&admin, &true);
+
+    client.dispute_charge(&id, &subscriber, &4_000000i128);
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Paused
+    );
+    assert_eq!(
+        client.get_dispute(&id),
+        Some(Dispute {
+            amount: 4_000000,
+            opened_at: env.ledger().timestamp(),
+            paused_subscription: true,
+        })
+    );
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 6_000000);
+    assert_eq!(
+        client.try_dispute_charge(&id, &subscriber, &1i128),
+        Err(Ok(Error::DisputeOpen))
+    );
+
+    // Resolved in the merchant's favour: funds return and billing resumes.
+    client.resolve_dispute(&id, &admin, &false);
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Active
+    );
+    assert_eq!(client.get_dispute(&id), None);
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 10_000000);
+    assert_eq!(client.reconcile_merchant(&merchant), (10_000000, 10_000000));
+}
+
+#[test]
+fn test_dispute_without_auto_pause_refunds_subscriber() {
+    let (env, client, token, admin) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &30_000000i128, &None);
+    env.ledger()
+        .set_timestamp(client.next_charge_timestamp(&id));
+    client.charge_subscription(&id, &None);
+    assert!(!client.get_auto_pause_on_dispute());
+    assert_eq!(
+        client.try_dispute_charge(&id, &subscriber, &10_000001i128),
+        Err(Ok(Error::InvalidAmount))
+    );
+
+    client.dispute_charge(&id, &subscriber, &10_000000i128);
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Active
+    );
+    let before = TokenClient::new(&env, &token).balance(&subscriber);
+    client.resolve_dispute(&id, &admin, &true);
+    assert_eq!(
+        TokenClient::new(&env, &token).balance(&subscriber),
+        before + 10_000000
+    );
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 0);
+}
+
+#[test]
+fn test_refunded_charges_cannot_be_disputed_again() {
+    let (env, client, token, admin) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &30_000000i128, &None);
+    env.ledger()
+        .set_timestamp(client.next_charge_timestamp(&id));
+    client.charge_subscription(&id, &None);
+    env.ledger()
+        .set_timestamp(client.next_charge_timestamp(&id));
+    client.charge_subscription(&id, &None);
+
+    client.dispute_charge(&id, &subscriber, &15_000000i128);
+    client.resolve_dispute(&id, &admin, &true);
+    // 20 charged, 15 refunded: only 5 is left to dispute.
+    assert_eq!(
+        client.try_dispute_charge(&id, &subscriber, &5_000001i128),
+        Err(Ok(Error::InvalidAmount))
+    );
+    client.dispute_charge(&id, &subscriber, &5_000000i128);
+    client.resolve_dispute(&id, &admin, &true);
+    assert_eq!(
+        client.try_dispute_charge(&id, &subscriber, &1i128),
+        Err(Ok(Error::InvalidAmount))
+    );
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 0);
+
+    // A dispute the merchant wins refunds nothing and leaves the bound unchanged.
+    env.ledger()
+        .set_timestamp(client.next_charge_timestamp(&id));
+    client.charge_subscription(&id, &None);
+    client.dispute_charge(&id, &subscriber, &10_000000i128);
+    client.resolve_dispute(&id, &admin, &false);
+    client.dispute_charge(&id, &subscriber, &10_000000i128);
+}
+
+#[test]
+fn test_purge_rejected_while_dispute_open() {
+    let (env, client, token, admin) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &30_000000i128, &None);
+    env.ledger()
+        .set_timestamp(client.next_charge_timestamp(&id));
+    client.charge_subscription(&id, &None);
+    client.dispute_charge(&id, &subscriber, &4_000000i128);
+    client.cancel_subscription(&id, &subscriber);
+
+    assert_eq!(
+        client.try_purge_subscription(&admin, &id),
+        Err(Ok(Error::DisputeOpen))
+    );
+    let before = TokenClient::new(&env, &token).balance(&subscriber);
+    client.resolve_dispute(&id, &admin, &true);
+    assert_eq!(
+        TokenClient::new(&env, &token).balance(&subscriber),
+        before + 4_000000
+    );
+    client.purge_subscription(&admin, &id);
+    assert_eq!(client.try_get_subscription(&id), Err(Ok(Error::NotFound)));
+}
+
+// =============================================================================
+// Owner indexes
+// =============================================================================
+
+#[test]
+fn test_admin_set_last_payment_corrects_schedule() {
+    let (env, client, _, admin) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let previous = client.get_subscription(&id).last_payment_timestamp;
+    let corrected = previous + 5_000;
+    client.set_last_payment(&admin, &id, &corrected);
+
+    let event: ScheduleCorrectedEvent = last_event_data(&env);
+    assert_eq!(event.previous_timestamp, previous);
+    assert_eq!(event.new_timestamp, corrected);
+    assert_eq!(
+        client.get_subscription(&id).last_payment_timestamp,
+        corrected
+    );
+    assert_eq!(client.next_charge_timestamp(&id), corrected + INTERVAL);
+
+    assert_eq!(
+        client.try_set_last_payment(&subscriber, &id, &previous),
+        Err(Ok(Error::Unauthorized))
+    );
+    assert_eq!(
+        client.get_subscription(&id).last_payment_timestamp,
+        corrected
+    );
+}
+
+#[test]
+fn test_compact_index_drops_purged_ids() {
+    let (env, client, _, admin) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let mut ids = Vec::new(&env);
+    for _ in 0..3 {
+        ids.push_back(client.create_subscription(
+            &subscriber,
+            &merchant,
+            &1000i128,
+            &INTERVAL,
+            &false,
+            &None,
+            &0,
+            &0,
+        ));
+    }
+    assert_eq!(client.get_subscriptions_by_subscriber(&subscriber), ids);
+    assert_eq!(client.get_subscriptions_by_merchant(&merchant), ids);
+
+    let (first, last) = (ids.get(0).unwrap(), ids.get(2).unwrap());
+    assert_eq!(
+        client.try_purge_subscription(&admin, &first),
+        Err(Ok(Error::InvalidStatusTransition))
+    );
+    client.cancel_subscription(&first, &subscriber);
+    client.cancel_subscription(&last, &merchant);
+    client.purge_subscription(&admin, &first);
+    client.purge_subscription(&admin, &last);
+    assert_eq!(
+        client.try_get_subscription(&first),
+        Err(Ok(Error::NotFound))
+    );
+
+    assert_eq!(client.compact_index(&admin, &subscriber, &false), 2);
+    let mut remaining = Vec::new(&env);
+    remaining.push_back(ids.get(1).unwrap());
+    assert_eq!(
+        client.get_subscriptions_by_subscriber(&subscriber),
+        remaining
+    );
+    // The merchant index is compacted separately.
+    assert_eq!(client.get_subscriptions_by_merchant(&merchant), ids);
+    assert_eq!(client.compact_index(&admin, &merchant, &true), 2);
+    assert_eq!(client.get_subscriptions_by_merchant(&merchant), remaining);
+    assert_eq!(client.compact_index(&admin, &merchant, &true), 0);
+
+    assert_eq!(
+        client.try_compact_index(&subscriber, &subscriber, &false),
+        Err(Ok(Error::Unauthorized))
+    );
+}
+
+#[test]
+fn test_merchant_projected_revenue_counts_affordable_charges() {
+    let (env, client, token, _) = setup_test_env();
+    let merchant = Address::generate(&env);
+    let week = 7 * 24 * 60 * 60;
+    let create = |interval: u64, deposit: i128| {
+        let subscriber = Address::generate(&env);
+        mint(&env, &token, &subscriber);
+        let id = client.create_subscription(
+            &subscriber,
+            &merchant,
+            &1_000000i128,
+            &interval,
+            &false,
+            &None,
+            &0,
+            &0,
+        );
+        client.deposit_funds(&id, &subscriber, &deposit, &None);
+        id
+    };
+    // Monthly: 3 charges fall in 90 days and 3.5 are funded -> 3.
+    create(INTERVAL, 3_500000);
+    // Weekly: 12 charges fall in 90 days but only 4 are funded -> 4.
+    let weekly = create(week, 4_000000);
+
+    let ninety_days = 3 * INTERVAL;
+    assert_eq!(
+        client.merchant_projected_revenue(&merchant, &ninety_days),
+        7_000000
+    );
+    // Shorter window: 1 monthly and 4 weekly charges (capped by funds).
+    assert_eq!(
+        client.merchant_projected_revenue(&merchant, &INTERVAL),
+        5_000000
+    );
+    // Inactive subscriptions are not projected.
+    client.pause_subscription(&weekly, &merchant);
+    assert_eq!(
+        client.merchant_projected_revenue(&merchant, &ninety_days),
+        3_000000
+    );
+}
+
+// =============================================================================
+// Auto charge on resume
+// =============================================================================
+
+#[test]
+fn test_resume_charges_overdue_subscription_when_enabled() {
+    let (env, client, _, admin) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &30_000000i128, &None);
+    client.set_auto_charge_on_resume(&admin, &true);
+    client.pause_subscription(&id, &subscriber);
+
+    env.ledger()
+        .set_timestamp(client.next_charge_timestamp(&id) + 3600);
+    client.resume_subscription(&id, &subscriber);
+    let events = env.events().all();
+    assert!(events
+        .iter()
+        .any(|e| e.1 == (symbol_short!("resumed"), id).into_val(&env)));
+    let event: SubscriptionChargedEvent = last_event_data(&env);
+    assert_eq!(event.subscription_id, id);
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.status, SubscriptionStatus::Active);
+    assert_eq!(sub.prepaid_balance, 20_000000);
+    assert_eq!(sub.last_payment_timestamp, env.ledger().timestamp());
+}
+
+#[test]
+fn test_resume_without_funds_or_flag_does_not_charge() {
+    let (env, client, _, admin) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.pause_subscription(&id, &subscriber);
+    env.ledger()
+        .set_timestamp(client.next_charge_timestamp(&id));
+
+    // Flag off: resume only.
+    client.deposit_funds(&id, &subscriber, &30_000000i128, &None);
+    client.resume_subscription(&id, &subscriber);
+    assert_eq!(client.get_subscription(&id).total_charged, 0);
+
+    // Flag on but unfunded: still resumes, no charge.
+    let (id2, subscriber2, _) = create_test_subscription(&env, &client, SubscriptionStatus::Paused);
+    client.set_auto_charge_on_resume(&admin, &true);
+    env.ledger()
+        .set_timestamp(client.next_charge_timestamp(&id2));
+    client.resume_subscription(&id2, &subscriber2);
+    let sub = client.get_subscription(&id2);
+    assert_eq!(sub.status, SubscriptionStatus::Active);
+    assert_eq!(sub.total_charged, 0);
+}
+
+#[test]
+fn test_funded_resume_allows_covered_subscription() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.set_require_funded_resume(&merchant, &true);
+    assert!(client.get_require_funded_resume(&merchant));
+    client.deposit_funds(&id, &subscriber, &10_000000i128, &None);
+    client.pause_subscription(&id, &subscriber);
+
+    client.resume_subscription(&id, &subscriber);
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Active
+    );
+}
+
+#[test]
+fn test_funded_resume_rejects_underfunded_subscription() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &9_000000i128, &None);
+    client.pause_subscription(&id, &subscriber);
+    client.set_require_funded_resume(&merchant, &true);
+
+    assert_eq!(
+        client.try_resume_subscription(&id, &subscriber),
+        Err(Ok(Error::InsufficientBalance))
+    );
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Paused
+    );
+
+    // Without the flag the same resume goes through.
+    client.set_require_funded_resume(&merchant, &false);
+    client.resume_subscription(&id, &subscriber);
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Active
+    );
+}
+
+#[test]
+fn test_pause_all_for_merchant_pauses_only_active() {
+    let (env, client, _, _) = setup_test_env();
+    let merchant = Address::generate(&env);
+    let mut ids = Vec::new(&env);
+    for _ in 0..3 {
+        let subscriber = Address::generate(&env);
+        ids.push_back(client.create_subscription(
+            &subscriber,
+            &merchant,
+            &1000i128,
+            &INTERVAL,
+            &false,
+            &None,
+            &0,
+            &0,
+        ));
+    }
+    let cancelled = ids.get(2).unwrap();
+    client.cancel_subscription(&cancelled, &merchant);
+    // Another merchant's subscription is untouched.
+    let (other, _, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+
+    assert_eq!(client.pause_all_for_merchant(&merchant), 2);
+    let paused_events = env
+        .events()
+        .all()
+        .iter()
+        .filter(|e| {
+            ids.iter()
+                .any(|id| e.1 == (symbol_short!("paused"), id).into_val(&env))
+        })
+        .count();
+    assert_eq!(paused_events, 2);
+    for id in [ids.get(0).unwrap(), ids.get(1).unwrap()] {
+        assert_eq!(
+            client.get_subscription(&id).status,
+            SubscriptionStatus::Paused
+        );
+    }
+    assert_eq!(
+        client.get_subscription(&cancelled).status,
+        SubscriptionStatus::Cancelled
+    );
+    assert_eq!(
+        client.get_subscription(&other).status,
+        SubscriptionStatus::Active
+    );
+    // Nothing left to pause.
+    assert_eq!(client.pause_all_for_merchant(&merchant), 0);
+}
+
+// =============================================================================
+// External reference
+// =============================================================================
+
+#[test]
+fn test_external_ref_in_getter_and_charged_event() {
+    let (env, client, token, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    mint(&env, &token, &subscriber);
+    let invoice = Bytes::from_slice(&env, b"INV-2024-0042");
+    let id = client.create_subscription_from_params(
+        &SubscriptionParams::new(subscriber.clone(), merchant.clone(), 1000, INTERVAL)
+            .unwrap()
+            .with_external_ref(invoice.clone())
+            .unwrap(),
+    );
+    assert_eq!(client.get_subscription(&id).external_ref, invoice);
+
+    let customer = Bytes::from_slice(&env, b"cust_81f3");
+    client.set_external_ref(&id, &merchant, &customer);
+    assert_eq!(client.get_subscription(&id).external_ref, customer);
+
+    client.deposit_funds(&id, &subscriber, &5_000000i128, &None);
+    env.ledger().set_timestamp(INTERVAL);
+    client.charge_subscription(&id, &None);
+    let event: SubscriptionChargedEvent = last_event_data(&env);
+    assert_eq!(event.external_ref, customer);
+}
+
+#[test]
+fn test_set_external_ref_rejects_subscriber_and_long_refs() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let r = Bytes::from_slice(&env, b"ref");
+    assert_eq!(
+        client.try_set_external_ref(&id, &subscriber, &r),
+        Err(Ok(Error::Unauthorized))
+    );
+    let too_long = Bytes::from_slice(&env, &[b'x'; 65]);
+    assert_eq!(
+        client.try_set_external_ref(&id, &merchant, &too_long),
+        Err(Ok(Error::InvalidAmount))
+    );
+}
+
+// =============================================================================
+// Merchant metadata
+// =============================================================================
+
+#[test]
+fn test_metadata_set_at_creation_and_updated() {
+    let (env, client, _, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    let plan = String::from_str(&env, "pro-annual");
+    let id = client.create_subscription_from_params(
+        &SubscriptionParams::new(subscriber, merchant.clone(), 1000, INTERVAL)
+            .unwrap()
+            .with_metadata(plan.clone())
+            .unwrap(),
+    );
+    assert_eq!(client.get_subscription(&id).metadata, plan);
+
+    let order = String::from_str(&env, "order-7731");
+    client.set_metadata(&id, &merchant, &order);
+    assert_eq!(client.get_subscription(&id).metadata, order);
+}
+
+#[test]
+fn test_set_metadata_rejects_non_merchant_and_long_values() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let label = String::from_str(&env, "basic");
+    assert_eq!(
+        client.try_set_metadata(&id, &subscriber, &label),
+        Err(Ok(Error::Unauthorized))
+    );
+    let too_long = String::from_str(&env, core::str::from_utf8(&[b'x'; 65]).unwrap());
+    assert_eq!(
+        client.try_set_metadata(&id, &merchant, &too_long),
+        Err(Ok(Error::InvalidAmount))
+    );
+    assert_eq!(
+        client.get_subscription(&id).metadata,
+        String::from_str(&env, "")
+    );
+}
+
+#[test]
+fn test_resume_all_for_merchant_skips_subscriber_pauses() {
+    let (env, client, _, _) = setup_test_env();
+    let merchant = Address::generate(&env);
+    let mut subs = Vec::new(&env);
+    for _ in 0..3 {
+        let subscriber = Address::generate(&env);
+        let id = client.create_subscription(
+            &subscriber,
+            &merchant,
+            &1000i128,
+            &INTERVAL,
+            &false,
+            &None,
+            &0,
+            &0,
+        );
+        subs.push_back((id, subscriber));
+    }
+    let (by_merchant, _) = subs.get(0).unwrap();
+    let (by_subscriber, subscriber) = subs.get(1).unwrap();
+    let (bulk_paused, _) = subs.get(2).unwrap();
+
+    client.pause_subscription(&by_merchant, &merchant);
+    client.pause_subscription(&by_subscriber, &subscriber);
+    assert_eq!(client.pause_all_for_merchant(&merchant), 1); // only `bulk_paused` was Active
+    assert_eq!(
+        client.get_paused_by(&by_subscriber),
+        Some(subscriber.clone())
+    );
+    assert_eq!(client.get_paused_by(&bulk_paused), Some(merchant.clone()));
+
+    assert_eq!(client.resume_all_for_merchant(&merchant), 2);
+    for id in [by_merchant, bulk_paused] {
+        assert_eq!(
+            client.get_subscription(&id).status,
+            SubscriptionStatus::Active
+        );
+        assert_eq!(client.get_paused_by(&id), None);
+    }
+    assert_eq!(
+        client.get_subscription(&by_subscriber).status,
+        SubscriptionStatus::Paused
+    );
+    assert_eq!(client.resume_all_for_merchant(&merchant), 0);
+}
+
+#[test]
+fn test_deposit_overflow_returns_clean_error() {
+    let (env, client, token, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    let id = client.create_subscription(
+        &subscriber,
+        &Address::generate(&env),
+        &1_000000i128,
+        &INTERVAL,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    let near_max = i128::MAX - 10;
+    StellarAssetClient::new(&env, &token).mint(&subscriber, &near_max);
+    client.deposit_funds(&id, &subscriber, &near_max, &None);
+    assert_eq!(client.get_subscription(&id).prepaid_balance, near_max);
+
+    // The second deposit is rejected by the balance check before any tokens move.
+    assert_eq!(
+        client.try_deposit_funds(&id, &subscriber, &near_max, &None),
+        Err(Ok(Error::Overflow))
+    );
+    assert_eq!(Error::Overflow.to_code(), 403);
+    assert_eq!(client.get_subscription(&id).prepaid_balance, near_max);
+    assert_eq!(client.total_prepaid(&token), near_max);
+}
+
+// =============================================================================
+// Third-party deposits
+// =============================================================================
+
+#[test]
+fn test_deposit_for_funds_from_third_party() {
+    let (env, client, token, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let employer = Address::generate(&env);
+    mint(&env, &token, &employer);
+    let token_client = TokenClient::new(&env, &token);
+    let (employer_before, subscriber_before) = (
+        token_client.balance(&employer),
+        token_client.balance(&subscriber),
+    );
+
+    env.mock_auths(&[MockAuth {
+        address: &employer,
+        invoke: &MockAuthInvoke {
+            contract: &client.address,
+            fn_name: "deposit_for",
+            args: (id, employer.clone(), 5_000000i128).into_val(&env),
+            sub_invokes: &[MockAuthInvoke {
+                contract: &token,
+                fn_name: "transfer",
+                args: (employer.clone(), client.address.clone(), 5_000000i128).into_val(&env),
+                sub_invokes: &[],
+            }],
+        },
+    }]);
+    client.deposit_for(&id, &employer, &5_000000i128);
+
+    let event: FundsDepositedEvent = last_event_data(&env);
+    assert_eq!(event.subscriber, subscriber);
+    assert_eq!(event.payer, employer);
+    assert_eq!(event.amount, 5_000000);
+    assert_eq!(event.new_balance, 5_000000);
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 5_000000);
+    assert_eq!(token_client.balance(&employer), employer_before - 5_000000);
+    assert_eq!(token_client.balance(&subscriber), subscriber_before);
+}
+
+#[test]
+fn test_deposit_batch_funds_each_with_one_transfer() {
+    let (env, client, token, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    mint(&env, &token, &subscriber);
+    let ids: [u32; 3] = core::array::from_fn(|_| {
+        client.create_subscription(
+            &subscriber,
+            &Address::generate(&env),
+            &1000i128,
+            &INTERVAL,
+            &false,
+            &None,
+            &0,
+            &0,
+        )
+    });
+    let token_client = TokenClient::new(&env, &token);
+    let before = token_client.balance(&subscriber);
+
+    let amounts = [1_000000i128, 2_000000, 3_000000];
+    client.deposit_batch(
+        &Vec::from_array(&env, ids),
+        &subscriber,
+        &Vec::from_array(&env, amounts),
+    );
+    let token_transfers = env
+        .events()
+        .all()
+        .iter()
+        .filter(|(contract, _, _)| *contract == token)
+        .count();
+    assert_eq!(token_transfers, 1);
+    for (id, amount) in ids.iter().zip(amounts) {
+        assert_eq!(client.get_subscription(id).prepaid_balance, amount);
+    }
+    assert_eq!(token_client.balance(&subscriber), before - 6_000000);
+}
+
+#[test]
+fn test_deposit_batch_rejects_bad_entries() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let (other_id, _, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let amounts = Vec::from_array(&env, [1_000000i128, 1_000000]);
+
+    assert_eq!(
+        client.try_deposit_batch(&Vec::from_array(&env, [id]), &subscriber, &amounts),
+        Err(Ok(Error::InvalidAmount))
+    );
+    assert_eq!(
+        client.try_deposit_batch(
+            &Vec::from_array(&env, [id, other_id]),
+            &subscriber,
+            &amounts
+        ),
+        Err(Ok(Error::Unauthorized))
+    );
+    assert_eq!(
+        client.try_deposit_batch(&Vec::from_array(&env, [id, 999]), &subscriber, &amounts),
+        Err(Ok(Error::NotFound))
+    );
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 0);
+}
+
+// =============================================================================
+// Prepaid withdrawals
+// =============================================================================
+
+#[test]
+fn test_withdraw_prepaid_partial() {
+    let (env, client, token, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &10_000000i128, &None);
+    let token_client = TokenClient::new(&env, &token);
+    let before = token_client.balance(&subscriber);
+
+    client.withdraw_prepaid(&id, &subscriber, &4_000000i128);
+    let event: PrepaidWithdrawnEvent = last_event_data(&env);
+    assert_eq!(event.subscriber, subscriber);
+    assert_eq!(event.amount, 4_000000);
+    assert_eq!(event.new_balance, 6_000000);
+
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.prepaid_balance, 6_000000);
+    assert_eq!(sub.status, SubscriptionStatus::Active);
+    assert_eq!(client.total_prepaid(&token), 6_000000);
+    assert_eq!(token_client.balance(&subscriber), before + 4_000000);
+}
+
+#[test]
+fn test_withdraw_prepaid_rejects_over_withdrawal_and_outsiders() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &10_000000i128, &None);
+
+    assert_eq!(
+        client.try_withdraw_prepaid(&id, &subscriber, &10_000001i128),
+        Err(Ok(Error::InsufficientBalance))
+    );
+    assert_eq!(
+        client.try_withdraw_prepaid(&id, &merchant, &1i128),
+        Err(Ok(Error::Unauthorized))
+    );
+    assert_eq!(
+        client.try_withdraw_prepaid(&id, &subscriber, &0i128),
+        Err(Ok(Error::InvalidAmount))
+    );
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 10_000000);
+}
+
+// =============================================================================
+// Merchant registration
+// =============================================================================
+
+fn setup_with_registration() -> (Env, SubscriptionVaultClient<'static>) {
+    let env = Env::default();
+    env.mock_all_auths();
+    let client = SubscriptionVaultClient::new(&env, &env.register(SubscriptionVault, ()));
+    client.init(
+        &create_token(&env),
+        &Address::generate(&env),
+        &1_000000i128,
+        &true,
+    );
+    (env, client)
+}
+
+#[test]
+fn test_registered_merchant_can_receive_subscriptions() {
+    let (env, client) = setup_with_registration();
+    let merchant = Address::generate(&env);
+    assert!(!client.is_merchant_registered(&merchant));
+    client.register_merchant(&merchant);
+    assert!(client.is_merchant_registered(&merchant));
+
+    let id = client.create_subscription(
+        &Address::generate(&env),
+        &merchant,
+        &1000i128,
+        &INTERVAL,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    assert_eq!(client.get_subscription(&id).merchant, merchant);
+}
+
+#[test]
+fn test_unregistered_merchant_is_rejected() {
+    let (env, client) = setup_with_registration();
+    let res = client.try_create_subscription(
+        &Address::generate(&env),
+        &Address::generate(&env),
+        &1000i128,
+        &INTERVAL,
+        &false,
+        &None,
+        &0,
+        &0,
+    );
+    assert_eq!(res, Err(Ok(Error::MerchantNotRegistered)));
+    assert_eq!(Error::MerchantNotRegistered.to_code(), 416);
+}
+
+#[test]
+fn test_max_subs_per_subscriber() {
+    let (env, client, _, admin) = setup_test_env();
+    client.set_max_subs_per_subscriber(&admin, &2);
+    assert_eq!(client.get_max_subs_per_subscriber(), 2);
+    let subscriber = Address::generate(&env);
+    let create = || {
+        client.try_create_subscription(
+            &subscriber,
+            &Address::generate(&env),
+            &1000i128,
+            &INTERVAL,
+            &false,
+            &None,
+            &0,
+            &0,
+        )
+    };
+    let first = create().unwrap().unwrap();
+    create().unwrap().unwrap();
+    assert_eq!(create(), Err(Ok(Error::TooManySubscriptions)));
+    assert_eq!(Error::TooManySubscriptions.to_code(), 417);
+
+    // Cancelled subscriptions do not count against the cap.
+    client.cancel_subscription(&first, &subscriber);
+    assert!(create().is_ok());
+    assert_eq!(
+        client.try_set_max_subs_per_subscriber(&subscriber, &0),
+        Err(Ok(Error::Unauthorized))
+    );
+}
+
+#[test]
+fn test_merchant_allowed_intervals() {
+    let (env, client, _, _) = setup_test_env();
+    let merchant = Address::generate(&env);
+    let monthly = 2_592_000u64;
+    let annual = 31_536_000u64;
+    client.set_allowed_intervals(&merchant, &Vec::from_array(&env, [monthly, annual]));
+    assert_eq!(client.get_allowed_intervals(&merchant).len(), 2);
+
+    let create = |interval: u64| {
+        client.try_create_subscription(
+            &Address::generate(&env),
+            &merchant,
+            &1000i128,
+            &interval,
+            &false,
+            &None,
+            &0,
+            &0,
+        )
+    };
+    assert!(create(monthly).is_ok());
+    assert!(create(annual).is_ok());
+    assert_eq!(create(604_800), Err(Ok(Error::IntervalNotAllowed)));
+    assert_eq!(Error::IntervalNotAllowed.to_code(), 451);
+
+    // Clearing the set accepts any interval again.
+    client.set_allowed_intervals(&merchant, &Vec::new(&env));
+    assert!(create(604_800).is_ok());
+}
+
+#[test]
+fn test_blocked_subscriber_cannot_subscribe_until_unblocked() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let create = |subscriber: &Address| {
+        client.try_create_subscription(
+            subscriber, &merchant, &1000i128, &INTERVAL, &false, &None, &0, &0,
+        )
+    };
+
+    client.block_subscriber(&merchant, &subscriber);
+    assert!(client.is_subscriber_blocked(&merchant, &subscriber));
+    assert_eq!(create(&subscriber), Err(Ok(Error::SubscriberBlocked)));
+    assert_eq!(Error::SubscriberBlocked.to_code(), 423);
+    // Only this pair is blocked, and the existing subscription is untouched.
+    assert!(create(&Address::generate(&env)).is_ok());
+    assert_eq!(
+        client.get_subscription(&id).status,
+        SubscriptionStatus::Active
+    );
+
+    client.unblock_subscriber(&merchant, &subscriber);
+    assert!(!client.is_subscriber_blocked(&merchant, &subscriber));
+    assert!(create(&subscriber).is_ok());
+}
+
+// =============================================================================
+// Yield adapter
+// =============================================================================
+
+/// Adapter that mints one share per token unit and returns funds on request.
+#[contract]
+struct MockYieldAdapter;
+
+#[contractimpl]
+impl MockYieldAdapter {
+    pub fn deposit(_env: Env, _depositor: Address, _token: Address, amount: i128) -> i128 {
+        amount
+    }
+
+    pub fn withdraw(env: Env, depositor: Address, token: Address, amount: i128) -> i128 {
+        TokenClient::new(&env, &token).transfer(
+            &env.current_contract_address(),
+            &depositor,
+            &amount,
+        );
+        amount
+    }
+}
+
+#[test]
+fn test_yield_adapter_receives_deposits_and_returns_charges() {
+    let (env, client, token, admin) = setup_test_env();
+    let adapter = env.register(MockYieldAdapter, ());
+    client.set_yield_adapter(&admin, &Some(adapter.clone()));
+    assert_eq!(client.get_yield_adapter(), Some(adapter.clone()));
+    let tokens = TokenClient::new(&env, &token);
+
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &30_000000i128, &None);
+    assert_eq!(tokens.balance(&adapter), 30_000000);
+    assert_eq!(tokens.balance(&client.address), 0);
+    assert_eq!(client.get_yield_position(&token), (30_000000, 30_000000));
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 30_000000);
+
+    // A charge pulls the charged amount back so the merchant can withdraw it.
+    env.ledger()
+        .set_timestamp(client.next_charge_timestamp(&id));
+    client.charge_subscription(&id, &None);
+    assert_eq!(tokens.balance(&adapter), 20_000000);
+    assert_eq!(tokens.balance(&client.address), 10_000000);
+    assert_eq!(client.get_yield_position(&token), (20_000000, 20_000000));
+    client.withdraw_merchant_funds(&merchant, &10_000000i128);
+    assert_eq!(tokens.balance(&merchant), 10_000000);
+
+    // The cancel refund is pulled back too.
+    client.cancel_subscription(&id, &subscriber);
+    assert_eq!(tokens.balance(&adapter), 0);
+    assert_eq!(client.get_yield_position(&token), (0, 0));
+}
+
+#[test]
+fn test_clearing_yield_adapter_withdraws_principal() {
+    let (env, client, token, admin) = setup_test_env();
+    let adapter = env.register(MockYieldAdapter, ());
+    client.set_yield_adapter(&admin, &Some(adapter.clone()));
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &30_000000i128, &None);
+
+    assert_eq!(
+        client.try_set_yield_adapter(&subscriber, &None),
+        Err(Ok(Error::Unauthorized))
+    );
+    client.set_yield_adapter(&admin, &None);
+    let tokens = TokenClient::new(&env, &token);
+    assert_eq!(tokens.balance(&adapter), 0);
+    assert_eq!(tokens.balance(&client.address), 30_000000);
+    assert_eq!(client.get_yield_position(&token), (0, 0));
+    assert_eq!(client.get_yield_adapter(), None);
+}
+
+#[test]
+fn test_cancel_and_resubscribe_reparks_refund_with_yield_adapter() {
+    let (env, client, token, admin) = setup_test_env();
+    let adapter = env.register(MockYieldAdapter, ());
+    client.set_yield_adapter(&admin, &Some(adapter.clone()));
+    let (old_id, subscriber, _) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&old_id, &subscriber, &30_000000i128, &None);
+
+    client.cancel_and_resubscribe(
+        &old_id,
+        &subscriber,
+        &Address::generate(&env),
+        &5_000000i128,
+        &INTERVAL,
+    );
+    let tokens = TokenClient::new(&env, &token);
+    assert_eq!(tokens.balance(&adapter), 30_000000);
+    assert_eq!(tokens.balance(&client.address), 0);
+    assert_eq!(client.get_yield_position(&token), (30_000000, 30_000000));
+}
+
+// =============================================================================
+// Swap adapter
+// =============================================================================
+
+/// Adapter that pays out two units of `token_out` per unit in, from its own balance, and leaves
+/// the slippage check to the vault.
+#[contract]
+struct MockSwapAdapter;
+
+#[contractimpl]
+impl MockSwapAdapter {
+    pub fn swap(
+        env: Env,
+        _token_in: Address,
+        amount_in: i128,
+        token_out: Address,
+        _min_out: i128,
+        to: Address,
+    ) -> i128 {
+        let amount_out = amount_in * 2;
+        TokenClient::new(&env, &token_out).transfer(
+            &env.current_contract_address(),
+            &to,
+            &amount_out,
+        );
+        amount_out
+    }
+}
+
+/// Merchant with a 10 USDC charge accrued, and a swap adapter holding `token_out`.
+fn setup_swap_payout() -> (
+    Env,
+    SubscriptionVaultClient<'static>,
+    Address,
+    Address,
+    Address,
+) {
+    let (env, client, token, admin) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &10_000000i128, &None);
+    env.ledger()
+        .set_timestamp(env.ledger().timestamp() + client.get_subscription(&id).interval_seconds);
+    client.charge_subscription(&id, &None);
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 10_000000);
+
+    let adapter = env.register(MockSwapAdapter, ());
+    let token_out = create_token(&env);
+    mint(&env, &token_out, &adapter);
+    client.set_swap_adapter(&admin, &Some(adapter.clone()));
+    (env, client, merchant, token_out, adapter)
+}
+
+#[test]
+fn test_withdraw_merchant_funds_as_pays_out_swapped_token() {
+    let (env, client, merchant, token_out, adapter) = setup_swap_payout();
+    let token = client.get_token();
+
+    assert_eq!(
+        client.withdraw_merchant_funds_as(&merchant, &4_000000i128, &token_out, &7_000000i128),
+        8_000000
+    );
+    let event: PayoutSwappedEvent = last_event_data(&env);
+    assert_eq!((event.amount_in, event.amount_out), (4_000000, 8_000000));
+    assert_eq!(event.token_out, token_out);
+    assert_eq!(
+        TokenClient::new(&env, &token_out).balance(&merchant),
+        8_000000
+    );
+    assert_eq!(TokenClient::new(&env, &token).balance(&adapter), 4_000000);
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 6_000000);
+}
+
+#[test]
+fn test_withdraw_merchant_funds_as_reverts_on_slippage() {
+    let (env, client, merchant, token_out, _) = setup_swap_payout();
+    assert_eq!(
+        client.try_withdraw_merchant_funds_as(&merchant, &4_000000i128, &token_out, &8_000001i128),
+        Err(Ok(Error::SlippageExceeded))
+    );
+    assert_eq!(Error::SlippageExceeded.to_code(), 428);
+    assert_eq!(
+        client.get_merchant_balance(&merchant, &client.get_token()),
+        10_000000
+    );
+    assert_eq!(TokenClient::new(&env, &token_out).balance(&merchant), 0);
+
+    client.set_swap_adapter(&client.get_admin(), &None);
+    assert_eq!(
+        client.try_withdraw_merchant_funds_as(&merchant, &1i128, &token_out, &0),
+        Err(Ok(Error::NotInitialized))
+    );
+}
+
+// =============================================================================
+// Usage charges
+// =============================================================================
+
+fn create_usage_subscription(
+    env: &Env,
+    client: &SubscriptionVaultClient,
+    min_charge: i128,
+) -> (u32, Address, Address) {
+    let subscriber = Address::generate(env);
+    let merchant = Address::generate(env);
+    let params = SubscriptionParams::new(subscriber.clone(), merchant.clone(), 10_000000, INTERVAL)
+        .unwrap()
+        .with_usage_enabled(true)
+        .with_min_charge(min_charge)
+        .unwrap();
+    let id = client.create_subscription_from_params(&params);
+    mint(env, &client.get_subscription(&id).token, &subscriber);
+    client.deposit_funds(&id, &subscriber, &10_000000i128, &None);
+    (id, subscriber, merchant)
+}
+
+#[test]
+fn test_charge_usage_accrues_below_min_charge() {
+    let (env, client, token, _) = setup_test_env();
+    let (id, _, merchant) = create_usage_subscription(&env, &client, 1_000);
+
+    assert_eq!(client.charge_usage(&id, &merchant, &300), 0);
+    assert_eq!(client.charge_usage(&id, &merchant, &300), 0);
+    assert_eq!(client.charge_usage(&id, &merchant, &300), 0);
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.accrued_usage, 900);
+    assert_eq!(sub.prepaid_balance, 10_000000);
+
+    // The fourth report crosses the threshold and the whole accrual is deducted.
+    assert_eq!(client.charge_usage(&id, &merchant, &300), 1_200);
+    let event = last_event_data::<UsageChargedEvent>(&env);
+    assert_eq!(event.amount, 1_200);
+    assert_eq!(event.remaining_balance, 10_000000 - 1_200);
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.accrued_usage, 0);
+    assert_eq!(sub.prepaid_balance, 10_000000 - 1_200);
+    assert_eq!(sub.total_charged, 1_200);
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 1_200);
+}
+
+#[test]
+fn test_charge_usage_without_min_charge_deducts_immediately() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, _, merchant) = create_usage_subscription(&env, &client, 0);
+    assert_eq!(client.charge_usage(&id, &merchant, &7), 7);
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 10_000000 - 7);
+}
+
+#[test]
+fn test_charge_usage_rejects_invalid_calls() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, _, merchant) = create_usage_subscription(&env, &client, 0);
+    assert_eq!(
+        client.try_charge_usage(&id, &Address::generate(&env), &7),
+        Err(Ok(Error::Unauthorized))
+    );
+    assert_eq!(
+        client.try_charge_usage(&id, &merchant, &0),
+        Err(Ok(Error::InvalidAmount))
+    );
+    assert_eq!(
+        client.try_charge_usage(&id, &merchant, &20_000000),
+        Err(Ok(Error::InsufficientBalance))
+    );
+
+    let (flat, _, flat_merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    assert_eq!(
+        client.try_charge_usage(&flat, &flat_merchant, &7),
+        Err(Ok(Error::UsageNotEnabled))
+    );
+    assert!(matches!(
+        SubscriptionParams::new(Address::generate(&env), merchant, 1, INTERVAL)
+            .unwrap()
+            .with_min_charge(-1),
+        Err(Error::InvalidAmount)
+    ));
+}
+
+/// Usage subscription priced at `rate` millionths of a base unit per unit.
+fn create_priced_usage_subscription(
+    env: &Env,
+    client: &SubscriptionVaultClient,
+    rate: i128,
+    rounding: UsageRounding,
+) -> (u32, Address) {
+    let subscriber = Address::generate(env);
+    let merchant = Address::generate(env);
+    let params = SubscriptionParams::new(subscriber.clone(), merchant.clone(), 10_000000, INTERVAL)
+        .unwrap()
+        .with_usage_enabled(true)
+        .with_usage_rate(rate, rounding)
+        .unwrap();
+    let id = client.create_subscription_from_params(&params);
+    mint(env, &client.get_subscription(&id).token, &subscriber);
+    client.deposit_funds(&id, &subscriber, &10_000000i128, &None);
+    (id, merchant)
+}
+
+#[test]
+fn test_usage_rounding_modes() {
+    let (env, client, _, _) = setup_test_env();
+    // 7 units at 2.5 base units each = 17.5.
+    let expected = [
+        (UsageRounding::Floor, 17),
+        (UsageRounding::Ceil, 18),
+        (UsageRounding::Nearest, 18),
+    ];
+    for (rounding, charge) in expected {
+        let (id, merchant) = create_priced_usage_subscription(&env, &client, 2_500_000, rounding);
+        assert_eq!(client.preview_usage_charge(&id, &7), charge);
+        assert_eq!(client.charge_usage_units(&id, &merchant, &7), charge);
+        assert_eq!(
+            client.get_subscription(&id).prepaid_balance,
+            10_000000 - charge
+        );
+    }
+
+    // Nearest rounds 7.2 down and 7.8 up.
+    let (id, _) =
+        create_priced_usage_subscription(&env, &client, 2_400_000, UsageRounding::Nearest);
+    assert_eq!(client.preview_usage_charge(&id, &3), 7);
+    let (id, _) =
+        create_priced_usage_subscription(&env, &client, 2_600_000, UsageRounding::Nearest);
+    assert_eq!(client.preview_usage_charge(&id, &3), 8);
+}
+
+#[test]
+fn test_usage_units_require_rate() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, _, merchant) = create_usage_subscription(&env, &client, 0);
+    assert_eq!(
+        client.try_charge_usage_units(&id, &merchant, &1),
+        Err(Ok(Error::InvalidAmount))
+    );
+    let (priced, _) =
+        create_priced_usage_subscription(&env, &client, 400_000, UsageRounding::Floor);
+    // 0.4 rounds down to nothing.
+    assert_eq!(client.try_preview_usage_charge(&priced, &1), Ok(Ok(0)));
+    assert_eq!(
+        client.try_preview_usage_charge(&priced, &0),
+        Err(Ok(Error::InvalidAmount))
+    );
+    assert!(matches!(
+        SubscriptionParams::new(Address::generate(&env), merchant, 1, INTERVAL)
+            .unwrap()
+            .with_usage_rate(-1, UsageRounding::Ceil),
+        Err(Error::InvalidAmount)
+    ));
+}
+
+// =============================================================================
+// Authorization expiry
+// =============================================================================
+
+#[test]
+fn test_charge_requires_unexpired_authorization() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &50_000000i128, &None);
+    let interval = client.get_subscription(&id).interval_seconds;
+    let start = env.ledger().timestamp();
+    client.renew_authorization(&id, &subscriber, &(start + interval + 1));
+    assert_eq!(client.get_authorization_expiry(&id), start + interval + 1);
+
+    // Within the window.
+    env.ledger().set_timestamp(start + interval);
+    client.charge_subscription(&id, &None);
+
+    // After the expiry the next due charge is refused.
+    env.ledger().set_timestamp(start + 2 * interval);
+    assert_eq!(client.can_charge(&id), (false, 420));
+    assert_eq!(
+        client.try_charge_subscription(&id, &None),
+        Err(Ok(Error::AuthorizationExpired))
+    );
+
+    // Renewing restores charging.
+    client.renew_authorization(&id, &subscriber, &(start + 3 * interval));
+    client.charge_subscription(&id, &None);
+    assert_eq!(client.get_subscription(&id).cycles_charged, 2);
+}
+
+#[test]
+fn test_renew_authorization_validation() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    env.ledger().set_timestamp(1_000);
+    assert_eq!(
+        client.try_renew_authorization(&id, &merchant, &2_000),
+        Err(Ok(Error::Unauthorized))
+    );
+    assert_eq!(
+        client.try_renew_authorization(&id, &subscriber, &1_000),
+        Err(Ok(Error::AuthorizationExpired))
+    );
+    client.renew_authorization(&id, &subscriber, &2_000);
+    client.renew_authorization(&id, &subscriber, &0);
+    assert_eq!(client.get_authorization_expiry(&id), 0);
+}
+
+// =============================================================================
+// Deposit and charge
+// =============================================================================
+
+#[test]
+fn test_deposit_and_charge_pays_due_charge() {
+    let (env, client, token, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    env.ledger()
+        .set_timestamp(client.next_charge_timestamp(&id));
+
+    client.deposit_and_charge(&id, &subscriber, &15_000000i128);
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.prepaid_balance, 5_000000);
+    assert_eq!(sub.last_payment_timestamp, env.ledger().timestamp());
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 10_000000);
+}
+
+#[test]
+fn test_deposit_and_charge_reverts_when_underfunded() {
+    let (env, client, token, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    env.ledger()
+        .set_timestamp(client.next_charge_timestamp(&id));
+    let wallet = TokenClient::new(&env, &token).balance(&subscriber);
+
+    assert_eq!(
+        client.try_deposit_and_charge(&id, &subscriber, &5_000000i128),
+        Err(Ok(Error::InsufficientBalance))
+    );
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.prepaid_balance, 0);
+    assert_eq!(sub.failed_charge_count, 0);
+    assert_eq!(TokenClient::new(&env, &token).balance(&subscriber), wallet);
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 0);
+
+    // Only the subscriber can use the combined call.
+    assert_eq!(
+        client.try_deposit_and_charge(&id, &merchant, &15_000000i128),
+        Err(Ok(Error::Unauthorized))
+    );
+}
+
+// =============================================================================
+// Setup fee
+// =============================================================================
+
+#[test]
+fn test_setup_fee_charged_from_initial_deposit_at_creation() {
+    let (env, client, token, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    mint(&env, &token, &subscriber);
+    let params = SubscriptionParams::new(subscriber.clone(), merchant.clone(), 10_000000, INTERVAL)
+        .unwrap()
+        .with_initial_deposit(30_000000)
+        .unwrap()
+        .with_setup_fee(5_000000)
+        .unwrap();
+    let id = client.create_subscription_from_params(&params);
+
+    let event = last_event_data::<SetupFeeChargedEvent>(&env);
+    assert_eq!(event.subscription_id, id);
+    assert_eq!(event.merchant, merchant);
+    assert_eq!(event.amount, 5_000000);
+    assert_eq!(event.remaining_balance, 25_000000);
+
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.prepaid_balance, 25_000000);
+    assert_eq!(sub.total_charged, 5_000000);
+    assert_eq!(client.get_merchant_balance(&merchant, &token), 5_000000);
+
+    // The recurring cycle is unaffected: the first interval charge is due one interval later.
+    assert_eq!(client.next_charge_timestamp(&id), sub.created_at + INTERVAL);
+    env.ledger().set_timestamp(sub.created_at + INTERVAL);
+    client.charge_subscription(&id, &None);
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 15_000000);
+}
+
+#[test]
+fn test_setup_fee_requires_covering_initial_deposit() {
+    let env = Env::default();
+    let params = SubscriptionParams::new(
+        Address::generate(&env),
+        Address::generate(&env),
+        10_000000,
+        INTERVAL,
+    )
+    .unwrap();
+    assert!(matches!(
+        params.clone().with_setup_fee(1),
+        Err(Error::InvalidAmount)
+    ));
+    assert!(matches!(
+        params.with_initial_deposit(-1),
+        Err(Error::InvalidAmount)
+    ));
+}
+
+// =============================================================================
+// Storage TTL
+// =============================================================================
+
+fn storage_ttl(env: &Env, client: &SubscriptionVaultClient) -> u32 {
+    env.as_contract(&client.address, || env.storage().instance().get_ttl())
+}
+
+#[test]
+fn test_access_extends_storage_ttl() {
+    let (env, client, _, admin) = setup_test_env();
+    assert_eq!(client.get_ttl_config(), (518_400, 3_110_400));
+    assert_eq!(storage_ttl(&env, &client), 3_110_400);
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+
+    // Extend whenever less than the full TTL is left, so short ledger advances are visible.
+    client.set_ttl_config(&admin, &3_110_400, &3_110_400);
+    let ledger = env.ledger().sequence();
+    env.ledger().set_sequence_number(ledger + 1_000);
+    assert_eq!(storage_ttl(&env, &client), 3_109_400);
+    client.deposit_funds(&id, &subscriber, &10_000000i128, &None);
+    assert_eq!(storage_ttl(&env, &client), 3_110_400);
+
+    // Nearly archived: a read keeps it live.
+    client.set_ttl_config(&admin, &10_000, &20_000);
+    env.ledger().set_sequence_number(ledger + 1_000 + 3_105_000);
+    assert_eq!(storage_ttl(&env, &client), 5_400);
+    client.get_subscription(&id);
+    assert_eq!(storage_ttl(&env, &client), 20_000);
+}
+
+#[test]
+fn test_set_ttl_config_validation() {
+    let (env, client, _, admin) = setup_test_env();
+    assert_eq!(
+        client.try_set_ttl_config(&Address::generate(&env), &1, &2),
+        Err(Ok(Error::Unauthorized))
+    );
+    assert_eq!(
+        client.try_set_ttl_config(&admin, &3, &2),
+        Err(Ok(Error::InvalidAmount))
+    );
+    assert_eq!(
+        client.try_set_ttl_config(&admin, &0, &0),
+        Err(Ok(Error::InvalidAmount))
+    );
+}
+
+// =============================================================================
+// Prepaid credits
+// =============================================================================
+
+#[test]
+fn test_buy_credits_and_consume_one_per_charge() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let sub = client.get_subscription(&id);
+    let token = TokenClient::new(&env, &sub.token);
+    let wallet_before = token.balance(&sub.subscriber);
+
+    assert_eq!(client.buy_credits(&id, &sub.subscriber, &2), 2);
+    let event: CreditsPurchasedEvent = last_event_data(&env);
+    assert_eq!((event.count, event.cost, event.credits), (2, 2000, 2));
+    assert_eq!(token.balance(&sub.subscriber), wallet_before - 2000);
+
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    client.charge_subscription(&id, &None);
+    let after = client.get_subscription(&id);
+    assert_eq!(after.credits, 1);
+    assert_eq!(after.prepaid_balance, 10_000000);
+    assert_eq!(after.total_charged, 1000);
+    assert_eq!(client.get_merchant_balance(&sub.merchant, &sub.token), 1000);
+
+    env.ledger().set_timestamp(T0 + 2 * INTERVAL);
+    client.charge_subscription(&id, &None);
+    assert_eq!(client.get_subscription(&id).credits, 0);
+
+    // Out of credits: the balance is not used instead.
+    env.ledger().set_timestamp(T0 + 3 * INTERVAL);
+    assert_eq!(
+        client.try_charge_subscription(&id, &None),
+        Err(Ok(Error::InsufficientBalance))
+    );
+    assert_eq!(
+        client.can_charge(&id),
+        (false, Error::InsufficientBalance.to_code())
+    );
+    let sub = client.get_subscription(&id);
+    assert_eq!(sub.prepaid_balance, 10_000000);
+    assert_eq!(sub.total_charged, 2000);
+}
+
+#[test]
+fn test_cancel_refunds_unused_credits() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let sub = client.get_subscription(&id);
+    let token = TokenClient::new(&env, &sub.token);
+    client.buy_credits(&id, &sub.subscriber, &3);
+    env.ledger().set_timestamp(T0 + INTERVAL);
+    client.charge_subscription(&id, &None);
+
+    assert_eq!(client.preview_cancel(&id), 10_000000 + 2000);
+    let wallet_before = token.balance(&sub.subscriber);
+    client.cancel_subscription(&id, &sub.subscriber);
+    assert_eq!(
+        token.balance(&sub.subscriber),
+        wallet_before + 10_000000 + 2000
+    );
+    let sub = client.get_subscription(&id);
+    assert_eq!((sub.credits, sub.prepaid_balance), (0, 0));
+}
+
+#[test]
+fn test_buy_credits_validation() {
+    let env = Env::default();
+    let (client, id) = setup(&env, INTERVAL);
+    let subscriber = client.get_subscription(&id).subscriber;
+    assert_eq!(
+        client.try_buy_credits(&id, &Address::generate(&env), &1),
+        Err(Ok(Error::Unauthorized))
+    );
+    assert_eq!(
+        client.try_buy_credits(&id, &subscriber, &0),
+        Err(Ok(Error::InvalidAmount))
+    );
+}
+
+// =============================================================================
+// Contract stats
+// =============================================================================
+
+#[test]
+fn test_stats_track_lifecycle_and_charges() {
+    let (env, client, _, _) = setup_test_env();
+    let empty = client.stats();
+    assert_eq!(
+        (empty.total_created, empty.active, empty.charges),
+        (0, 0, 0)
+    );
+
+    let (a, subscriber_a, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let (b, subscriber_b, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    let (c, subscriber_c, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&a, &subscriber_a, &50_000000i128, &None);
+    client.deposit_funds(&b, &subscriber_b, &50_000000i128, &None);
+
+    env.ledger()
+        .set_timestamp(env.ledger().timestamp() + client.get_subscription(&a).interval_seconds);
+    client.charge_subscription(&a, &None);
+    client.charge_subscription(&b, &None);
+    client.cancel_subscription(&c, &subscriber_c);
+    client.pause_subscription(&a, &subscriber_a);
+
+    assert_eq!(
+        client.stats(),
+        VaultStats {
+            total_created: 3,
+            active: 1,
+            cancelled: 1,
+            charges: 2,
+            volume: 20_000000,
+        }
+    );
+
+    client.resume_subscription(&a, &subscriber_a);
+    assert_eq!(client.stats().active, 2);
+}
+
+#[test]
+fn test_stats_charges_vs_total_charges() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, merchant) = create_usage_subscription(&env, &client, 0);
+    client.deposit_funds(&id, &subscriber, &50_000000i128, &None);
+    env.ledger()
+        .set_timestamp(client.next_charge_timestamp(&id));
+    client.charge_subscription(&id, &None);
+    client.charge_usage(&id, &merchant, &500);
+    assert_eq!(client.get_total_charges(), 2);
+    assert_eq!(client.stats().charges, 2);
+
+    // One-off charges count in the stats only.
+    client.charge_one_off(&id, &merchant, &1_000);
+    assert_eq!(client.get_total_charges(), 2);
+    let stats = client.stats();
+    assert_eq!(stats.charges, 3);
+    assert_eq!(stats.volume, 10_000000 + 500 + 1_000);
+}
+
+// =============================================================================
+// Signed amount validation
+// =============================================================================
+
+#[test]
+fn test_create_subscription_negative_amount_rejected() {
+    let (env, client, _, _) = setup_test_env();
+    let res = client.try_create_subscription(
+        &Address::generate(&env),
+        &Address::generate(&env),
+        &-1i128,
+        &INTERVAL,
+        &false,
+        &None,
+        &0i128,
+        &0i128,
+    );
+    assert_eq!(res, Err(Ok(Error::InvalidAmount)));
+}
+
+#[test]
+fn test_deposit_non_positive_rejected_without_min_topup() {
+    let (env, client, _, admin) = setup_test_env();
+    let (id, subscriber, _) = create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    // With no minimum top-up the amount checks are the only guard.
+    client.set_min_topup(&admin, &0);
+    for amount in [0i128, -1, -10_000000] {
+        assert_eq!(
+            client.try_deposit_funds(&id, &subscriber, &amount, &None),
+            Err(Ok(Error::InvalidAmount))
+        );
+        assert_eq!(
+            client.try_deposit_for(&id, &subscriber, &amount),
+            Err(Ok(Error::InvalidAmount))
+        );
+        assert_eq!(
+            client.try_deposit_and_charge(&id, &subscriber, &amount),
+            Err(Ok(Error::InvalidAmount))
+        );
+    }
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 0);
+}
+
+#[test]
+fn test_negative_min_topup_rejected() {
+    let (env, client, token, admin) = setup_test_env();
+    assert_eq!(
+        client.try_set_min_topup(&admin, &-1),
+        Err(Ok(Error::InvalidAmount))
+    );
+    let fresh = SubscriptionVaultClient::new(&env, &env.register(SubscriptionVault, ()));
+    assert_eq!(
+        fresh.try_init(&token, &admin, &-1, &false),
+        Err(Ok(Error::InvalidAmount))
+    );
+}
+
+#[test]
+fn test_charge_paths_non_positive_amount_rejected() {
+    let (env, client, _, _) = setup_test_env();
+    let subscriber = Address::generate(&env);
+    let merchant = Address::generate(&env);
+    mint(&env, &client.get_token(), &subscriber);
+    let id = client.create_subscription(
+        &subscriber,
+        &merchant,
+        &1_000000i128,
+        &INTERVAL,
+        &true,
+        &None,
+        &0i128,
+        &0i128,
+    );
+    client.deposit_funds(&id, &subscriber, &10_000000i128, &None);
+    for amount in [0i128, -1] {
+        assert_eq!(
+            client.try_charge_one_off(&id, &merchant, &amount),
+            Err(Ok(Error::InvalidAmount))
+        );
+        assert_eq!(
+            client.try_charge_usage(&id, &merchant, &amount),
+            Err(Ok(Error::InvalidAmount))
+        );
+    }
+    assert_eq!(client.get_subscription(&id).prepaid_balance, 10_000000i128);
+}
+
+#[test]
+fn test_import_non_positive_amount_rejected() {
+    let (env, old, token, admin) = setup_test_env();
+    let (id, _, _) = create_test_subscription(&env, &old, SubscriptionStatus::Active);
+    let new = SubscriptionVaultClient::new(&env, &env.register(SubscriptionVault, ()));
+    new.init(&token, &admin, &1_000000i128, &false);
+
+    let mut sub = old.export_subscription(&admin, &id);
+    sub.amount = 0;
+    assert_eq!(
+        new.try_import_subscription(&admin, &sub),
+        Err(Ok(Error::InvalidAmount))
+    );
+    sub.amount = 1000;
+    sub.prepaid_balance = -1;
+    assert_eq!(
+        new.try_import_subscription(&admin, &sub),
+        Err(Ok(Error::InvalidAmount))
+    );
+}
+
+#[test]
+fn test_import_zero_interval_rejected() {
+    let (env, old, token, admin) = setup_test_env();
+    let (id, _, _) = create_test_subscription(&env, &old, SubscriptionStatus::Active);
+    let new = SubscriptionVaultClient::new(&env, &env.register(SubscriptionVault, ()));
+    new.init(&token, &admin, &1_000000i128, &false);
+
+    let mut sub = old.export_subscription(&admin, &id);
+    sub.interval_seconds = 0;
+    assert_eq!(
+        new.try_import_subscription(&admin, &sub),
+        Err(Ok(Error::InvalidInterval))
+    );
+}
+
+#[test]
+fn test_withdraw_merchant_funds_non_positive_rejected() {
+    let (env, client, _, _) = setup_test_env();
+    let (id, subscriber, merchant) =
+        create_test_subscription(&env, &client, SubscriptionStatus::Active);
+    client.deposit_funds(&id, &subscriber, &20_000000i128, &None);
+    env.ledger()
+        .set_timestamp(env.ledger().timestamp() + INTERVAL);
+    client.charge_subscription(&id, &None);
+    for amount in [0i128, -1] {
+        assert_eq!(
+            client.try_withdraw_merchant_funds(&merchant, &amount),
+            Err(Ok(Error::InvalidAmount))
+        );
+    }
+    assert_eq!(
+        client.get_merchant_balance(&merchant, &client.get_token()),
+        10_000000i128
+    );
+}
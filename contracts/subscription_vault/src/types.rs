@@ -3,7 +3,7 @@
 //! Kept in a separate module to reduce merge conflicts when editing state machine
 //! or contract entrypoints.
 
-use soroban_sdk::{contracterror, contracttype, Address};
+use soroban_sdk::{contracterror, contracttype, Address, Bytes, String, Symbol};
 
 #[contracterror]
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -25,6 +25,42 @@ pub enum Error {
     Replay = 1004,
     /// One-off or other operation used an invalid amount (e.g. non-positive).
     InvalidAmount = 1005,
+    /// Deposit is below the subscription's `min_deposit`.
+    DepositTooSmall = 411,
+    /// Charge would push `total_charged` above the subscription's `max_total_charge`.
+    SpendingCapReached = 412,
+    /// Charge attempted before the subscription's `start_timestamp`.
+    NotStarted = 414,
+    /// Merchant has not called `register_merchant` and the instance requires registration.
+    MerchantNotRegistered = 416,
+    /// Subscriber already has `max_subs_per_subscriber` non-cancelled subscriptions.
+    TooManySubscriptions = 417,
+    /// Read of a value set by `init` before `init` has run.
+    NotInitialized = 408,
+    /// `init` called on a contract that is already initialized.
+    AlreadyInitialized = 407,
+    /// `import_subscription` called after subscriptions were created on this instance.
+    NotEmpty = 409,
+    /// `dispute_charge` called while the subscription already has an open dispute.
+    DisputeOpen = 426,
+    /// Interval is not in the merchant's allowed set (see `set_allowed_intervals`).
+    IntervalNotAllowed = 451,
+    /// Deposit attempted while a `cancel_at_period_end` is pending; the balance would be refunded.
+    PendingCancellation = 452,
+    /// `interval_seconds` is zero.
+    InvalidInterval = 419,
+    /// The subscriber's charge authorization has expired; see `renew_authorization`.
+    AuthorizationExpired = 420,
+    /// Subscriber and merchant are the same address.
+    SelfSubscription = 422,
+    /// `charge_usage` on a subscription created without `usage_enabled`.
+    UsageNotEnabled = 427,
+    /// The merchant has blocked this subscriber (`block_subscriber`).
+    SubscriberBlocked = 423,
+    /// A swapped payout (`withdraw_merchant_funds_as`) returned less than `min_out`.
+    SlippageExceeded = 428,
+    /// A deposit would push the prepaid balance above the subscription's `max_prepaid`.
+    ExceedsMaxPrepaid = 424,
 }
 
 impl Error {
@@ -41,11 +77,30 @@ impl Error {
             Error::InsufficientBalance => 1003,
             Error::Replay => 1004,
             Error::InvalidAmount => 1005,
+            Error::DepositTooSmall => 411,
+            Error::SpendingCapReached => 412,
+            Error::NotStarted => 414,
+            Error::MerchantNotRegistered => 416,
+            Error::TooManySubscriptions => 417,
+            Error::NotInitialized => 408,
+            Error::AlreadyInitialized => 407,
+            Error::NotEmpty => 409,
+            Error::DisputeOpen => 426,
+            Error::IntervalNotAllowed => 451,
+            Error::PendingCancellation => 452,
+            Error::InvalidInterval => 419,
+            Error::AuthorizationExpired => 420,
+            Error::SelfSubscription => 422,
+            Error::UsageNotEnabled => 427,
+            Error::SubscriberBlocked => 423,
+            Error::SlippageExceeded => 428,
+            Error::ExceedsMaxPrepaid => 424,
         }
     }
 }
 
-/// Result of charging one subscription in a batch. Used by [`crate::SubscriptionVault::batch_charge`].
+/// Result of charging one subscription in a batch. Used by
+/// [`crate::SubscriptionVault::batch_charge`].
 #[contracttype]
 #[derive(Clone, Debug)]
 pub struct BatchChargeResult {
@@ -67,13 +122,13 @@ pub struct BatchChargeResult {
 /// - **Paused**: Subscription is temporarily suspended, no charges are processed.
 ///   - Can transition to: `Active`, `Cancelled`
 ///
-/// - **Cancelled**: Subscription is permanently terminated, no further changes allowed.
-///   - No outgoing transitions (terminal state)
+/// - **Cancelled**: Subscription is terminated; no charges, pauses or resumes.
+///   - Can transition to: `Active` (via the subscriber's `reactivate_subscription` only)
 ///
 /// - **InsufficientBalance**: Subscription failed due to insufficient funds.
 ///   - Can transition to: `Active` (after deposit), `Cancelled`
 ///
-/// Invalid transitions (e.g., `Cancelled` -> `Active`) are rejected with
+/// Invalid transitions (e.g., `InsufficientBalance` -> `Paused`) are rejected with
 /// [`Error::InvalidStatusTransition`].
 #[contracttype]
 #[derive(Clone, Debug, Eq, PartialEq)]
@@ -82,21 +137,63 @@ pub enum SubscriptionStatus {
     Active = 0,
     /// Subscription is temporarily paused, no charges processed.
     Paused = 1,
-    /// Subscription is permanently cancelled (terminal state).
+    /// Subscription is cancelled; only `reactivate_subscription` can reopen it.
     Cancelled = 2,
     /// Subscription failed due to insufficient balance for charging.
     InsufficientBalance = 3,
 }
 
+/// Whether cancelling a subscription takes one final charge before refunding the subscriber.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum CancelSettlement {
+    /// No final charge; the full prepaid balance is refunded (default).
+    Off = 0,
+    /// Take a final charge only if the subscription is due (interval elapsed, period uncharged).
+    IfDue = 1,
+    /// Always take a final charge, regardless of the billing schedule.
+    Always = 2,
+    /// Charge the part of `amount` for the time used since `last_payment_timestamp` (at most one
+    /// full interval), e.g. half the amount when cancelling halfway through an interval.
+    Prorated = 3,
+}
+
+/// Who receives the fractional base unit left over when a platform fee is not a whole number.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum RoundingRemainderPolicy {
+    /// The fee rounds down, so the remainder stays with the merchant (default).
+    Merchant = 0,
+    /// The fee rounds up, so the remainder goes to the platform.
+    Platform = 1,
+}
+
+/// How `charge_usage_units` rounds `units * usage_rate` to whole base units.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub enum UsageRounding {
+    /// Round down; fractions are never charged (default).
+    Floor = 0,
+    /// Round up to the next whole base unit.
+    Ceil = 1,
+    /// Round to the nearest base unit, halves up.
+    Nearest = 2,
+}
+
+/// `usage_rate` is priced in 1 / `USAGE_RATE_SCALE` base units per unit.
+pub const USAGE_RATE_SCALE: i128 = 1_000_000;
+
 /// Stores subscription details and current state.
 ///
 /// The `status` field is managed by the state machine. Use the provided
 /// transition helpers to modify status, never set it directly.
 #[contracttype]
-#[derive(Clone, Debug)]
+#[derive(Clone, Debug, Eq, PartialEq)]
 pub struct Subscription {
     pub subscriber: Address,
     pub merchant: Address,
+    /// Payment token for this subscription. Defaults to the token set at `init`.
+    pub token: Address,
     pub amount: i128,
     pub interval_seconds: u64,
     pub last_payment_timestamp: u64,
@@ -104,6 +201,287 @@ pub struct Subscription {
     pub status: SubscriptionStatus,
     pub prepaid_balance: i128,
     pub usage_enabled: bool,
+    /// Minimum accepted `deposit_funds` amount for this subscription. Zero disables the check.
+    pub min_deposit: i128,
+    /// Merchant's cap on the prepaid balance plus unused credits (`set_max_prepaid`); deposits
+    /// and credit purchases that would exceed it fail with `ExceedsMaxPrepaid`. Zero disables.
+    pub max_prepaid: i128,
+    /// Lifetime ceiling on the sum of all charges. Zero means unlimited.
+    pub max_total_charge: i128,
+    /// Sum of all charges taken so far (interval, one-off and settlement).
+    pub total_charged: i128,
+    /// A `LowBalanceEvent` is emitted when a charge leaves `prepaid_balance` below this value.
+    /// Zero disables the warning.
+    pub low_balance_threshold: i128,
+    /// Charges of at least this amount are flagged `receipt` in `SubscriptionChargedEvent` so
+    /// the subscriber is notified; smaller ones are not. Zero flags every charge.
+    pub receipt_threshold: i128,
+    /// Merchant coupon: interval charges are reduced by this many basis points while the
+    /// ledger time is before `discount_expiry`. Zero means no discount.
+    pub discount_bps: u32,
+    pub discount_expiry: u64,
+    /// Seconds after a missed (unfunded) charge during which the subscription is past due but
+    /// stays `Active` so a deposit can recover it. Set at creation from the global default
+    /// unless overridden.
+    pub grace_seconds: u64,
+    /// Consecutive unfunded charge attempts; reset by a successful interval charge.
+    pub failed_charge_count: u32,
+    /// Number of interval charges after which the subscription expires (cancelled with a
+    /// refund). Zero means unlimited.
+    pub max_cycles: u32,
+    /// Interval charges taken so far.
+    pub cycles_charged: u32,
+    /// Usage charges (`charge_usage`) accrue in `accrued_usage` and are only deducted once the
+    /// accrued total reaches this amount. Zero deducts every usage charge immediately.
+    pub min_charge: i128,
+    /// Usage reported but not yet deducted because it is below `min_charge`.
+    pub accrued_usage: i128,
+    /// Manual pauses so far (`pause_subscription`, `pause_all_for_merchant`); automatic pauses
+    /// and no-op repeats are not counted.
+    pub pause_count: u32,
+    /// Manual resumes so far (`resume_subscription`, `resume_all_for_merchant`); automatic
+    /// resumes and no-op repeats are not counted.
+    pub resume_count: u32,
+    /// Prepaid credits left (`buy_credits`). Once credits are bought, each interval charge uses
+    /// one credit instead of `prepaid_balance`.
+    pub credits: u32,
+    /// No charge may happen before this time. The first billing interval runs from here.
+    pub start_timestamp: u64,
+    /// Ledger time the subscription was created.
+    pub created_at: u64,
+    /// Merchant's own reference (invoice or customer id) for reconciliation; empty if unset.
+    /// Echoed in `SubscriptionChargedEvent`.
+    pub external_ref: Bytes,
+    /// Merchant's free-form label, e.g. a plan name or order id (`set_metadata`); empty if unset.
+    pub metadata: String,
+}
+
+/// Seconds per day, for `create_subscription_days`.
+pub const DAY_SECONDS: u64 = 86_400;
+
+/// Length of one release month for vested charges (30 days).
+pub const RELEASE_MONTH_SECONDS: u64 = 2_592_000;
+
+/// Part of a merchant's balance that vests linearly by month from `start` (see
+/// `SubscriptionParams::with_release_months`).
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ReleaseTranche {
+    pub amount: i128,
+    pub start: u64,
+    pub months: u32,
+}
+
+/// Longest accepted `external_ref`, in bytes.
+pub const MAX_EXTERNAL_REF_LEN: u32 = 64;
+
+/// Longest accepted `metadata`, in bytes.
+pub const MAX_METADATA_LEN: u32 = 64;
+
+/// Creation parameters for `create_subscription_from_params`.
+///
+/// Build with [`SubscriptionParams::new`] and the `with_*` methods; each step validates its input
+/// so invalid parameters are rejected when they are constructed. The contract validates again on
+/// entry since callers outside Rust can encode any value.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct SubscriptionParams {
+    pub(crate) subscriber: Address,
+    pub(crate) merchant: Address,
+    pub(crate) amount: i128,
+    pub(crate) interval_seconds: u64,
+    pub(crate) usage_enabled: bool,
+    pub(crate) token: Option<Address>,
+    pub(crate) min_deposit: i128,
+    pub(crate) max_total_charge: i128,
+    pub(crate) grace_seconds: Option<u64>,
+    pub(crate) start_timestamp: Option<u64>,
+    pub(crate) external_ref: Option<Bytes>,
+    pub(crate) metadata: Option<String>,
+    pub(crate) release_months: u32,
+    pub(crate) max_cycles: u32,
+    pub(crate) min_charge: i128,
+    pub(crate) initial_deposit: i128,
+    pub(crate) setup_fee: i128,
+    pub(crate) usage_rate: i128,
+    pub(crate) usage_rounding: UsageRounding,
+}
+
+impl SubscriptionParams {
+    /// Starts a parameter set with the required fields. `amount` must be positive.
+    pub fn new(
+        subscriber: Address,
+        merchant: Address,
+        amount: i128,
+        interval_seconds: u64,
+    ) -> Result<Self, Error> {
+        let params = Self {
+            subscriber,
+            merchant,
+            amount,
+            interval_seconds,
+            usage_enabled: false,
+            token: None,
+            min_deposit: 0,
+            max_total_charge: 0,
+            grace_seconds: None,
+            start_timestamp: None,
+            external_ref: None,
+            metadata: None,
+            release_months: 0,
+            max_cycles: 0,
+            min_charge: 0,
+            initial_deposit: 0,
+            setup_fee: 0,
+            usage_rate: 0,
+            usage_rounding: UsageRounding::Floor,
+        };
+        params.validate()?;
+        Ok(params)
+    }
+
+    pub fn with_usage_enabled(mut self, usage_enabled: bool) -> Self {
+        self.usage_enabled = usage_enabled;
+        self
+    }
+
+    /// Bills in `token` instead of the default token set at `init`.
+    pub fn with_token(mut self, token: Address) -> Self {
+        self.token = Some(token);
+        self
+    }
+
+    /// Sets the per-subscription minimum deposit. Must not be negative.
+    pub fn with_min_deposit(mut self, min_deposit: i128) -> Result<Self, Error> {
+        self.min_deposit = min_deposit;
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Sets the lifetime spending cap. Must not be negative.
+    pub fn with_max_total_charge(mut self, max_total_charge: i128) -> Result<Self, Error> {
+        self.max_total_charge = max_total_charge;
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Overrides the global default grace period for this subscription.
+    pub fn with_grace_seconds(mut self, grace_seconds: u64) -> Self {
+        self.grace_seconds = Some(grace_seconds);
+        self
+    }
+
+    /// Schedules the subscription to start at `start_timestamp` instead of at creation. Earlier
+    /// times are treated as "now".
+    pub fn with_start_timestamp(mut self, start_timestamp: u64) -> Self {
+        self.start_timestamp = Some(start_timestamp);
+        self
+    }
+
+    /// Attaches the merchant's reference; at most [`MAX_EXTERNAL_REF_LEN`] bytes.
+    pub fn with_external_ref(mut self, external_ref: Bytes) -> Result<Self, Error> {
+        self.external_ref = Some(external_ref);
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Attaches the merchant's metadata label; at most [`MAX_METADATA_LEN`] bytes.
+    pub fn with_metadata(mut self, metadata: String) -> Result<Self, Error> {
+        self.metadata = Some(metadata);
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Vests each interval charge to the merchant over `release_months` months of
+    /// [`RELEASE_MONTH_SECONDS`] (e.g. 12 for an annual plan paid up front); the merchant can
+    /// only withdraw the vested part. Zero (the default) releases charges immediately.
+    pub fn with_release_months(mut self, release_months: u32) -> Self {
+        self.release_months = release_months;
+        self
+    }
+
+    /// Expires the subscription after `max_cycles` interval charges: the last one is followed by
+    /// a cancel that refunds the remaining balance. Zero (the default) never expires.
+    pub fn with_max_cycles(mut self, max_cycles: u32) -> Self {
+        self.max_cycles = max_cycles;
+        self
+    }
+
+    /// Sets the smallest usage charge deducted from the balance; smaller reported usage accrues
+    /// until it reaches this amount. Must not be negative.
+    pub fn with_min_charge(mut self, min_charge: i128) -> Result<Self, Error> {
+        self.min_charge = min_charge;
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Funds the subscription from the subscriber in the same call that creates it. The usual
+    /// deposit minimums apply. Must not be negative.
+    pub fn with_initial_deposit(mut self, initial_deposit: i128) -> Result<Self, Error> {
+        self.initial_deposit = initial_deposit;
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// One-time fee charged to the merchant's benefit from the initial deposit at creation,
+    /// before any interval charge. Requires an initial deposit of at least `setup_fee`, so call
+    /// [`Self::with_initial_deposit`] first.
+    pub fn with_setup_fee(mut self, setup_fee: i128) -> Result<Self, Error> {
+        self.setup_fee = setup_fee;
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Prices usage per unit for `charge_usage_units`: each unit costs `usage_rate` /
+    /// [`USAGE_RATE_SCALE`] base units and the total is rounded with `rounding`. Agreed at
+    /// creation so the subscriber consents to the rounding. Must not be negative; zero (the
+    /// default) leaves only `charge_usage` with explicit amounts.
+    pub fn with_usage_rate(
+        mut self,
+        usage_rate: i128,
+        rounding: UsageRounding,
+    ) -> Result<Self, Error> {
+        self.usage_rate = usage_rate;
+        self.usage_rounding = rounding;
+        self.validate()?;
+        Ok(self)
+    }
+
+    /// Checks every field; returns `InvalidAmount` for a non-positive amount, a negative
+    /// minimum deposit, spending cap, minimum charge, initial deposit, setup fee or usage rate, a
+    /// setup fee above the initial deposit, or an over-long external reference or metadata,
+    /// `InvalidInterval` for a zero interval, and `SelfSubscription` when the subscriber is also
+    /// the merchant.
+    pub fn validate(&self) -> Result<(), Error> {
+        if self.subscriber == self.merchant {
+            return Err(Error::SelfSubscription);
+        }
+        if self.interval_seconds == 0 {
+            return Err(Error::InvalidInterval);
+        }
+        if self.amount <= 0
+            || self.min_deposit < 0
+            || self.max_total_charge < 0
+            || self.min_charge < 0
+            || self.initial_deposit < 0
+            || self.setup_fee < 0
+            || self.setup_fee > self.initial_deposit
+            || self.usage_rate < 0
+        {
+            return Err(Error::InvalidAmount);
+        }
+        if let Some(ref r) = self.external_ref {
+            if r.len() > MAX_EXTERNAL_REF_LEN {
+                return Err(Error::InvalidAmount);
+            }
+        }
+        if let Some(ref m) = self.metadata {
+            if m.len() > MAX_METADATA_LEN {
+                return Err(Error::InvalidAmount);
+            }
+        }
+        Ok(())
+    }
 }
 
 // Event types
@@ -115,6 +493,8 @@ pub struct SubscriptionCreatedEvent {
     pub merchant: Address,
     pub amount: i128,
     pub interval_seconds: u64,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
 }
 
 #[contracttype]
@@ -122,7 +502,26 @@ pub struct SubscriptionCreatedEvent {
 pub struct FundsDepositedEvent {
     pub subscription_id: u32,
     pub subscriber: Address,
+    /// Address the tokens came from: the subscriber, or a third party using `deposit_for`.
+    pub payer: Address,
     pub amount: i128,
+    /// Prepaid balance after the deposit.
+    pub new_balance: i128,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
+}
+
+/// Emitted when the subscriber takes unused prepaid balance back with `withdraw_prepaid`.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct PrepaidWithdrawnEvent {
+    pub subscription_id: u32,
+    pub subscriber: Address,
+    pub amount: i128,
+    /// Prepaid balance after the withdrawal.
+    pub new_balance: i128,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
 }
 
 #[contracttype]
@@ -130,7 +529,17 @@ pub struct FundsDepositedEvent {
 pub struct SubscriptionChargedEvent {
     pub subscription_id: u32,
     pub merchant: Address,
+    /// Amount actually debited, after any coupon discount.
     pub amount: i128,
+    /// Contract-wide charge sequence number: grows by one on every successful interval, final
+    /// settlement or usage charge, across all subscriptions.
+    pub charge_nonce: u64,
+    /// Whether the subscriber should get a receipt: `amount >= receipt_threshold`.
+    pub receipt: bool,
+    /// The subscription's `external_ref` (empty if unset).
+    pub external_ref: Bytes,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
 }
 
 #[contracttype]
@@ -139,6 +548,14 @@ pub struct SubscriptionCancelledEvent {
     pub subscription_id: u32,
     pub authorizer: Address,
     pub refund_amount: i128,
+    /// Where the refund went: the subscriber, the `cancel_subscription_to` override, or this
+    /// contract when the refund funds a new subscription (`cancel_and_resubscribe`).
+    pub refund_to: Address,
+    /// Prepaid balance moved to the merchant instead of refunded (`cancel_no_refund`); 0
+    /// otherwise.
+    pub swept_to_merchant: i128,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
 }
 
 #[contracttype]
@@ -146,6 +563,8 @@ pub struct SubscriptionCancelledEvent {
 pub struct SubscriptionPausedEvent {
     pub subscription_id: u32,
     pub authorizer: Address,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
 }
 
 #[contracttype]
@@ -153,13 +572,53 @@ pub struct SubscriptionPausedEvent {
 pub struct SubscriptionResumedEvent {
     pub subscription_id: u32,
     pub authorizer: Address,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
+}
+
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct SubscriptionReactivatedEvent {
+    pub subscription_id: u32,
+    pub subscriber: Address,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
 }
 
 #[contracttype]
 #[derive(Clone, Debug)]
 pub struct MerchantWithdrawalEvent {
     pub merchant: Address,
+    pub token: Address,
     pub amount: i128,
+    /// Charges that credited the merchant in `token` since the previous withdrawal.
+    pub charge_count: u32,
+}
+
+/// Emitted just before `SubscriptionChargedEvent` (or `OneOffChargedEvent`) for the merchant's
+/// share of the charge, after the platform fee.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct MerchantCreditedEvent {
+    pub subscription_id: u32,
+    pub merchant: Address,
+    pub token: Address,
+    pub amount: i128,
+    /// Merchant's balance in `token` after the credit.
+    pub new_balance: i128,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
+}
+
+/// Emitted by `sweep_dust` for each merchant balance below the dust limit that was moved to the
+/// fee collector.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct DustSweptEvent {
+    pub merchant: Address,
+    pub token: Address,
+    pub amount: i128,
+    pub fee_collector: Address,
 }
 
 /// Emitted when a merchant-initiated one-off charge is applied to a subscription.
@@ -169,4 +628,261 @@ pub struct OneOffChargedEvent {
     pub subscription_id: u32,
     pub merchant: Address,
     pub amount: i128,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
+}
+
+/// Emitted when accrued usage reaches the subscription's `min_charge` and is deducted.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct UsageChargedEvent {
+    pub subscription_id: u32,
+    pub merchant: Address,
+    pub amount: i128,
+    pub remaining_balance: i128,
+    /// Contract-wide charge sequence number, shared with `SubscriptionChargedEvent`.
+    pub charge_nonce: u64,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
+}
+
+/// Emitted once by `init` with the deployment's configuration.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct InitializedEvent {
+    pub token: Address,
+    pub admin: Address,
+    pub min_topup: i128,
+    pub require_registration: bool,
+}
+
+/// Emitted when the merchant's setup fee is charged from the initial deposit at creation.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct SetupFeeChargedEvent {
+    pub subscription_id: u32,
+    pub merchant: Address,
+    pub amount: i128,
+    pub remaining_balance: i128,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
+}
+
+/// Emitted by `withdraw_merchant_funds_as` after its `MerchantWithdrawalEvent`, recording the
+/// swap of the withdrawn amount into the payout token.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct PayoutSwappedEvent {
+    pub merchant: Address,
+    pub token_in: Address,
+    pub amount_in: i128,
+    pub token_out: Address,
+    /// Amount of `token_out` paid to the merchant.
+    pub amount_out: i128,
+}
+
+/// Published when `caller` fails the lifecycle authorizer check of `action` (`pause`,
+/// `resume`, `cancel` or `cancel_pe` for `cancel_at_period_end`). The failed call rolls it back,
+/// so it is only visible in diagnostic event streams; it carries no `seq` for that reason.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct UnauthorizedAttemptEvent {
+    pub subscription_id: u32,
+    pub action: Symbol,
+    pub caller: Address,
+}
+
+/// Emitted when accrued platform fees are swept to the fee collector.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct FeesWithdrawnEvent {
+    pub fee_collector: Address,
+    pub token: Address,
+    pub amount: i128,
+}
+
+/// Emitted by `cancel_at_period_end`. The subscription stays `Active` until `effective_at`; the
+/// first charge attempt from then on cancels it (emitting [`SubscriptionCancelledEvent`]).
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct CancelScheduledEvent {
+    pub subscription_id: u32,
+    pub authorizer: Address,
+    /// End of the paid period (`next_charge_timestamp`).
+    pub effective_at: u64,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
+}
+
+/// Emitted when the admin overrides a subscription's `last_payment_timestamp` via
+/// `set_last_payment`. No funds move.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct ScheduleCorrectedEvent {
+    pub subscription_id: u32,
+    pub previous_timestamp: u64,
+    pub new_timestamp: u64,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
+}
+
+/// Emitted when a subscription with auto-renew disabled reaches the end of its paid period and
+/// ends without a charge. Distinct from [`SubscriptionCancelledEvent`] (voluntary churn).
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct SubscriptionLapsedEvent {
+    pub subscription_id: u32,
+    /// End of the last paid cycle (`last_payment_timestamp + interval_seconds`).
+    pub last_cycle_end: u64,
+    /// Prepaid balance returned to the subscriber.
+    pub refund_amount: i128,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
+}
+
+/// Emitted on every interval charge attempt that the prepaid balance cannot cover.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct ChargeFailedEvent {
+    pub subscription_id: u32,
+    /// Amount that was due.
+    pub amount: i128,
+    pub prepaid_balance: i128,
+    /// Consecutive failed attempts including this one.
+    pub failed_charge_count: u32,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
+}
+
+/// Emitted once when a charge drops `prepaid_balance` below `low_balance_threshold`. Re-armed
+/// when a deposit brings the balance back to the threshold or above.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct LowBalanceEvent {
+    pub subscription_id: u32,
+    pub subscriber: Address,
+    pub prepaid_balance: i128,
+    pub threshold: i128,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
+}
+
+/// Emitted by a deposit that brings `prepaid_balance` back to `low_balance_threshold` or above
+/// after a `LowBalanceEvent`, so off-chain systems can clear the warning.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct BalanceRestoredEvent {
+    pub subscription_id: u32,
+    pub subscriber: Address,
+    pub prepaid_balance: i128,
+    pub threshold: i128,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
+}
+
+/// Emitted when the subscriber buys prepaid credits with `buy_credits`.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct CreditsPurchasedEvent {
+    pub subscription_id: u32,
+    pub subscriber: Address,
+    pub count: u32,
+    pub cost: i128,
+    /// Credits held after the purchase.
+    pub credits: u32,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
+}
+
+/// An open charge dispute. The disputed amount is frozen out of the merchant's balance until the
+/// admin resolves it.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Dispute {
+    pub amount: i128,
+    pub opened_at: u64,
+    /// True if opening the dispute paused the subscription (`auto_pause_on_dispute`).
+    pub paused_subscription: bool,
+}
+
+/// Emitted when a subscriber disputes charged funds.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct DisputeOpenedEvent {
+    pub subscription_id: u32,
+    pub subscriber: Address,
+    pub amount: i128,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
+}
+
+/// Emitted when the admin resolves a dispute. `refunded` is true if the frozen amount went back
+/// to the subscriber, false if it was returned to the merchant.
+#[contracttype]
+#[derive(Clone, Debug)]
+pub struct DisputeResolvedEvent {
+    pub subscription_id: u32,
+    pub amount: i128,
+    pub refunded: bool,
+    /// Per-subscription event sequence number, starting at 0 (see `get_event_seq`).
+    pub seq: u64,
+}
+
+/// Breakdown of the next interval charge, from `quote_next_charge`.
+///
+/// `net = base - discount` is what the charge debits from the prepaid balance; the platform fee
+/// is taken out of that, so `merchant_share + platform_fee == net`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct Quote {
+    /// Subscription `amount` before modifiers.
+    pub base: i128,
+    /// Coupon discount active at the charge time.
+    pub discount: i128,
+    pub platform_fee: i128,
+    pub merchant_share: i128,
+    pub net: i128,
+    /// Time the quote applies to (`next_charge_timestamp`, or now if already due).
+    pub charge_timestamp: u64,
+}
+
+/// Contract-wide operational counters returned by `stats`.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct VaultStats {
+    /// Subscriptions created or imported so far.
+    pub total_created: u32,
+    /// Subscriptions currently `Active`.
+    pub active: u32,
+    /// Subscriptions currently `Cancelled`, including purged ones.
+    pub cancelled: u32,
+    /// Charges taken: interval, final settlement, usage, one-off and setup fee charges.
+    /// `get_total_charges` counts only the first three, which carry a `charge_nonce`.
+    pub charges: u64,
+    /// Gross amount of those charges in base units, summed across tokens.
+    pub volume: i128,
+}
+
+/// One interval or settlement charge in a subscription's charge history.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct ChargeRecord {
+    pub timestamp: u64,
+    pub amount: i128,
+}
+
+/// One status change in a subscription's status history.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct StatusRecord {
+    pub timestamp: u64,
+    pub status: SubscriptionStatus,
+}
+
+/// One deposit in a subscription's deposit history.
+#[contracttype]
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub struct DepositRecord {
+    pub timestamp: u64,
+    pub amount: i128,
 }
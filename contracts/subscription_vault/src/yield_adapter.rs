@@ -0,0 +1,125 @@
+//! Optional yield adapter for idle prepaid funds: set_yield_adapter.
+//!
+//! **PRs that only change how prepaid funds are parked in a yield contract should edit this file
+//! only.**
+//!
+//! When the admin configures an adapter, every deposit is forwarded to it and the contract holds
+//! adapter shares instead of the tokens. Subscription balances stay denominated in tokens: the
+//! contract tracks, per token, the principal it has placed with the adapter and the shares it
+//! received. Whenever prepaid funds leave the prepaid pool (a charge, refund or withdrawal; see
+//! `adjust_total_prepaid`) the same amount is pulled back, capped at the principal, so merchant
+//! and fee balances and refunds are always paid from tokens held by this contract. Yield beyond
+//! the principal stays with the adapter.
+
+use crate::admin::require_admin;
+use crate::subscription::get_tracked_tokens;
+use crate::types::Error;
+use soroban_sdk::{contractclient, symbol_short, token, Address, Env, Symbol};
+
+const KEY_ADAPTER: Symbol = symbol_short!("yadapter");
+const KEY_PRINCIPAL: Symbol = symbol_short!("yprin");
+const KEY_SHARES: Symbol = symbol_short!("yshares");
+
+/// Interface a yield adapter contract must implement.
+#[contractclient(name = "YieldAdapterClient")]
+pub trait YieldAdapter {
+    /// Called after `amount` of `token` was transferred to the adapter; credits `depositor` and
+    /// returns the shares minted.
+    fn deposit(env: Env, depositor: Address, token: Address, amount: i128) -> i128;
+    /// Transfers `amount` of `token` back to `depositor` and returns the shares burned.
+    fn withdraw(env: Env, depositor: Address, token: Address, amount: i128) -> i128;
+}
+
+pub fn get_yield_adapter(env: &Env) -> Option<Address> {
+    env.storage().instance().get(&KEY_ADAPTER)
+}
+
+/// `(principal, shares)` this contract holds with the adapter in `token`.
+pub fn get_yield_position(env: &Env, token: &Address) -> (i128, i128) {
+    let principal = env
+        .storage()
+        .instance()
+        .get(&(KEY_PRINCIPAL, token.clone()))
+        .unwrap_or(0);
+    let shares = env
+        .storage()
+        .instance()
+        .get(&(KEY_SHARES, token.clone()))
+        .unwrap_or(0);
+    (principal, shares)
+}
+
+fn set_position(env: &Env, token: &Address, principal: i128, shares: i128) {
+    env.storage()
+        .instance()
+        .set(&(KEY_PRINCIPAL, token.clone()), &principal);
+    env.storage()
+        .instance()
+        .set(&(KEY_SHARES, token.clone()), &shares);
+}
+
+/// Sets or clears (`None`) the adapter. Admin only. The principal held with the previous adapter
+/// is withdrawn first, so later deposits start a fresh position.
+pub fn do_set_yield_adapter(
+    env: &Env,
+    admin: Address,
+    adapter: Option<Address>,
+) -> Result<(), Error> {
+    admin.require_auth();
+    if admin != require_admin(env)? {
+        return Err(Error::Unauthorized);
+    }
+    for token in get_tracked_tokens(env).iter() {
+        let (principal, _) = get_yield_position(env, &token);
+        withdraw_from_adapter(env, &token, principal)?;
+    }
+    match adapter {
+        Some(a) => env.storage().instance().set(&KEY_ADAPTER, &a),
+        None => env.storage().instance().remove(&KEY_ADAPTER),
+    }
+    Ok(())
+}
+
+/// Forwards `amount` of `token`, already held by this contract, to the adapter. No-op without
+/// an adapter.
+pub fn deposit_to_adapter(env: &Env, token: &Address, amount: i128) -> Result<(), Error> {
+    let Some(adapter) = get_yield_adapter(env) else {
+        return Ok(());
+    };
+    if amount <= 0 {
+        return Ok(());
+    }
+    let vault = env.current_contract_address();
+    token::Client::new(env, token).transfer(&vault, &adapter, &amount);
+    let minted = YieldAdapterClient::new(env, &adapter).deposit(&vault, token, &amount);
+    let (principal, shares) = get_yield_position(env, token);
+    set_position(
+        env,
+        token,
+        principal.checked_add(amount).ok_or(Error::Overflow)?,
+        shares.checked_add(minted).ok_or(Error::Overflow)?,
+    );
+    Ok(())
+}
+
+/// Pulls up to `amount` of `token` back from the adapter, never more than the principal placed
+/// there. No-op without an adapter.
+pub fn withdraw_from_adapter(env: &Env, token: &Address, amount: i128) -> Result<(), Error> {
+    let Some(adapter) = get_yield_adapter(env) else {
+        return Ok(());
+    };
+    let (principal, shares) = get_yield_position(env, token);
+    let amount = amount.min(principal);
+    if amount <= 0 {
+        return Ok(());
+    }
+    let vault = env.current_contract_address();
+    let burned = YieldAdapterClient::new(env, &adapter).withdraw(&vault, token, &amount);
+    set_position(
+        env,
+        token,
+        principal - amount,
+        shares.saturating_sub(burned).max(0),
+    );
+    Ok(())
+}